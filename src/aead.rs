@@ -0,0 +1,42 @@
+use std::error::Error;
+use std::fmt;
+
+/// The error from opening a ciphertext that fails authentication. It says
+/// nothing more, since the plaintext of a forgery must never be released.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AeadError;
+
+impl fmt::Display for AeadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "authentication failed")
+    }
+}
+
+impl Error for AeadError {}
+
+/// Authenticated encryption with associated data: `seal` encrypts a
+/// plaintext and authenticates it together with data sent in the clear, and
+/// `open` returns the plaintext only if neither has been tampered with.
+///
+/// A nonce must never be used twice with the same key.
+pub trait Aead: Sized {
+    /// Key length in bytes.
+    const KEY_SIZE: usize;
+
+    /// Nonce length in bytes.
+    const NONCE_SIZE: usize;
+
+    /// Tag length in bytes.
+    const TAG_SIZE: usize;
+
+    /// Sets up the cipher; panics unless key is KEY_SIZE bytes.
+    fn new(key: &[u8]) -> Self;
+
+    /// Returns the ciphertext followed by the tag. Panics unless nonce is
+    /// NONCE_SIZE bytes.
+    fn seal(&self, nonce: &[u8], associated_data: &[u8], plaintext: &[u8]) -> Vec<u8>;
+
+    /// Checks the tag at the end of ciphertext, and if it matches returns
+    /// the plaintext. Panics unless nonce is NONCE_SIZE bytes.
+    fn open(&self, nonce: &[u8], associated_data: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, AeadError>;
+}
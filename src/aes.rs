@@ -0,0 +1,438 @@
+use super::aead::{Aead, AeadError};
+use super::hmac::constant_time_eq;
+
+/// Multiplication by x in GF(2^8) modulo x^8 + x^4 + x^3 + x + 1.
+const fn xtime(a: u8) -> u8 {
+    (a << 1) ^ (if a & 0x80 != 0 { 0x1b } else { 0 })
+}
+
+const fn gf256_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0;
+    while b != 0 {
+        if b & 1 != 0 {
+            product ^= a;
+        }
+        a = xtime(a);
+        b >>= 1;
+    }
+    product
+}
+
+/// The S-box from FIPS 197, section 5.1.1: inversion in GF(2^8) followed by
+/// an affine map. Built at compile time rather than typed in.
+const SBOX: [u8; 256] = {
+    let mut sbox = [0u8; 256];
+    let mut a = 0;
+    while a < 256 {
+        // a^254 = a^-1, with 0 going to 0
+        let mut inverse = 1u8;
+        let mut i = 0;
+        while i < 254 {
+            inverse = gf256_mul(inverse, a as u8);
+            i += 1;
+        }
+        let b = inverse;
+        sbox[a] = b ^ b.rotate_left(1) ^ b.rotate_left(2) ^ b.rotate_left(3) ^ b.rotate_left(4) ^ 0x63;
+        a += 1;
+    }
+    sbox
+};
+
+const INVERSE_SBOX: [u8; 256] = {
+    let mut inverse = [0u8; 256];
+    let mut a = 0;
+    while a < 256 {
+        inverse[SBOX[a] as usize] = a as u8;
+        a += 1;
+    }
+    inverse
+};
+
+type Block = [u8; 16];
+
+/// The AES block cipher from FIPS 197, with 128, 192 or 256-bit keys.
+///
+/// This is the textbook byte-oriented version; its table lookups depend on
+/// secret data, so it is not protected against cache-timing attacks.
+#[derive(Clone, Debug)]
+pub struct Aes {
+    round_keys: Vec<Block>,
+}
+
+impl Aes {
+    /// Expands the key (16, 24 or 32 bytes) into the round keys.
+    pub fn new(key: &[u8]) -> Aes {
+        assert!(matches!(key.len(), 16 | 24 | 32), "AES keys are 16, 24 or 32 bytes.");
+        let key_words = key.len() / 4;
+        let rounds = key_words + 6;
+
+        let mut words: Vec<[u8; 4]> = key.chunks_exact(4).map(|w| w.try_into().unwrap()).collect();
+        let mut round_constant = 1u8;
+        for i in key_words..4 * (rounds + 1) {
+            let mut word = words[i - 1];
+            if i % key_words == 0 {
+                word.rotate_left(1);
+                word = word.map(|b| SBOX[b as usize]);
+                word[0] ^= round_constant;
+                round_constant = xtime(round_constant);
+            } else if key_words > 6 && i % key_words == 4 {
+                word = word.map(|b| SBOX[b as usize]);
+            }
+            for (b, &previous) in word.iter_mut().zip(words[i - key_words].iter()) {
+                *b ^= previous;
+            }
+            words.push(word);
+        }
+
+        let round_keys = words.chunks_exact(4).map(|four| four.concat().try_into().unwrap()).collect();
+        Aes { round_keys }
+    }
+
+    fn add_round_key(state: &mut Block, round_key: &Block) {
+        for (s, k) in state.iter_mut().zip(round_key.iter()) {
+            *s ^= k;
+        }
+    }
+
+    /// Rotates row r of the column-major state left by r (or right, for the
+    /// inverse).
+    fn shift_rows(state: &mut Block, inverse: bool) {
+        let original = *state;
+        for row in 1..4 {
+            for column in 0..4 {
+                let from = if inverse { (column + 4 - row) % 4 } else { (column + row) % 4 };
+                state[4 * column + row] = original[4 * from + row];
+            }
+        }
+    }
+
+    fn mix_columns(state: &mut Block, coefficients: [u8; 4]) {
+        for column in state.chunks_exact_mut(4) {
+            let original: [u8; 4] = (*column).try_into().unwrap();
+            for (row, byte) in column.iter_mut().enumerate() {
+                *byte = (0..4).fold(0, |acc, i| acc ^ gf256_mul(coefficients[(i + 4 - row) % 4], original[i]));
+            }
+        }
+    }
+
+    /// Encrypts one 16-byte block.
+    pub fn encrypt_block(&self, block: &Block) -> Block {
+        let rounds = self.round_keys.len() - 1;
+        let mut state = *block;
+        Aes::add_round_key(&mut state, &self.round_keys[0]);
+        for round in 1..=rounds {
+            state = state.map(|b| SBOX[b as usize]);
+            Aes::shift_rows(&mut state, false);
+            if round != rounds {
+                Aes::mix_columns(&mut state, [2, 3, 1, 1]);
+            }
+            Aes::add_round_key(&mut state, &self.round_keys[round]);
+        }
+        state
+    }
+
+    /// Decrypts one 16-byte block.
+    pub fn decrypt_block(&self, block: &Block) -> Block {
+        let rounds = self.round_keys.len() - 1;
+        let mut state = *block;
+        Aes::add_round_key(&mut state, &self.round_keys[rounds]);
+        for round in (0..rounds).rev() {
+            Aes::shift_rows(&mut state, true);
+            state = state.map(|b| INVERSE_SBOX[b as usize]);
+            Aes::add_round_key(&mut state, &self.round_keys[round]);
+            if round != 0 {
+                Aes::mix_columns(&mut state, [14, 11, 13, 9]);
+            }
+        }
+        state
+    }
+
+    /// Encrypts or decrypts data in place in CTR mode (SP 800-38A, section
+    /// 6.5), treating the whole counter block as a big-endian integer.
+    pub fn ctr_xor(&self, initial_counter: &Block, data: &mut [u8]) {
+        let counter = u128::from_be_bytes(*initial_counter);
+        self.keystream_xor(data, |i| counter.wrapping_add(i as u128));
+    }
+
+    /// XORs data with the encryptions of the counter blocks counter(0),
+    /// counter(1), ...
+    fn keystream_xor<F: Fn(usize) -> u128>(&self, data: &mut [u8], counter: F) {
+        for (i, chunk) in data.chunks_mut(16).enumerate() {
+            let keystream = self.encrypt_block(&counter(i).to_be_bytes());
+            for (byte, &k) in chunk.iter_mut().zip(keystream.iter()) {
+                *byte ^= k;
+            }
+        }
+    }
+}
+
+/// Multiplication in GF(2^128) as GCM defines it (SP 800-38D, section 6.3):
+/// modulo x^128 + x^7 + x^2 + x + 1, with the bits of each byte string read
+/// so that the first bit is the coefficient of x^0.
+pub fn gf128_mul(x: u128, y: u128) -> u128 {
+    const R: u128 = 0xe1 << 120;
+    let mut product = 0;
+    let mut v = y;
+    for i in (0..128).rev() {
+        if (x >> i) & 1 == 1 {
+            product ^= v;
+        }
+        v = if v & 1 == 1 { (v >> 1) ^ R } else { v >> 1 };
+    }
+    product
+}
+
+/// The GHASH function, fed with data in zero-padded 16-byte blocks.
+struct Ghash {
+    h: u128,
+    y: u128,
+}
+
+impl Ghash {
+    fn update_padded(&mut self, data: &[u8]) {
+        for chunk in data.chunks(16) {
+            let mut block = [0u8; 16];
+            block[..chunk.len()].copy_from_slice(chunk);
+            self.y = gf128_mul(self.y ^ u128::from_be_bytes(block), self.h);
+        }
+    }
+}
+
+/// AES in Galois/Counter Mode (SP 800-38D) with 96-bit nonces and 128-bit
+/// tags, for a KEY_SIZE-byte AES key.
+#[derive(Clone, Debug)]
+pub struct AesGcm<const KEY_SIZE: usize> {
+    cipher: Aes,
+    /// The hash subkey, the encryption of the zero block.
+    h: u128,
+}
+
+pub type Aes128Gcm = AesGcm<16>;
+pub type Aes256Gcm = AesGcm<32>;
+
+/// The longest plaintext GCM allows under one nonce, 2^39 - 256 bits: the
+/// low 32 bits of the counter run from 2 to 2^32 - 1 before they would wrap
+/// (SP 800-38D, section 5.2.1.1).
+const MAX_TEXT_SIZE: u64 = ((1 << 32) - 2) * 16;
+
+/// The longest associated data GCM allows, 2^64 - 1 bits rounded down to
+/// whole bytes.
+const MAX_ASSOCIATED_DATA_SIZE: u64 = (1 << 61) - 1;
+
+impl<const KEY_SIZE: usize> AesGcm<KEY_SIZE> {
+    /// Whether SP 800-38D allows associated data and a plaintext or
+    /// ciphertext body of these sizes in bytes.
+    fn supports_lengths(associated_data: u64, text: u64) -> bool {
+        associated_data <= MAX_ASSOCIATED_DATA_SIZE && text <= MAX_TEXT_SIZE
+    }
+
+    /// The pre-counter block J0 for a 96-bit nonce: the nonce, then 1.
+    fn initial_counter(nonce: &[u8]) -> u128 {
+        assert_eq!(12, nonce.len(), "AES-GCM nonces are 12 bytes.");
+        let mut block = [0u8; 16];
+        block[..12].copy_from_slice(nonce);
+        block[15] = 1;
+        u128::from_be_bytes(block)
+    }
+
+    /// GCTR from J0 + 1, incrementing only the low 32 bits.
+    fn gctr(&self, initial_counter: u128, data: &mut [u8]) {
+        assert!(data.len() as u64 <= MAX_TEXT_SIZE, "Data too long for the AES-GCM block counter.");
+        let high = initial_counter & !(u32::MAX as u128);
+        let low = initial_counter as u32;
+        self.cipher.keystream_xor(data, |i| high | (low + 1 + i as u32) as u128);
+    }
+
+    fn tag(&self, initial_counter: u128, associated_data: &[u8], ciphertext: &[u8]) -> Block {
+        let mut ghash = Ghash { h: self.h, y: 0 };
+        ghash.update_padded(associated_data);
+        ghash.update_padded(ciphertext);
+        let lengths = ((associated_data.len() as u128 * 8) << 64) | (ciphertext.len() as u128 * 8);
+        ghash.update_padded(&lengths.to_be_bytes());
+
+        let mask = self.cipher.encrypt_block(&initial_counter.to_be_bytes());
+        (ghash.y ^ u128::from_be_bytes(mask)).to_be_bytes()
+    }
+}
+
+impl<const KEY_SIZE: usize> Aead for AesGcm<KEY_SIZE> {
+    const KEY_SIZE: usize = KEY_SIZE;
+    const NONCE_SIZE: usize = 12;
+    const TAG_SIZE: usize = 16;
+
+    fn new(key: &[u8]) -> AesGcm<KEY_SIZE> {
+        assert_eq!(KEY_SIZE, key.len(), "Wrong key length for this AES-GCM variant.");
+        let cipher = Aes::new(key);
+        let h = u128::from_be_bytes(cipher.encrypt_block(&[0; 16]));
+        AesGcm { cipher, h }
+    }
+
+    fn seal(&self, nonce: &[u8], associated_data: &[u8], plaintext: &[u8]) -> Vec<u8> {
+        assert!(
+            Self::supports_lengths(associated_data.len() as u64, plaintext.len() as u64),
+            "Plaintext or associated data too long for AES-GCM.");
+        let initial_counter = Self::initial_counter(nonce);
+        let mut ciphertext = plaintext.to_vec();
+        self.gctr(initial_counter, &mut ciphertext);
+        let tag = self.tag(initial_counter, associated_data, &ciphertext);
+        ciphertext.extend_from_slice(&tag);
+        ciphertext
+    }
+
+    fn open(&self, nonce: &[u8], associated_data: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, AeadError> {
+        let initial_counter = Self::initial_counter(nonce);
+        if ciphertext.len() < Self::TAG_SIZE {
+            return Err(AeadError);
+        }
+        let (body, tag) = ciphertext.split_at(ciphertext.len() - Self::TAG_SIZE);
+        if !Self::supports_lengths(associated_data.len() as u64, body.len() as u64) {
+            return Err(AeadError);
+        }
+        if !constant_time_eq(&self.tag(initial_counter, associated_data, body), tag) {
+            return Err(AeadError);
+        }
+
+        let mut plaintext = body.to_vec();
+        self.gctr(initial_counter, &mut plaintext);
+        Ok(plaintext)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{bytes, hex};
+
+    #[test]
+    fn test_sbox() {
+        // FIPS 197, figure 7
+        assert_eq!([0x63, 0x7c, 0x77, 0x7b], SBOX[..4]);
+        assert_eq!(0xed, SBOX[0x53]);
+        assert_eq!(0x16, SBOX[0xff]);
+        assert!((0..256).all(|a| INVERSE_SBOX[SBOX[a] as usize] as usize == a));
+    }
+
+    #[test]
+    fn test_key_expansion() {
+        // FIPS 197, appendix A.1
+        let aes = Aes::new(&bytes("2b7e151628aed2a6abf7158809cf4f3c"));
+        assert_eq!(11, aes.round_keys.len());
+        assert_eq!("a0fafe1788542cb123a339392a6c7605", hex(&aes.round_keys[1]));
+        assert_eq!("d014f9a8c9ee2589e13f0cc8b6630ca6", hex(&aes.round_keys[10]));
+    }
+
+    #[test]
+    fn test_aes_fips197() {
+        // FIPS 197, appendix C
+        let plaintext = bytes("00112233445566778899aabbccddeeff").try_into().unwrap();
+        let key: Vec<u8> = (0..32).collect();
+        for (key_size, expected) in [
+            (16, "69c4e0d86a7b0430d8cdb78070b4c55a"),
+            (24, "dda97ca4864cdfe06eaf70a0ec0d7191"),
+            (32, "8ea2b7ca516745bfeafc49904b496089"),
+        ] {
+            let aes = Aes::new(&key[..key_size]);
+            let ciphertext = aes.encrypt_block(&plaintext);
+            assert_eq!(expected, hex(&ciphertext));
+            assert_eq!(plaintext, aes.decrypt_block(&ciphertext));
+        }
+    }
+
+    #[test]
+    fn test_ctr() {
+        // SP 800-38A, appendix F.5.1
+        let aes = Aes::new(&bytes("2b7e151628aed2a6abf7158809cf4f3c"));
+        let counter = bytes("f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff").try_into().unwrap();
+        let plaintext = bytes(concat!(
+            "6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51",
+            "30c81c46a35ce411e5fbc1191a0a52eff69f2445df4f9b17ad2b417be66c3710"));
+        let mut data = plaintext.clone();
+        aes.ctr_xor(&counter, &mut data);
+        assert_eq!(
+            concat!("874d6191b620e3261bef6864990db6ce9806f66b7970fdff8617187bb9fffdff",
+                    "5ae4df3edbd5d35e5b4f09020db03eab1e031dda2fbe03d1792170a0f3009cee"),
+            hex(&data));
+
+        aes.ctr_xor(&counter, &mut data);
+        assert_eq!(plaintext, data);
+    }
+
+    #[test]
+    fn test_gf128_mul() {
+        // the bit-reflected convention puts 1 at the top bit
+        let one = 1 << 127;
+        let x = 0x66e94bd4ef8a2c3b884cfa59ca342b2e;
+        assert_eq!(x, gf128_mul(x, one));
+        assert_eq!(x, gf128_mul(one, x));
+        assert_eq!(0, gf128_mul(x, 0));
+        let y = 0x0388dace60b6a392f328c2b971b2fe78;
+        assert_eq!(gf128_mul(x, y), gf128_mul(y, x));
+        // x^127 * x = x^128 = x^7 + x^2 + x + 1
+        assert_eq!(0xe1 << 120, gf128_mul(1, 1 << 126));
+    }
+
+    #[test]
+    fn test_gcm() {
+        // test cases 2, 4, 13 and 16 from McGrew and Viega's GCM specification
+        let cipher = Aes128Gcm::new(&[0; 16]);
+        assert_eq!(
+            "0388dace60b6a392f328c2b971b2fe78ab6e47d42cec13bdf53a67b21257bddf",
+            hex(&cipher.seal(&[0; 12], &[], &[0; 16])));
+
+        let key = bytes("feffe9928665731c6d6a8f9467308308");
+        let nonce = bytes("cafebabefacedbaddecaf888");
+        let plaintext = bytes(concat!(
+            "d9313225f88406e5a55909c5aff5269a86a7a9531534f7da2e4c303d8a318a72",
+            "1c3c0c95956809532fcf0e2449a6b525b16aedf5aa0de657ba637b39"));
+        let associated_data = bytes("feedfacedeadbeeffeedfacedeadbeefabaddad2");
+        let cipher = Aes128Gcm::new(&key);
+        let sealed = cipher.seal(&nonce, &associated_data, &plaintext);
+        assert_eq!(
+            concat!("42831ec2217774244b7221b784d0d49ce3aa212f2c02a4e035c17e2329aca12e",
+                    "21d514b25466931c7d8f6a5aac84aa051ba30b396a0aac973d58e091",
+                    "5bc94fbc3221a5db94fae95ae7121a47"),
+            hex(&sealed));
+        assert_eq!(Ok(plaintext.clone()), cipher.open(&nonce, &associated_data, &sealed));
+
+        let cipher = Aes256Gcm::new(&[0; 32]);
+        assert_eq!("530f8afbc74536b9a963b4f1c4cb738b", hex(&cipher.seal(&[0; 12], &[], &[])));
+
+        let cipher = Aes256Gcm::new(&[key.clone(), key].concat());
+        let sealed = cipher.seal(&nonce, &associated_data, &plaintext);
+        assert_eq!(
+            concat!("522dc1f099567d07f47f37a32a84427d643a8cdcbfe5c0c97598a2bd2555d1aa",
+                    "8cb08e48590dbb3da7b08b1056828838c5f61e6393ba7a0abcc9f662",
+                    "76fc6ece0f4e1768cddf8853bb2d551b"),
+            hex(&sealed));
+        assert_eq!(Ok(plaintext), cipher.open(&nonce, &associated_data, &sealed));
+    }
+
+    #[test]
+    fn test_gcm_rejects_tampering() {
+        let cipher = Aes256Gcm::new(&[7; 32]);
+        let nonce = [1u8; 12];
+        let sealed = cipher.seal(&nonce, b"header", b"attack at dawn");
+
+        let mut flipped = sealed.clone();
+        flipped[0] ^= 1;
+        assert_eq!(Err(AeadError), cipher.open(&nonce, b"header", &flipped));
+        assert_eq!(Err(AeadError), cipher.open(&nonce, b"", &sealed));
+        assert_eq!(Err(AeadError), cipher.open(&[2u8; 12], b"header", &sealed));
+        assert_eq!(Err(AeadError), cipher.open(&nonce, b"header", &sealed[..10]));
+    }
+
+    #[test]
+    fn test_gcm_length_limits() {
+        assert_eq!((1 << 39) - 256, MAX_TEXT_SIZE * 8);
+        assert!(Aes128Gcm::supports_lengths(0, 0));
+        assert!(Aes128Gcm::supports_lengths(MAX_ASSOCIATED_DATA_SIZE, MAX_TEXT_SIZE));
+        assert!(!Aes128Gcm::supports_lengths(0, MAX_TEXT_SIZE + 1));
+        assert!(!Aes128Gcm::supports_lengths(1 << 61, 0));
+
+        // the last block allowed takes the low 32 bits of the counter to
+        // 2^32 - 1 without wrapping
+        let initial_counter = Aes128Gcm::initial_counter(&[0xff; 12]);
+        assert_eq!(1, initial_counter as u32);
+        assert_eq!(u32::MAX, initial_counter as u32 + (MAX_TEXT_SIZE / 16) as u32);
+    }
+}
@@ -0,0 +1,925 @@
+use rand::Rng;
+use std::cmp::Ordering;
+use std::error::Error;
+use std::fmt;
+use std::ops::{
+    Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Rem, RemAssign, Shl,
+    ShlAssign, Shr, ShrAssign, Sub, SubAssign,
+};
+use std::str::FromStr;
+
+use super::integer::{SignedInteger, UnsignedInteger};
+
+/// An arbitrary-precision unsigned integer.
+///
+/// Stored as little-endian base-2^64 limbs with no trailing zero limbs, so
+/// that zero is the empty vector and every value has exactly one
+/// representation (which lets us derive equality and hashing).
+#[derive(Clone, Default, PartialEq, Eq, Hash)]
+pub struct BigUint {
+    limbs: Vec<u64>,
+}
+
+/// An arbitrary-precision signed integer, in sign-magnitude form.
+///
+/// Zero is never negative.
+#[derive(Clone, Default, PartialEq, Eq, Hash)]
+pub struct BigInt {
+    negative: bool,
+    magnitude: BigUint,
+}
+
+/// Error returned when parsing a big integer from a string fails.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseBigIntError {
+    Empty,
+    InvalidDigit,
+    InvalidRadix,
+}
+
+impl fmt::Display for ParseBigIntError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseBigIntError::Empty => write!(f, "cannot parse integer from empty string"),
+            ParseBigIntError::InvalidDigit => write!(f, "invalid digit found in string"),
+            ParseBigIntError::InvalidRadix => write!(f, "radix must be between 2 and 36"),
+        }
+    }
+}
+
+impl Error for ParseBigIntError {}
+
+impl BigUint {
+    /// Builds an integer from little-endian base-2^64 limbs.
+    pub fn from_limbs(limbs: Vec<u64>) -> BigUint {
+        let mut n = BigUint { limbs };
+        n.normalize();
+        n
+    }
+
+    /// Returns the little-endian base-2^64 limbs of this integer.
+    pub fn limbs(&self) -> &[u64] {
+        &self.limbs
+    }
+
+    fn normalize(&mut self) {
+        while let Some(&0) = self.limbs.last() {
+            self.limbs.pop();
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    pub fn is_even(&self) -> bool {
+        self.limbs.first().is_none_or(|&l| l & 1 == 0)
+    }
+
+    /// Returns the number of significant bits (0 for zero).
+    pub fn bits(&self) -> u32 {
+        match self.limbs.last() {
+            None => 0,
+            Some(&top) => {
+                64 * (self.limbs.len() as u32 - 1) + (64 - top.leading_zeros())
+            }
+        }
+    }
+
+    /// Returns whether bit i (counting from the least significant) is set.
+    pub fn bit(&self, i: u32) -> bool {
+        match self.limbs.get((i / 64) as usize) {
+            None => false,
+            Some(&l) => (l >> (i % 64)) & 1 == 1,
+        }
+    }
+
+    /// Returns the value as a u128, if it fits.
+    pub fn to_u128(&self) -> Option<u128> {
+        match self.limbs.len() {
+            0 => Some(0),
+            1 => Some(self.limbs[0] as u128),
+            2 => Some(self.limbs[0] as u128 | (self.limbs[1] as u128) << 64),
+            _ => None,
+        }
+    }
+
+    /// Returns self raised to the e-th power.
+    pub fn pow(&self, mut e: u32) -> BigUint {
+        let mut a = self.clone();
+        let mut b = BigUint::from(1u64);
+
+        while e > 0 {
+            if e % 2 == 1 {
+                b = &b * &a;
+            }
+            e /= 2;
+            if e > 0 {
+                a = &a * &a;
+            }
+        }
+
+        b
+    }
+
+    /// Returns (q, r) such that self = q * divisor + r and r < divisor.
+    ///
+    /// Uses Knuth's Algorithm D (TAOCP vol. 2, section 4.3.1), with u128
+    /// intermediates for the two-limb by one-limb estimates.
+    pub fn div_rem(&self, divisor: &BigUint) -> (BigUint, BigUint) {
+        assert!(!divisor.is_zero(), "attempt to divide by zero");
+
+        if self < divisor {
+            return (BigUint::default(), self.clone());
+        }
+        if divisor.limbs.len() == 1 {
+            let (q, r) = div_rem_limb(&self.limbs, divisor.limbs[0]);
+            return (BigUint::from_limbs(q), BigUint::from(r));
+        }
+
+        // Normalize so that the top limb of the divisor has its high bit set;
+        // this keeps each quotient digit estimate off by at most 2.
+        let shift = divisor.limbs.last().unwrap().leading_zeros();
+        let v = (divisor << shift).limbs;
+        let mut u = (self << shift).limbs;
+        u.resize(self.limbs.len() + 1, 0);
+
+        let n = v.len();
+        let m = u.len() - n - 1;
+        let mut q = vec![0u64; m + 1];
+        let v_top = v[n - 1] as u128;
+        let v_next = v[n - 2] as u128;
+        let base = 1u128 << 64;
+
+        for j in (0..=m).rev() {
+            let numerator = (u[j + n] as u128) << 64 | u[j + n - 1] as u128;
+            let mut q_hat = numerator / v_top;
+            let mut r_hat = numerator % v_top;
+            while q_hat >= base
+                || q_hat * v_next > (r_hat << 64 | u[j + n - 2] as u128) {
+                q_hat -= 1;
+                r_hat += v_top;
+                if r_hat >= base { break; }
+            }
+
+            // u[j..=j+n] -= q_hat * v
+            let mut borrow = 0i128;
+            let mut carry = 0u128;
+            for i in 0..n {
+                let p = q_hat * v[i] as u128 + carry;
+                carry = p >> 64;
+                let t = u[i + j] as i128 - borrow - (p as u64) as i128;
+                u[i + j] = t as u64;
+                borrow = (t < 0) as i128;
+            }
+            let t = u[j + n] as i128 - borrow - carry as i128;
+            u[j + n] = t as u64;
+
+            if t < 0 {
+                // q_hat was one too large; add the divisor back
+                q_hat -= 1;
+                let mut carry = 0u128;
+                for i in 0..n {
+                    let s = u[i + j] as u128 + v[i] as u128 + carry;
+                    u[i + j] = s as u64;
+                    carry = s >> 64;
+                }
+                u[j + n] = u[j + n].wrapping_add(carry as u64);
+            }
+
+            q[j] = q_hat as u64;
+        }
+
+        u.truncate(n);
+        (BigUint::from_limbs(q), BigUint::from_limbs(u) >> shift)
+    }
+
+    /// Returns (q, r) such that self = q * divisor + r and r < divisor, for a
+    /// single-limb divisor. Cheaper than `div_rem`.
+    pub fn div_rem_u64(&self, divisor: u64) -> (BigUint, u64) {
+        assert!(divisor != 0, "attempt to divide by zero");
+
+        let (q, r) = div_rem_limb(&self.limbs, divisor);
+        (BigUint::from_limbs(q), r)
+    }
+
+    /// Returns self mod divisor, for a single-limb divisor.
+    pub fn rem_u64(&self, divisor: u64) -> u64 {
+        assert!(divisor != 0, "attempt to divide by zero");
+
+        let mut r = 0u128;
+        for &l in self.limbs.iter().rev() {
+            r = (r << 64 | l as u128) % divisor as u128;
+        }
+        r as u64
+    }
+
+    /// Parses an integer from a string of digits in the given radix (2-36).
+    pub fn from_str_radix(s: &str, radix: u32) -> Result<BigUint, ParseBigIntError> {
+        if !(2..=36).contains(&radix) {
+            return Err(ParseBigIntError::InvalidRadix);
+        }
+        if s.is_empty() {
+            return Err(ParseBigIntError::Empty);
+        }
+
+        let mut n = BigUint::default();
+        for c in s.chars() {
+            let d = c.to_digit(radix).ok_or(ParseBigIntError::InvalidDigit)?;
+            mul_add_limb(&mut n.limbs, radix as u64, d as u64);
+        }
+        n.normalize();
+
+        Ok(n)
+    }
+
+    /// Returns a uniformly random integer in [0, bound).
+    pub fn random_below<R: Rng + ?Sized>(rng: &mut R, bound: &BigUint) -> BigUint {
+        assert!(!bound.is_zero(), "Cannot sample from an empty range.");
+
+        let bits = bound.bits();
+        let top_mask = match bits % 64 {
+            0 => u64::MAX,
+            r => (1u64 << r) - 1,
+        };
+
+        // rejection sampling: draw numbers with the same bit length as bound
+        loop {
+            let mut limbs: Vec<u64> = (0..bound.limbs.len()).map(|_| rng.gen()).collect();
+            *limbs.last_mut().unwrap() &= top_mask;
+            let n = BigUint::from_limbs(limbs);
+            if &n < bound {
+                return n;
+            }
+        }
+    }
+}
+
+/// Divides a little-endian limb vector by a single limb, returning the
+/// (unnormalized) quotient limbs and the remainder.
+fn div_rem_limb(a: &[u64], d: u64) -> (Vec<u64>, u64) {
+    let mut q = vec![0u64; a.len()];
+    let mut r = 0u128;
+
+    for i in (0..a.len()).rev() {
+        let cur = r << 64 | a[i] as u128;
+        q[i] = (cur / d as u128) as u64;
+        r = cur % d as u128;
+    }
+
+    (q, r as u64)
+}
+
+/// Sets a = a * m + c in place.
+fn mul_add_limb(a: &mut Vec<u64>, m: u64, c: u64) {
+    let mut carry = c as u128;
+    for limb in a.iter_mut() {
+        let t = *limb as u128 * m as u128 + carry;
+        *limb = t as u64;
+        carry = t >> 64;
+    }
+    if carry > 0 {
+        a.push(carry as u64);
+    }
+}
+
+impl From<u64> for BigUint {
+    fn from(n: u64) -> BigUint {
+        BigUint::from_limbs(vec![n])
+    }
+}
+
+impl From<u128> for BigUint {
+    fn from(n: u128) -> BigUint {
+        BigUint::from_limbs(vec![n as u64, (n >> 64) as u64])
+    }
+}
+
+impl FromStr for BigUint {
+    type Err = ParseBigIntError;
+
+    /// Parses decimal, or hexadecimal if prefixed with "0x".
+    fn from_str(s: &str) -> Result<BigUint, ParseBigIntError> {
+        match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+            Some(hex) => BigUint::from_str_radix(hex, 16),
+            None => BigUint::from_str_radix(s, 10),
+        }
+    }
+}
+
+impl Ord for BigUint {
+    fn cmp(&self, other: &BigUint) -> Ordering {
+        self.limbs.len()
+            .cmp(&other.limbs.len())
+            .then_with(|| self.limbs.iter().rev().cmp(other.limbs.iter().rev()))
+    }
+}
+
+impl PartialOrd for BigUint {
+    fn partial_cmp(&self, other: &BigUint) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for BigUint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_zero() {
+            return f.pad_integral(true, "", "0");
+        }
+
+        // peel off 19 decimal digits at a time, the most that fit in a limb
+        const CHUNK: u64 = 10_000_000_000_000_000_000;
+        let mut chunks = vec![];
+        let mut limbs = self.limbs.clone();
+        while !limbs.is_empty() {
+            let (q, r) = div_rem_limb(&limbs, CHUNK);
+            chunks.push(r);
+            limbs = BigUint::from_limbs(q).limbs;
+        }
+
+        let mut s = chunks.pop().unwrap().to_string();
+        for chunk in chunks.iter().rev() {
+            s.push_str(&format!("{:019}", chunk));
+        }
+        f.pad_integral(true, "", &s)
+    }
+}
+
+impl fmt::Debug for BigUint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl fmt::LowerHex for BigUint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut s = match self.limbs.last() {
+            None => String::from("0"),
+            Some(top) => format!("{:x}", top),
+        };
+        for limb in self.limbs.iter().rev().skip(1) {
+            s.push_str(&format!("{:016x}", limb));
+        }
+        f.pad_integral(true, "0x", &s)
+    }
+}
+
+impl Add<&BigUint> for &BigUint {
+    type Output = BigUint;
+
+    fn add(self, other: &BigUint) -> BigUint {
+        let (long, short) = if self.limbs.len() >= other.limbs.len() {
+            (&self.limbs, &other.limbs)
+        } else {
+            (&other.limbs, &self.limbs)
+        };
+
+        let mut limbs = Vec::with_capacity(long.len() + 1);
+        let mut carry = 0u128;
+        for (i, &l) in long.iter().enumerate() {
+            let s = l as u128 + *short.get(i).unwrap_or(&0) as u128 + carry;
+            limbs.push(s as u64);
+            carry = s >> 64;
+        }
+        limbs.push(carry as u64);
+
+        BigUint::from_limbs(limbs)
+    }
+}
+
+impl Sub<&BigUint> for &BigUint {
+    type Output = BigUint;
+
+    fn sub(self, other: &BigUint) -> BigUint {
+        assert!(self >= other, "attempt to subtract with overflow");
+
+        let mut limbs = Vec::with_capacity(self.limbs.len());
+        let mut borrow = 0i128;
+        for i in 0..self.limbs.len() {
+            let t = self.limbs[i] as i128
+                - *other.limbs.get(i).unwrap_or(&0) as i128
+                - borrow;
+            limbs.push(t as u64);
+            borrow = (t < 0) as i128;
+        }
+
+        BigUint::from_limbs(limbs)
+    }
+}
+
+impl Mul<&BigUint> for &BigUint {
+    type Output = BigUint;
+
+    /// Schoolbook multiplication, O(mn) limb products.
+    fn mul(self, other: &BigUint) -> BigUint {
+        if self.is_zero() || other.is_zero() {
+            return BigUint::default();
+        }
+
+        let mut limbs = vec![0u64; self.limbs.len() + other.limbs.len()];
+        for (i, &a) in self.limbs.iter().enumerate() {
+            let mut carry = 0u128;
+            for (j, &b) in other.limbs.iter().enumerate() {
+                let t = a as u128 * b as u128 + limbs[i + j] as u128 + carry;
+                limbs[i + j] = t as u64;
+                carry = t >> 64;
+            }
+            limbs[i + other.limbs.len()] = carry as u64;
+        }
+
+        BigUint::from_limbs(limbs)
+    }
+}
+
+impl Div<&BigUint> for &BigUint {
+    type Output = BigUint;
+
+    fn div(self, other: &BigUint) -> BigUint {
+        self.div_rem(other).0
+    }
+}
+
+impl Rem<&BigUint> for &BigUint {
+    type Output = BigUint;
+
+    fn rem(self, other: &BigUint) -> BigUint {
+        self.div_rem(other).1
+    }
+}
+
+impl Shl<u32> for &BigUint {
+    type Output = BigUint;
+
+    fn shl(self, shift: u32) -> BigUint {
+        if self.is_zero() {
+            return BigUint::default();
+        }
+
+        let (limb_shift, bit_shift) = ((shift / 64) as usize, shift % 64);
+        let mut limbs = vec![0u64; limb_shift];
+        if bit_shift == 0 {
+            limbs.extend_from_slice(&self.limbs);
+        } else {
+            let mut carry = 0u64;
+            for &l in self.limbs.iter() {
+                limbs.push(l << bit_shift | carry);
+                carry = l >> (64 - bit_shift);
+            }
+            limbs.push(carry);
+        }
+
+        BigUint::from_limbs(limbs)
+    }
+}
+
+impl Shr<u32> for &BigUint {
+    type Output = BigUint;
+
+    fn shr(self, shift: u32) -> BigUint {
+        let (limb_shift, bit_shift) = ((shift / 64) as usize, shift % 64);
+        if limb_shift >= self.limbs.len() {
+            return BigUint::default();
+        }
+
+        let rest = &self.limbs[limb_shift..];
+        let limbs = if bit_shift == 0 {
+            rest.to_vec()
+        } else {
+            (0..rest.len())
+                .map(|i| {
+                    let high = rest.get(i + 1).map_or(0, |&h| h << (64 - bit_shift));
+                    rest[i] >> bit_shift | high
+                })
+                .collect()
+        };
+
+        BigUint::from_limbs(limbs)
+    }
+}
+
+/// Derives the owned and mixed-ownership variants of a binary operator (and
+/// its compound assignment) from the `&T op &T` implementation.
+macro_rules! forward_binop {
+    ($t:ty, $imp:ident, $method:ident, $assign_imp:ident, $assign_method:ident) => {
+        impl $imp<$t> for $t {
+            type Output = $t;
+
+            fn $method(self, other: $t) -> $t {
+                (&self).$method(&other)
+            }
+        }
+
+        impl<'a> $imp<&'a $t> for $t {
+            type Output = $t;
+
+            fn $method(self, other: &$t) -> $t {
+                (&self).$method(other)
+            }
+        }
+
+        impl $imp<$t> for &$t {
+            type Output = $t;
+
+            fn $method(self, other: $t) -> $t {
+                self.$method(&other)
+            }
+        }
+
+        impl $assign_imp<$t> for $t {
+            fn $assign_method(&mut self, other: $t) {
+                *self = $imp::$method(&*self, &other);
+            }
+        }
+
+        impl<'a> $assign_imp<&'a $t> for $t {
+            fn $assign_method(&mut self, other: &$t) {
+                *self = $imp::$method(&*self, other);
+            }
+        }
+    };
+}
+
+macro_rules! forward_shift {
+    ($t:ty, $imp:ident, $method:ident, $assign_imp:ident, $assign_method:ident) => {
+        impl $imp<u32> for $t {
+            type Output = $t;
+
+            fn $method(self, shift: u32) -> $t {
+                (&self).$method(shift)
+            }
+        }
+
+        impl $assign_imp<u32> for $t {
+            fn $assign_method(&mut self, shift: u32) {
+                *self = (&*self).$method(shift);
+            }
+        }
+    };
+}
+
+forward_binop!(BigUint, Add, add, AddAssign, add_assign);
+forward_binop!(BigUint, Sub, sub, SubAssign, sub_assign);
+forward_binop!(BigUint, Mul, mul, MulAssign, mul_assign);
+forward_binop!(BigUint, Div, div, DivAssign, div_assign);
+forward_binop!(BigUint, Rem, rem, RemAssign, rem_assign);
+forward_shift!(BigUint, Shl, shl, ShlAssign, shl_assign);
+forward_shift!(BigUint, Shr, shr, ShrAssign, shr_assign);
+
+impl UnsignedInteger for BigUint {
+    type Signed = BigInt;
+
+    fn is_zero(&self) -> bool {
+        BigUint::is_zero(self)
+    }
+
+    fn is_even(&self) -> bool {
+        BigUint::is_even(self)
+    }
+
+    fn bits(&self) -> u32 {
+        BigUint::bits(self)
+    }
+
+    fn bit(&self, i: u32) -> bool {
+        BigUint::bit(self, i)
+    }
+
+    fn to_signed(&self) -> BigInt {
+        BigInt::from(self.clone())
+    }
+
+    fn to_u128(&self) -> Option<u128> {
+        BigUint::to_u128(self)
+    }
+
+    fn from_u128(n: u128) -> BigUint {
+        BigUint::from(n)
+    }
+
+    fn to_biguint(&self) -> BigUint {
+        self.clone()
+    }
+
+    fn from_biguint(n: &BigUint) -> BigUint {
+        n.clone()
+    }
+
+    fn add_mod(&self, other: &BigUint, modulus: &BigUint) -> BigUint {
+        let s = self + other;
+        if &s >= modulus { s - modulus } else { s }
+    }
+
+    fn mul_mod(&self, other: &BigUint, modulus: &BigUint) -> BigUint {
+        (self * other) % modulus
+    }
+
+    fn random_below<R: Rng + ?Sized>(rng: &mut R, bound: &BigUint) -> BigUint {
+        BigUint::random_below(rng, bound)
+    }
+}
+
+impl BigInt {
+    /// Builds a signed integer from a sign and a magnitude.
+    pub fn from_sign_magnitude(negative: bool, magnitude: BigUint) -> BigInt {
+        BigInt {
+            negative: negative && !magnitude.is_zero(),
+            magnitude,
+        }
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    pub fn magnitude(&self) -> &BigUint {
+        &self.magnitude
+    }
+
+    /// Returns the least non-negative residue of self modulo m.
+    pub fn rem_euclid(&self, modulus: &BigUint) -> BigUint {
+        let r = &self.magnitude % modulus;
+        if self.negative && !r.is_zero() { modulus - r } else { r }
+    }
+}
+
+impl From<BigUint> for BigInt {
+    fn from(n: BigUint) -> BigInt {
+        BigInt::from_sign_magnitude(false, n)
+    }
+}
+
+impl From<i64> for BigInt {
+    fn from(n: i64) -> BigInt {
+        BigInt::from_sign_magnitude(n < 0, BigUint::from(n.unsigned_abs()))
+    }
+}
+
+impl From<i128> for BigInt {
+    fn from(n: i128) -> BigInt {
+        BigInt::from_sign_magnitude(n < 0, BigUint::from(n.unsigned_abs()))
+    }
+}
+
+impl FromStr for BigInt {
+    type Err = ParseBigIntError;
+
+    fn from_str(s: &str) -> Result<BigInt, ParseBigIntError> {
+        match s.strip_prefix('-') {
+            Some(rest) => Ok(BigInt::from_sign_magnitude(true, rest.parse()?)),
+            None => Ok(BigInt::from(s.parse::<BigUint>()?)),
+        }
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &BigInt) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, false) => self.magnitude.cmp(&other.magnitude),
+            (true, true) => other.magnitude.cmp(&self.magnitude),
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &BigInt) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad_integral(!self.negative, "", &self.magnitude.to_string())
+    }
+}
+
+impl fmt::Debug for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl Neg for BigInt {
+    type Output = BigInt;
+
+    fn neg(self) -> BigInt {
+        BigInt::from_sign_magnitude(!self.negative, self.magnitude)
+    }
+}
+
+impl Neg for &BigInt {
+    type Output = BigInt;
+
+    fn neg(self) -> BigInt {
+        -self.clone()
+    }
+}
+
+impl Add<&BigInt> for &BigInt {
+    type Output = BigInt;
+
+    fn add(self, other: &BigInt) -> BigInt {
+        if self.negative == other.negative {
+            return BigInt::from_sign_magnitude(
+                self.negative, &self.magnitude + &other.magnitude);
+        }
+
+        // opposite signs: the result takes the sign of the larger magnitude
+        if self.magnitude >= other.magnitude {
+            BigInt::from_sign_magnitude(
+                self.negative, &self.magnitude - &other.magnitude)
+        } else {
+            BigInt::from_sign_magnitude(
+                other.negative, &other.magnitude - &self.magnitude)
+        }
+    }
+}
+
+impl Sub<&BigInt> for &BigInt {
+    type Output = BigInt;
+
+    fn sub(self, other: &BigInt) -> BigInt {
+        self + &(-other)
+    }
+}
+
+impl Mul<&BigInt> for &BigInt {
+    type Output = BigInt;
+
+    fn mul(self, other: &BigInt) -> BigInt {
+        BigInt::from_sign_magnitude(
+            self.negative != other.negative, &self.magnitude * &other.magnitude)
+    }
+}
+
+impl Div<&BigInt> for &BigInt {
+    type Output = BigInt;
+
+    /// Truncating division, matching the primitive signed integers.
+    fn div(self, other: &BigInt) -> BigInt {
+        BigInt::from_sign_magnitude(
+            self.negative != other.negative, &self.magnitude / &other.magnitude)
+    }
+}
+
+impl Rem<&BigInt> for &BigInt {
+    type Output = BigInt;
+
+    /// Remainder of truncating division; takes the sign of the dividend.
+    fn rem(self, other: &BigInt) -> BigInt {
+        BigInt::from_sign_magnitude(self.negative, &self.magnitude % &other.magnitude)
+    }
+}
+
+forward_binop!(BigInt, Add, add, AddAssign, add_assign);
+forward_binop!(BigInt, Sub, sub, SubAssign, sub_assign);
+forward_binop!(BigInt, Mul, mul, MulAssign, mul_assign);
+forward_binop!(BigInt, Div, div, DivAssign, div_assign);
+forward_binop!(BigInt, Rem, rem, RemAssign, rem_assign);
+
+impl SignedInteger for BigInt {
+    type Unsigned = BigUint;
+
+    fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    fn unsigned_abs(&self) -> BigUint {
+        self.magnitude.clone()
+    }
+
+    fn modulo(&self, modulus: &BigUint) -> BigUint {
+        self.rem_euclid(modulus)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn big(s: &str) -> BigUint {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_and_display() {
+        for s in ["0", "1", "18446744073709551615", "18446744073709551616",
+                  "340282366920938463463374607431768211457",
+                  "1000000000000000000000000000000000000000000000000000000007"] {
+            assert_eq!(s, big(s).to_string());
+        }
+
+        assert_eq!(BigUint::from(255u64), big("0xff"));
+        assert_eq!(BigUint::from(u128::MAX), big("0xffffffffffffffffffffffffffffffff"));
+        assert_eq!("0x1000000000000000000000000", format!("{:#x}", big("0x1000000000000000000000000")));
+        assert_eq!(Ok(BigUint::from(5u64)), BigUint::from_str_radix("101", 2));
+
+        assert_eq!(Err(ParseBigIntError::Empty), "".parse::<BigUint>());
+        assert_eq!(Err(ParseBigIntError::InvalidDigit), "12a".parse::<BigUint>());
+        assert_eq!(Err(ParseBigIntError::InvalidDigit), "0xfg".parse::<BigUint>());
+
+        assert_eq!("-12345678901234567890123", "-12345678901234567890123".parse::<BigInt>().unwrap().to_string());
+        assert_eq!(BigInt::default(), "-0".parse::<BigInt>().unwrap());
+    }
+
+    #[test]
+    fn test_arithmetic_matches_u128() {
+        // format: (x, y) with x * y and x + y fitting in a u128
+        let test_cases: Vec<(u128, u128)> = vec![
+            (0, 0),
+            (1, 0),
+            (7, 5),
+            (u64::MAX as u128, u64::MAX as u128),
+            (u64::MAX as u128 + 1, 3),
+            (123456789012345678901234567, 98765),
+            (1 << 100, (1 << 27) - 1),
+        ];
+
+        for &(x, y) in test_cases.iter() {
+            let (bx, by) = (BigUint::from(x), BigUint::from(y));
+            assert_eq!(Some(x + y), (&bx + &by).to_u128());
+            assert_eq!(Some(x - y), (&bx - &by).to_u128());
+            assert_eq!(Some(x * y), (&bx * &by).to_u128());
+            if let Some(q) = x.checked_div(y) {
+                assert_eq!(Some(q), (&bx / &by).to_u128());
+                assert_eq!(Some(x % y), (&bx % &by).to_u128());
+            }
+            assert_eq!(x.cmp(&y), bx.cmp(&by));
+            assert_eq!(Some(x >> 3), (&bx >> 3).to_u128());
+            assert_eq!(128 - x.leading_zeros(), bx.bits());
+        }
+    }
+
+    #[test]
+    fn test_div_rem_multi_limb() {
+        let a = big("0x123456789abcdef0fedcba9876543210deadbeefcafebabe0123456789abcdef");
+        let b = big("0xfedcba98765432100123456789abcdef1");
+        let (q, r) = a.div_rem(&b);
+        assert!(r < b);
+        assert_eq!(a, &q * &b + &r);
+
+
+        let mut rng = rand::thread_rng();
+        for _ in 0..200 {
+            let a = BigUint::from_limbs((0..rng.gen_range(1..8)).map(|_| rng.gen()).collect());
+            let b = BigUint::from_limbs((0..rng.gen_range(1..5)).map(|_| rng.gen()).collect());
+            if b.is_zero() { continue; }
+            let (q, r) = a.div_rem(&b);
+            assert!(r < b);
+            assert_eq!(a, &q * &b + &r);
+        }
+    }
+
+    #[test]
+    fn test_div_rem_u64() {
+        let a = big("0x123456789abcdef0fedcba9876543210deadbeefcafebabe");
+        for d in [1u64, 2, 7, 1000003, u64::MAX] {
+            let (q, r) = a.div_rem_u64(d);
+            assert_eq!(r, a.rem_u64(d));
+            assert_eq!(a, q * BigUint::from(d) + BigUint::from(r));
+        }
+    }
+
+    #[test]
+    fn test_shifts() {
+        let x = big("0xdeadbeefcafebabe0123456789abcdef");
+        for s in [0, 1, 63, 64, 65, 128, 200] {
+            assert_eq!(x, (&x << s) >> s);
+        }
+        assert_eq!(big("0xdeadbeefcafebabe012345678"), &x >> 28);
+        assert_eq!(BigUint::default(), &x >> 128);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_sub_panics_on_underflow() {
+        let _ = BigUint::from(1u64) - BigUint::from(2u64);
+    }
+
+    #[test]
+    fn test_signed_arithmetic() {
+        // format: (x, y) for which i128 arithmetic does not overflow
+        let test_cases: Vec<(i128, i128)> = vec![
+            (7, 5),
+            (-7, 5),
+            (7, -5),
+            (-7, -5),
+            (0, -3),
+            (-123456789012345678901234567, 98765),
+        ];
+
+        for &(x, y) in test_cases.iter() {
+            let (bx, by) = (BigInt::from(x), BigInt::from(y));
+            assert_eq!(BigInt::from(x + y), &bx + &by);
+            assert_eq!(BigInt::from(x - y), &bx - &by);
+            assert_eq!(BigInt::from(x * y), &bx * &by);
+            assert_eq!(BigInt::from(x / y), &bx / &by);
+            assert_eq!(BigInt::from(x % y), &bx % &by);
+            assert_eq!(x.cmp(&y), bx.cmp(&by));
+            assert_eq!(
+                BigUint::from(x.rem_euclid(11) as u128),
+                bx.rem_euclid(&BigUint::from(11u64)));
+        }
+    }
+}
@@ -0,0 +1,276 @@
+use rand::{CryptoRng, RngCore};
+
+use super::integer::{gcd, mod_inverse, pow_mod, UnsignedInteger};
+use super::primes::random_blum_prime_with_rng;
+
+/// The Blum Blum Shub generator: x_{i+1} = x_i^2 mod n for a Blum integer
+/// n = pq (p = q = 3 mod 4), outputting the low bits of each x_i.
+///
+/// Squaring is a permutation of the quadratic residues mod a Blum integer,
+/// and running it backwards needs the factors of n. Predicting the previous
+/// output bit is as hard as deciding quadratic residuosity mod n, which is
+/// believed to need the factorisation, so anyone without p and q can't
+/// predict the output any better than chance.
+#[derive(Clone, Debug)]
+pub struct BlumBlumShub<T = u128> {
+    pub modulus: T,
+    /// x_0, the square of the seed.
+    initial_state: T,
+    state: T,
+    index: u64,
+}
+
+impl<T: UnsignedInteger> BlumBlumShub<T> {
+    /// Starts the generator with x_0 = seed^2 mod modulus, or returns None
+    /// if the seed is not coprime to the modulus or its square is 1, which
+    /// would give a constant sequence.
+    ///
+    /// Squaring the seed makes x_0 a quadratic residue, so every state is.
+    pub fn new(modulus: T, seed: T) -> Option<BlumBlumShub<T>> {
+        if gcd(seed.clone(), modulus.clone()) != T::one() {
+            return None;
+        }
+        let initial_state = seed.mul_mod(&seed, &modulus);
+        if initial_state == T::one() {
+            return None;
+        }
+        Some(BlumBlumShub { modulus, state: initial_state.clone(), initial_state, index: 0 })
+    }
+
+    /// Returns a generator over a random Blum integer with the given number
+    /// of bits (at least 8) and a random seed, together with the factors
+    /// p and q, which must stay secret.
+    pub fn generate(bits: u32) -> (BlumBlumShub<T>, T, T) {
+        BlumBlumShub::generate_with_rng(&mut rand::thread_rng(), bits)
+    }
+
+    /// `generate` drawing the primes and the seed from rng.
+    pub fn generate_with_rng<R: RngCore + CryptoRng + ?Sized>(rng: &mut R, bits: u32) -> (BlumBlumShub<T>, T, T) {
+        assert!(bits >= 8, "Blum Blum Shub modulus too small.");
+        loop {
+            let p: T = random_blum_prime_with_rng(rng, bits - bits / 2);
+            let q: T = random_blum_prime_with_rng(rng, bits / 2);
+            let n = p.clone() * q.clone();
+            if p == q || n.bits() != bits {
+                continue;
+            }
+
+            let seed = T::random_below(rng, &(n.clone() - T::from(2))) + T::from(2);
+            if let Some(generator) = BlumBlumShub::new(n, seed) {
+                return (generator, p, q);
+            }
+        }
+    }
+
+    /// Returns i, the index of the current state x_i.
+    pub fn index(&self) -> u64 {
+        self.index
+    }
+
+    /// Returns the current state x_i.
+    pub fn state(&self) -> &T {
+        &self.state
+    }
+
+    /// How many low bits of each state `next_bits` outputs: log2(log2(n)),
+    /// the number Vazirani and Vazirani showed to be as secure as one.
+    pub fn bits_per_step(&self) -> u32 {
+        (self.modulus.bits().max(2).ilog2()).min(64)
+    }
+
+    /// Moves to x_{i+1} = x_i^2 mod n.
+    pub fn step(&mut self) -> &T {
+        self.state = self.state.mul_mod(&self.state, &self.modulus);
+        self.index += 1;
+        &self.state
+    }
+
+    /// Steps, and returns the parity of the new state.
+    pub fn next_bit(&mut self) -> bool {
+        self.step().bit(0)
+    }
+
+    /// Steps, and returns the low `bits_per_step` bits of the new state.
+    pub fn next_bits(&mut self) -> u64 {
+        let count = self.bits_per_step();
+        let state = self.step();
+        (0..count).fold(0, |bits, i| bits | ((state.bit(i) as u64) << i))
+    }
+
+    /// Jumps straight to x_index using the factorisation.
+    ///
+    /// x_i = x_0^{2^i}, and since x_0 is a unit its order divides the
+    /// Carmichael function lambda(n) = lcm(p - 1, q - 1), so the exponent
+    /// can be reduced to 2^i mod lambda(n): O(log n) multiplications
+    /// instead of i. Without p and q, lambda(n) is as hard to find as the
+    /// factors themselves.
+    pub fn jump_to(&mut self, index: u64, p: &T, q: &T) {
+        assert!(p.clone() * q.clone() == self.modulus, "p and q are not the factors of the modulus.");
+        let one = T::one();
+        let (p_minus_one, q_minus_one) = (p.clone() - one.clone(), q.clone() - one);
+        let lambda = p_minus_one.clone() / gcd(p_minus_one, q_minus_one.clone()) * q_minus_one;
+
+        let exponent = pow_mod(&T::from(2), &T::from(index), &lambda);
+        self.state = pow_mod(&self.initial_state, &exponent, &self.modulus);
+        self.index = index;
+    }
+
+    /// Moves back to x_{i-1} using the factorisation, for i >= 1.
+    ///
+    /// Of the four square roots of x_i mod n, exactly one is a quadratic
+    /// residue: modulo p it is x_i^{(p+1)/4}, and likewise modulo q. This is
+    /// what makes squaring a permutation of the residues, and why knowing
+    /// which root is a residue, and hence the previous output, amounts to
+    /// deciding quadratic residuosity.
+    pub fn step_back(&mut self, p: &T, q: &T) {
+        assert!(self.index > 0, "Already at the initial state.");
+        assert!(p.clone() * q.clone() == self.modulus, "p and q are not the factors of the modulus.");
+        let four = T::from(4);
+        let root_p = pow_mod(&self.state, &((p.clone() + T::one()) / four.clone()), p);
+        let root_q = pow_mod(&self.state, &((q.clone() + T::one()) / four), q);
+
+        // Chinese remaindering: root_q + q * ((root_p - root_q) / q mod p)
+        let q_inverse = mod_inverse(q.clone() % p.clone(), p.clone()).unwrap();
+        let difference = root_p.sub_mod(&(root_q.clone() % p.clone()), p);
+        self.state = root_q + q.clone() * difference.mul_mod(&q_inverse, p);
+        self.index -= 1;
+    }
+}
+
+impl<T: UnsignedInteger> RngCore for BlumBlumShub<T> {
+    fn next_u32(&mut self) -> u32 {
+        let mut bytes = [0; 4];
+        self.fill_bytes(&mut bytes);
+        u32::from_le_bytes(bytes)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0; 8];
+        self.fill_bytes(&mut bytes);
+        u64::from_le_bytes(bytes)
+    }
+
+    /// Uses one bit per squaring, so this is slow.
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for byte in dest.iter_mut() {
+            *byte = (0..8).fold(0, |byte, i| byte | ((self.next_bit() as u8) << i));
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bigint::BigUint;
+    use crate::integer::{is_prime, jacobi_symbol};
+
+    #[test]
+    fn test_blum_blum_shub() {
+        // the textbook example: n = 11 * 23 = 253, seed 3
+        let mut generator = BlumBlumShub::new(253u128, 3).unwrap();
+        assert_eq!(9, *generator.state());
+        let states: Vec<u128> = (0..6).map(|_| *generator.step()).collect();
+        assert_eq!(vec![81, 236, 36, 31, 202, 71], states);
+        assert_eq!(6, generator.index());
+
+        let mut generator = BlumBlumShub::new(253u128, 3).unwrap();
+        let bits: Vec<bool> = (0..6).map(|_| generator.next_bit()).collect();
+        assert_eq!(vec![true, false, false, true, false, true], bits);
+
+        assert!(BlumBlumShub::new(253u128, 22).is_none());
+        assert!(BlumBlumShub::new(253u128, 1).is_none());
+    }
+
+    #[test]
+    fn test_generate() {
+        let (generator, p, q): (BlumBlumShub, u128, u128) = BlumBlumShub::generate(64);
+        assert_eq!(64, generator.modulus.bits());
+        assert_eq!(p * q, generator.modulus);
+        assert!(is_prime(p) && is_prime(q));
+        assert_eq!((3, 3), (p % 4, q % 4));
+        assert_eq!(6, generator.bits_per_step());
+    }
+
+    #[test]
+    fn test_states_are_quadratic_residues() {
+        let (p, q) = (1000003u128, 1000039u128);
+        let mut generator = BlumBlumShub::new(p * q, 123456789).unwrap();
+        for _ in 0..100 {
+            let x = *generator.step();
+            // Euler's criterion: x is a residue mod p iff x^{(p-1)/2} = 1
+            assert_eq!(1, pow_mod(&x, &((p - 1) / 2), &p));
+            assert_eq!(1, pow_mod(&x, &((q - 1) / 2), &q));
+            // the Jacobi symbol mod n is 1 for residues, but also for
+            // non-residues mod both p and q, so it can't tell them apart
+            assert_eq!(1, jacobi_symbol(x, p * q));
+        }
+    }
+
+    #[test]
+    fn test_jump_to() {
+        let (p, q) = (1000003u128, 1000039u128);
+        let mut stepped = BlumBlumShub::new(p * q, 987654321).unwrap();
+        let mut jumped = stepped.clone();
+        for _ in 0..1000 {
+            stepped.step();
+        }
+        jumped.jump_to(1000, &p, &q);
+        assert_eq!(stepped.state(), jumped.state());
+        assert_eq!(1000, jumped.index());
+
+        jumped.jump_to(0, &p, &q);
+        assert_eq!(BlumBlumShub::new(p * q, 987654321).unwrap().state(), jumped.state());
+    }
+
+    #[test]
+    fn test_jump_to_big_integers() {
+        let (mut generator, p, q): (BlumBlumShub<BigUint>, _, _) = BlumBlumShub::generate(256);
+        let mut jumped = generator.clone();
+        jumped.jump_to(500, &p, &q);
+        for _ in 0..500 {
+            generator.step();
+        }
+        assert_eq!(generator.state(), jumped.state());
+
+        // an index far beyond anything reachable by stepping
+        jumped.jump_to(u64::MAX, &p, &q);
+        let mut back = jumped.clone();
+        back.step_back(&p, &q);
+        back.step();
+        assert_eq!(jumped.state(), back.state());
+    }
+
+    #[test]
+    fn test_step_back() {
+        let (p, q) = (1000003u128, 1000039u128);
+        let mut generator = BlumBlumShub::new(p * q, 55555).unwrap();
+        let states: Vec<u128> = (0..20).map(|_| *generator.step()).collect();
+        for i in (0..19).rev() {
+            generator.step_back(&p, &q);
+            assert_eq!(states[i], *generator.state());
+        }
+        generator.step_back(&p, &q);
+        assert_eq!(55555 * 55555 % (p * q), *generator.state());
+    }
+
+    #[test]
+    fn test_next_bits() {
+        let (p, q) = (1000003u128, 1000039u128);
+        let mut generator = BlumBlumShub::new(p * q, 31337).unwrap();
+        let mut copy = generator.clone();
+        assert_eq!(5, generator.bits_per_step());
+        for _ in 0..20 {
+            let bits = generator.next_bits();
+            assert_eq!((*copy.step() % 32) as u64, bits);
+        }
+
+        let mut bytes = [0u8; 16];
+        generator.fill_bytes(&mut bytes);
+        assert_eq!(20 + 128, generator.index());
+    }
+}
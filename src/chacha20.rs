@@ -0,0 +1,400 @@
+use super::aead::{Aead, AeadError};
+use super::hmac::constant_time_eq;
+
+/// "expand 32-byte k", the first row of every ChaCha state.
+const CONSTANTS: [u32; 4] = [0x61707865, 0x3320646e, 0x79622d32, 0x6b206574];
+
+fn quarter_round(state: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(16);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(12);
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(8);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(7);
+}
+
+fn le_words(bytes: &[u8]) -> impl Iterator<Item = u32> + '_ {
+    bytes.chunks_exact(4).map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+}
+
+/// The ChaCha20 block function from RFC 8439, section 2.3: 64 bytes of
+/// keystream for one value of the block counter.
+pub fn chacha20_block(key: &[u8; 32], counter: u32, nonce: &[u8; 12]) -> [u8; 64] {
+    let mut initial = [0u32; 16];
+    initial[..4].copy_from_slice(&CONSTANTS);
+    for (word, k) in initial[4..12].iter_mut().zip(le_words(key)) {
+        *word = k;
+    }
+    initial[12] = counter;
+    for (word, n) in initial[13..].iter_mut().zip(le_words(nonce)) {
+        *word = n;
+    }
+
+    let mut state = initial;
+    for _ in 0..10 {
+        quarter_round(&mut state, 0, 4, 8, 12);
+        quarter_round(&mut state, 1, 5, 9, 13);
+        quarter_round(&mut state, 2, 6, 10, 14);
+        quarter_round(&mut state, 3, 7, 11, 15);
+        quarter_round(&mut state, 0, 5, 10, 15);
+        quarter_round(&mut state, 1, 6, 11, 12);
+        quarter_round(&mut state, 2, 7, 8, 13);
+        quarter_round(&mut state, 3, 4, 9, 14);
+    }
+
+    let mut block = [0u8; 64];
+    for ((bytes, &word), &start) in block.chunks_exact_mut(4).zip(state.iter()).zip(initial.iter()) {
+        bytes.copy_from_slice(&word.wrapping_add(start).to_le_bytes());
+    }
+    block
+}
+
+/// Encrypts or decrypts data in place with ChaCha20 (RFC 8439, section
+/// 2.4), starting from the given block counter.
+///
+/// The counter is 32 bits, so one nonce covers at most 256 GiB.
+pub fn chacha20_xor(key: &[u8; 32], counter: u32, nonce: &[u8; 12], data: &mut [u8]) {
+    assert!(
+        data.len().div_ceil(64) as u64 <= (1u64 << 32) - counter as u64,
+        "Data too long for the ChaCha20 block counter.");
+    for (i, chunk) in data.chunks_mut(64).enumerate() {
+        let block = chacha20_block(key, counter + i as u32, nonce);
+        for (byte, &k) in chunk.iter_mut().zip(block.iter()) {
+            *byte ^= k;
+        }
+    }
+}
+
+/// The Poly1305 one-time authenticator from RFC 8439, section 2.5, with a
+/// streaming interface.
+///
+/// The accumulator is kept in five 26-bit limbs, so that all the products
+/// in a multiplication modulo 2^130 - 5 fit in 64 bits.
+#[derive(Clone, Debug)]
+pub struct Poly1305 {
+    r: [u32; 5],
+    s: [u32; 4],
+    h: [u32; 5],
+    /// Bytes not yet absorbed; always shorter than a block.
+    buffer: Vec<u8>,
+}
+
+impl Poly1305 {
+    const LIMB_MASK: u32 = (1 << 26) - 1;
+
+    /// Starts a MAC under a one-time key: r (which gets clamped) and then s.
+    pub fn new(key: &[u8; 32]) -> Poly1305 {
+        let word = |i: usize| u32::from_le_bytes(key[i..i + 4].try_into().unwrap());
+        let r = [
+            word(0) & 0x3ffffff,
+            (word(3) >> 2) & 0x3ffff03,
+            (word(6) >> 4) & 0x3ffc0ff,
+            (word(9) >> 6) & 0x3f03fff,
+            (word(12) >> 8) & 0x00fffff,
+        ];
+        let s = [word(16), word(20), word(24), word(28)];
+        Poly1305 { r, s, h: [0; 5], buffer: vec![] }
+    }
+
+    /// Adds a 16-byte block, with 2^128 (or, for a padded final block, the
+    /// 1 byte appended to it) on top, and multiplies by r.
+    fn absorb(&mut self, block: &[u8; 16], high_bit: u32) {
+        let word = |i: usize| u32::from_le_bytes(block[i..i + 4].try_into().unwrap());
+        let mask = Poly1305::LIMB_MASK;
+        let h = &mut self.h;
+        h[0] += word(0) & mask;
+        h[1] += (word(3) >> 2) & mask;
+        h[2] += (word(6) >> 4) & mask;
+        h[3] += (word(9) >> 6) & mask;
+        h[4] += (word(12) >> 8) | high_bit;
+
+        let [r0, r1, r2, r3, r4] = self.r.map(|x| x as u64);
+        let [s1, s2, s3, s4] = [r1 * 5, r2 * 5, r3 * 5, r4 * 5];
+        let [h0, h1, h2, h3, h4] = h.map(|x| x as u64);
+        let mut d = [
+            h0 * r0 + h1 * s4 + h2 * s3 + h3 * s2 + h4 * s1,
+            h0 * r1 + h1 * r0 + h2 * s4 + h3 * s3 + h4 * s2,
+            h0 * r2 + h1 * r1 + h2 * r0 + h3 * s4 + h4 * s3,
+            h0 * r3 + h1 * r2 + h2 * r1 + h3 * r0 + h4 * s4,
+            h0 * r4 + h1 * r3 + h2 * r2 + h3 * r1 + h4 * r0,
+        ];
+
+        for i in 0..4 {
+            d[i + 1] += d[i] >> 26;
+            d[i] &= mask as u64;
+        }
+        // 2^130 = 5 modulo the prime
+        let carry = d[4] >> 26;
+        d[4] &= mask as u64;
+        d[0] += carry * 5;
+        d[1] += d[0] >> 26;
+        d[0] &= mask as u64;
+
+        *h = d.map(|x| x as u32);
+    }
+
+    /// Feeds more data into the MAC.
+    pub fn update(&mut self, mut data: &[u8]) {
+        if !self.buffer.is_empty() {
+            let take = data.len().min(16 - self.buffer.len());
+            self.buffer.extend_from_slice(&data[..take]);
+            data = &data[take..];
+            if self.buffer.len() < 16 {
+                return;
+            }
+            let block = self.buffer[..].try_into().unwrap();
+            self.absorb(&block, 1 << 24);
+            self.buffer.clear();
+        }
+
+        let mut blocks = data.chunks_exact(16);
+        for block in blocks.by_ref() {
+            self.absorb(block.try_into().unwrap(), 1 << 24);
+        }
+        self.buffer.extend_from_slice(blocks.remainder());
+    }
+
+    /// Returns the 16-byte tag of everything passed to `update`.
+    pub fn finalize(mut self) -> [u8; 16] {
+        if !self.buffer.is_empty() {
+            let mut block = [0u8; 16];
+            block[..self.buffer.len()].copy_from_slice(&self.buffer);
+            block[self.buffer.len()] = 1;
+            self.absorb(&block, 0);
+        }
+
+        let mask = Poly1305::LIMB_MASK;
+        let mut h = self.h;
+        for i in 1..5 {
+            h[i] += h[i - 1] >> 26;
+            h[i - 1] &= mask;
+        }
+        let carry = h[4] >> 26;
+        h[4] &= mask;
+        h[0] += carry * 5;
+        h[1] += h[0] >> 26;
+        h[0] &= mask;
+
+        // h - p = h + 5 - 2^130; keep it instead of h if it is not negative,
+        // choosing with masks rather than a branch
+        let mut g = [0u32; 5];
+        let mut carry = 5;
+        for i in 0..5 {
+            g[i] = h[i] + carry;
+            carry = g[i] >> 26;
+            g[i] &= mask;
+        }
+        g[4] = g[4].wrapping_add(carry << 26).wrapping_sub(1 << 26);
+        let keep_g = (g[4] >> 31).wrapping_sub(1);
+        for (x, y) in h.iter_mut().zip(g.iter()) {
+            *x = (*x & !keep_g) | (y & keep_g);
+        }
+
+        // h modulo 2^128, plus s
+        let words = [
+            h[0] | (h[1] << 26),
+            (h[1] >> 6) | (h[2] << 20),
+            (h[2] >> 12) | (h[3] << 14),
+            (h[3] >> 18) | (h[4] << 8),
+        ];
+        let mut tag = [0u8; 16];
+        let mut carry = 0u64;
+        for ((bytes, &w), &s) in tag.chunks_exact_mut(4).zip(words.iter()).zip(self.s.iter()) {
+            let sum = w as u64 + s as u64 + carry;
+            bytes.copy_from_slice(&(sum as u32).to_le_bytes());
+            carry = sum >> 32;
+        }
+        tag
+    }
+
+    /// Returns the tag of data in one go.
+    pub fn mac(key: &[u8; 32], data: &[u8]) -> [u8; 16] {
+        let mut poly = Poly1305::new(key);
+        poly.update(data);
+        poly.finalize()
+    }
+}
+
+/// The ChaCha20-Poly1305 AEAD from RFC 8439, section 2.8.
+#[derive(Clone, Debug)]
+pub struct ChaCha20Poly1305 {
+    key: [u8; 32],
+}
+
+impl ChaCha20Poly1305 {
+    /// Computes the Poly1305 tag over the associated data and ciphertext,
+    /// with a one-time key from block 0 of the keystream.
+    fn tag(&self, nonce: &[u8; 12], associated_data: &[u8], ciphertext: &[u8]) -> [u8; 16] {
+        let one_time_key = chacha20_block(&self.key, 0, nonce)[..32].try_into().unwrap();
+        let mut poly = Poly1305::new(&one_time_key);
+        let zeros = [0u8; 16];
+        poly.update(associated_data);
+        poly.update(&zeros[..(16 - associated_data.len() % 16) % 16]);
+        poly.update(ciphertext);
+        poly.update(&zeros[..(16 - ciphertext.len() % 16) % 16]);
+        poly.update(&(associated_data.len() as u64).to_le_bytes());
+        poly.update(&(ciphertext.len() as u64).to_le_bytes());
+        poly.finalize()
+    }
+}
+
+impl Aead for ChaCha20Poly1305 {
+    const KEY_SIZE: usize = 32;
+    const NONCE_SIZE: usize = 12;
+    const TAG_SIZE: usize = 16;
+
+    fn new(key: &[u8]) -> ChaCha20Poly1305 {
+        ChaCha20Poly1305 { key: key.try_into().expect("ChaCha20-Poly1305 keys are 32 bytes.") }
+    }
+
+    fn seal(&self, nonce: &[u8], associated_data: &[u8], plaintext: &[u8]) -> Vec<u8> {
+        let nonce = nonce.try_into().expect("ChaCha20-Poly1305 nonces are 12 bytes.");
+        let mut ciphertext = plaintext.to_vec();
+        chacha20_xor(&self.key, 1, nonce, &mut ciphertext);
+        let tag = self.tag(nonce, associated_data, &ciphertext);
+        ciphertext.extend_from_slice(&tag);
+        ciphertext
+    }
+
+    fn open(&self, nonce: &[u8], associated_data: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, AeadError> {
+        let nonce = nonce.try_into().expect("ChaCha20-Poly1305 nonces are 12 bytes.");
+        if ciphertext.len() < Self::TAG_SIZE {
+            return Err(AeadError);
+        }
+        let (body, tag) = ciphertext.split_at(ciphertext.len() - Self::TAG_SIZE);
+        if !constant_time_eq(&self.tag(nonce, associated_data, body), tag) {
+            return Err(AeadError);
+        }
+
+        let mut plaintext = body.to_vec();
+        chacha20_xor(&self.key, 1, nonce, &mut plaintext);
+        Ok(plaintext)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{bytes, hex};
+
+    const SUNSCREEN: &[u8] = concat!(
+        "Ladies and Gentlemen of the class of '99: If I could offer you only one ",
+        "tip for the future, sunscreen would be it.").as_bytes();
+
+    fn sequential_key(start: u8) -> [u8; 32] {
+        std::array::from_fn(|i| start + i as u8)
+    }
+
+    #[test]
+    fn test_quarter_round() {
+        // RFC 8439, section 2.2.1
+        let mut state = [0u32; 16];
+        state[..4].copy_from_slice(&[0x11111111, 0x01020304, 0x9b8d6f43, 0x01234567]);
+        quarter_round(&mut state, 0, 1, 2, 3);
+        assert_eq!([0xea2a92f4, 0xcb1cf8ce, 0x4581472e, 0x5881c4bb], state[..4]);
+    }
+
+    #[test]
+    fn test_chacha20_block() {
+        // RFC 8439, section 2.3.2
+        let nonce = bytes("000000090000004a00000000").try_into().unwrap();
+        assert_eq!(
+            concat!("10f1e7e4d13b5915500fdd1fa32071c4c7d1f4c733c068030422aa9ac3d46c4e",
+                    "d2826446079faa0914c2d705d98b02a2b5129cd1de164eb9cbd083e8a2503c4e"),
+            hex(&chacha20_block(&sequential_key(0), 1, &nonce)));
+    }
+
+    #[test]
+    fn test_chacha20_encryption() {
+        // RFC 8439, section 2.4.2
+        let nonce = bytes("000000000000004a00000000").try_into().unwrap();
+        let mut data = SUNSCREEN.to_vec();
+        chacha20_xor(&sequential_key(0), 1, &nonce, &mut data);
+        assert_eq!(
+            concat!("6e2e359a2568f98041ba0728dd0d6981e97e7aec1d4360c20a27afccfd9fae0b",
+                    "f91b65c5524733ab8f593dabcd62b3571639d624e65152ab8f530c359f0861d8",
+                    "07ca0dbf500d6a6156a38e088a22b65e52bc514d16ccf806818ce91ab7793736",
+                    "5af90bbf74a35be6b40b8eedf2785e42874d"),
+            hex(&data));
+
+        chacha20_xor(&sequential_key(0), 1, &nonce, &mut data);
+        assert_eq!(SUNSCREEN, &data[..]);
+    }
+
+    #[test]
+    fn test_poly1305() {
+        // RFC 8439, section 2.5.2
+        let key = bytes("85d6be7857556d337f4452fe42d506a80103808afb0db2fd4abff6af4149f51b");
+        let key = key.try_into().unwrap();
+        let message = b"Cryptographic Forum Research Group";
+        assert_eq!("a8061dc1305136c6c22b8baf0c0127a9", hex(&Poly1305::mac(&key, message)));
+
+        let mut poly = Poly1305::new(&key);
+        for piece in message.chunks(5) {
+            poly.update(piece);
+        }
+        assert_eq!(Poly1305::mac(&key, message), poly.finalize());
+    }
+
+    #[test]
+    fn test_poly1305_edge_cases() {
+        // accumulators landing just past 2^130 - 5, which need the final
+        // reduction, and an s whose addition wraps around 2^128
+        let mut key = [0u8; 32];
+        key[0] = 2;
+        assert_eq!("03000000000000000000000000000000", hex(&Poly1305::mac(&key, &[0xff; 16])));
+
+        key[0] = 1;
+        assert_eq!("03000000000000000000000000000000", hex(&Poly1305::mac(&key, &[0xff; 32])));
+
+        key[0] = 2;
+        key[16..].copy_from_slice(&[0xff; 16]);
+        let mut message = [0u8; 16];
+        message[0] = 2;
+        assert_eq!("03000000000000000000000000000000", hex(&Poly1305::mac(&key, &message)));
+    }
+
+    #[test]
+    fn test_poly1305_key_generation() {
+        // RFC 8439, section 2.6.2
+        let nonce = bytes("000000000001020304050607").try_into().unwrap();
+        assert_eq!(
+            "8ad5a08b905f81cc815040274ab29471a833b637e3fd0da508dbb8e2fdd1a646",
+            hex(&chacha20_block(&sequential_key(0x80), 0, &nonce)[..32]));
+    }
+
+    #[test]
+    fn test_aead() {
+        // RFC 8439, section 2.8.2
+        let cipher = ChaCha20Poly1305::new(&sequential_key(0x80));
+        let nonce = bytes("070000004041424344454647");
+        let associated_data = bytes("50515253c0c1c2c3c4c5c6c7");
+        let sealed = cipher.seal(&nonce, &associated_data, SUNSCREEN);
+        assert_eq!(
+            concat!("d31a8d34648e60db7b86afbc53ef7ec2a4aded51296e08fea9e2b5a736ee62d6",
+                    "3dbea45e8ca9671282fafb69da92728b1a71de0a9e060b2905d6a5b67ecd3b36",
+                    "92ddbd7f2d778b8c9803aee328091b58fab324e4fad675945585808b4831d7bc",
+                    "3ff4def08e4b7a9de576d26586cec64b6116",
+                    "1ae10b594f09e26a7e902ecbd0600691"),
+            hex(&sealed));
+        assert_eq!(Ok(SUNSCREEN.to_vec()), cipher.open(&nonce, &associated_data, &sealed));
+    }
+
+    #[test]
+    fn test_aead_rejects_tampering() {
+        let cipher = ChaCha20Poly1305::new(&sequential_key(0x80));
+        let nonce = [7u8; 12];
+        let sealed = cipher.seal(&nonce, b"header", b"attack at dawn");
+
+        let mut flipped = sealed.clone();
+        flipped[3] ^= 0x10;
+        assert_eq!(Err(AeadError), cipher.open(&nonce, b"header", &flipped));
+        assert_eq!(Err(AeadError), cipher.open(&nonce, b"heades", &sealed));
+        assert_eq!(Err(AeadError), cipher.open(&[8u8; 12], b"header", &sealed));
+        assert_eq!(Err(AeadError), cipher.open(&nonce, b"header", &sealed[..15]));
+
+        assert_eq!(Ok(vec![]), cipher.open(&nonce, &[], &cipher.seal(&nonce, &[], &[])));
+    }
+}
@@ -0,0 +1,261 @@
+use std::error::Error;
+use std::fmt;
+
+use rand::{CryptoRng, RngCore};
+
+use super::diffie_hellman::{compute_shared_secret, derive_key, generate_secret_and_shared_value_with_rng};
+use super::hash::Hash;
+use super::hmac::{constant_time_eq, Hmac};
+use super::integer::UnsignedInteger;
+use super::modular::Residue;
+
+/// Context string mixed into the key derivation, so that keys derived here
+/// never coincide with keys derived from the same secret for other uses.
+const KDF_INFO: &[u8] = b"DHIES key encapsulation";
+
+/// An error from decrypting a DHIES ciphertext.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DhiesError {
+    /// The ephemeral public value does not have order q in the recipient's
+    /// group, so using it could leak the private key modulo a small factor
+    /// of the group order.
+    InvalidEphemeralKey,
+    /// The tag does not match, so the ciphertext was corrupted, forged, or
+    /// encrypted to a different key.
+    AuthenticationFailed,
+}
+
+impl fmt::Display for DhiesError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DhiesError::InvalidEphemeralKey => write!(f, "invalid ephemeral key"),
+            DhiesError::AuthenticationFailed => write!(f, "authentication failed"),
+        }
+    }
+}
+
+impl Error for DhiesError {}
+
+/// The output of `encrypt`: the encapsulated key, the encrypted message and
+/// a tag over the latter.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Ciphertext<T = u128> {
+    pub ephemeral_key: Residue<T>,
+    pub body: Vec<u8>,
+    pub tag: Vec<u8>,
+}
+
+/// Generates a fresh ephemeral key pair in the group of base and returns
+/// `length` bytes of key material shared with the holder of the private key
+/// for public_key, together with the ephemeral public value that lets them
+/// recover it with `decapsulate`.
+pub fn encapsulate<H: Hash, T: UnsignedInteger>(
+        base: &Residue<T>, public_key: &Residue<T>, length: usize) -> (Vec<u8>, Residue<T>) {
+    encapsulate_with_rng::<H, T, _>(&mut rand::thread_rng(), base, public_key, length)
+}
+
+/// `encapsulate` drawing the ephemeral key from rng.
+pub fn encapsulate_with_rng<H: Hash, T: UnsignedInteger, R: RngCore + CryptoRng + ?Sized>(
+        rng: &mut R, base: &Residue<T>, public_key: &Residue<T>,
+        length: usize) -> (Vec<u8>, Residue<T>) {
+    assert_eq!(
+        base.modulus, public_key.modulus,
+        "Base and public_key have different moduli.");
+
+    let (ephemeral_secret, ephemeral_key) = generate_secret_and_shared_value_with_rng(rng, base);
+    let shared_secret = compute_shared_secret(ephemeral_secret, public_key);
+    let key = derive_key::<H, T>(&shared_secret, &ephemeral_key.to_bytes(), KDF_INFO, length);
+    (key, ephemeral_key)
+}
+
+/// Recovers the key material `encapsulate` produced for the ephemeral public
+/// value, using the recipient's private key.
+///
+/// base must generate a subgroup of prime order q, such as the squares
+/// modulo a safe prime p = 2q + 1. The ephemeral value is rejected unless it
+/// also has order q: with a primitive root as the base, an attacker could
+/// send p - 1 or another element of a small subgroup and, by watching
+/// whether decryption succeeds, learn the private key modulo that
+/// subgroup's order.
+pub fn decapsulate<H: Hash, T: UnsignedInteger>(
+        base: &Residue<T>, q: &T, private_key: T::Signed, ephemeral_key: &Residue<T>,
+        length: usize) -> Result<Vec<u8>, DhiesError> {
+    assert!(base.is_generator_of_subgroup(q), "Base does not have order q.");
+    if ephemeral_key.modulus != base.modulus || !ephemeral_key.is_generator_of_subgroup(q) {
+        return Err(DhiesError::InvalidEphemeralKey);
+    }
+
+    let shared_secret = compute_shared_secret(private_key, ephemeral_key);
+    Ok(derive_key::<H, T>(&shared_secret, &ephemeral_key.to_bytes(), KDF_INFO, length))
+}
+
+/// XORs data with the keystream HMAC(key, 0) || HMAC(key, 1) || ..., which
+/// is a stream cipher as long as HMAC is a pseudorandom function.
+fn apply_keystream<H: Hash>(key: &[u8], data: &mut [u8]) {
+    for (counter, chunk) in data.chunks_mut(H::OUTPUT_SIZE).enumerate() {
+        let block = Hmac::<H>::mac(key, &(counter as u64).to_be_bytes());
+        for (byte, &k) in chunk.iter_mut().zip(block.iter()) {
+            *byte ^= k;
+        }
+    }
+}
+
+/// Encrypts a message of any length to the holder of the private key for
+/// public_key, in the style of DHIES: a Diffie-Hellman key encapsulation,
+/// HKDF, a stream cipher and an encrypt-then-MAC tag, all built from H.
+pub fn encrypt<H: Hash, T: UnsignedInteger>(
+        base: &Residue<T>, public_key: &Residue<T>, message: &[u8]) -> Ciphertext<T> {
+    encrypt_with_rng::<H, T, _>(&mut rand::thread_rng(), base, public_key, message)
+}
+
+/// `encrypt` drawing the ephemeral key from rng.
+pub fn encrypt_with_rng<H: Hash, T: UnsignedInteger, R: RngCore + CryptoRng + ?Sized>(
+        rng: &mut R, base: &Residue<T>, public_key: &Residue<T>,
+        message: &[u8]) -> Ciphertext<T> {
+    let (keys, ephemeral_key) = encapsulate_with_rng::<H, T, R>(rng, base, public_key, 2 * H::OUTPUT_SIZE);
+    let (encryption_key, mac_key) = keys.split_at(H::OUTPUT_SIZE);
+
+    let mut body = message.to_vec();
+    apply_keystream::<H>(encryption_key, &mut body);
+    let tag = Hmac::<H>::mac(mac_key, &body);
+
+    Ciphertext { ephemeral_key, body, tag }
+}
+
+/// Decrypts a ciphertext from `encrypt`, checking the tag before anything is
+/// decrypted. As in `decapsulate`, base must generate a subgroup of prime
+/// order q, and the ephemeral key is checked to lie in it.
+pub fn decrypt<H: Hash, T: UnsignedInteger>(
+        base: &Residue<T>, q: &T, private_key: T::Signed,
+        ciphertext: &Ciphertext<T>) -> Result<Vec<u8>, DhiesError> {
+    let keys = decapsulate::<H, T>(base, q, private_key, &ciphertext.ephemeral_key, 2 * H::OUTPUT_SIZE)?;
+    let (encryption_key, mac_key) = keys.split_at(H::OUTPUT_SIZE);
+
+    if !constant_time_eq(&Hmac::<H>::mac(mac_key, &ciphertext.body), &ciphertext.tag) {
+        return Err(DhiesError::AuthenticationFailed);
+    }
+
+    let mut message = ciphertext.body.clone();
+    apply_keystream::<H>(encryption_key, &mut message);
+    Ok(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bigint::BigUint;
+    use crate::diffie_hellman::generate_secret_and_shared_value;
+    use crate::hash::{Sha256, Sha512};
+
+    /// The squares modulo the safe prime p = 2q + 1, generated by 4, and q.
+    fn group() -> (Residue, u128) {
+        let q = 476126068529u128;
+        (Residue::from_unsigned_integer(4, 2 * q + 1), q)
+    }
+
+    #[test]
+    fn test_encapsulate() {
+        let (base, q) = group();
+        let (private_key, public_key) = generate_secret_and_shared_value(&base);
+
+        let (key, ephemeral_key) = encapsulate::<Sha256, _>(&base, &public_key, 48);
+        assert_eq!(48, key.len());
+        assert_eq!(Ok(key.clone()), decapsulate::<Sha256, _>(&base, &q, private_key, &ephemeral_key, 48));
+
+        let (other_key, other_ephemeral_key) = encapsulate::<Sha256, _>(&base, &public_key, 48);
+        assert_ne!(ephemeral_key, other_ephemeral_key);
+        assert_ne!(key, other_key);
+    }
+
+    #[test]
+    fn test_dhies() {
+        let (base, q) = group();
+        let (private_key, public_key) = generate_secret_and_shared_value(&base);
+
+        for length in [0, 1, 31, 32, 33, 1000] {
+            let message: Vec<u8> = (0..length).map(|i| (i * 7) as u8).collect();
+            let ciphertext = encrypt::<Sha256, _>(&base, &public_key, &message);
+            assert_eq!(length, ciphertext.body.len());
+            if length >= 32 {
+                assert_ne!(message, ciphertext.body);
+            }
+            assert_eq!(Ok(message), decrypt::<Sha256, _>(&base, &q, private_key, &ciphertext));
+        }
+    }
+
+    #[test]
+    fn test_dhies_seeded() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let (base, q) = group();
+        let (private_key, public_key) = generate_secret_and_shared_value(&base);
+        let run = |seed| encrypt_with_rng::<Sha256, _, _>(
+            &mut StdRng::seed_from_u64(seed), &base, &public_key, b"message");
+        assert_eq!(run(1), run(1));
+        assert_ne!(run(1), run(2));
+        assert_eq!(Ok(b"message".to_vec()), decrypt::<Sha256, _>(&base, &q, private_key, &run(1)));
+    }
+
+    #[test]
+    fn test_dhies_big_integers() {
+        let p: BigUint = crate::primes::random_safe_prime(256);
+        let q = (p.clone() - BigUint::from(1u64)) >> 1;
+        let base = Residue::from_unsigned_integer(BigUint::from(4u64), p);
+        let (private_key, public_key) = generate_secret_and_shared_value(&base);
+
+        let message = b"a message much longer than anything that fits under the modulus";
+        let ciphertext = encrypt::<Sha512, _>(&base, &public_key, message);
+        assert_eq!(Ok(message.to_vec()), decrypt::<Sha512, _>(&base, &q, private_key, &ciphertext));
+    }
+
+    #[test]
+    fn test_dhies_rejects_tampering() {
+        let (base, q) = group();
+        let (private_key, public_key) = generate_secret_and_shared_value(&base);
+        let (other_private_key, _) = generate_secret_and_shared_value(&base);
+        let ciphertext = encrypt::<Sha256, _>(&base, &public_key, b"attack at dawn");
+
+        let mut flipped = ciphertext.clone();
+        flipped.body[0] ^= 1;
+        assert_eq!(Err(DhiesError::AuthenticationFailed), decrypt::<Sha256, _>(&base, &q, private_key, &flipped));
+
+        let mut truncated = ciphertext.clone();
+        truncated.tag.pop();
+        assert_eq!(Err(DhiesError::AuthenticationFailed), decrypt::<Sha256, _>(&base, &q, private_key, &truncated));
+
+        let mut replaced = ciphertext.clone();
+        replaced.ephemeral_key = base.pow(12345);
+        assert_eq!(Err(DhiesError::AuthenticationFailed), decrypt::<Sha256, _>(&base, &q, private_key, &replaced));
+
+        assert_eq!(
+            Err(DhiesError::AuthenticationFailed),
+            decrypt::<Sha256, _>(&base, &q, other_private_key, &ciphertext));
+    }
+
+    #[test]
+    fn test_dhies_rejects_small_subgroups() {
+        let (base, q) = group();
+        let p = base.modulus;
+        let (private_key, _) = generate_secret_and_shared_value(&base);
+        let ciphertext = encrypt::<Sha256, _>(&base, &base.pow(2), b"attack at dawn");
+
+        // 0, 1, p - 1 of order 2, a primitive root of order 2q, and a value
+        // from another modulus
+        for ephemeral_key in [
+            Residue::from_unsigned_integer(0, p),
+            Residue::from_unsigned_integer(1, p),
+            Residue::from_unsigned_integer(p - 1, p),
+            Residue::primitive_root(p).unwrap(),
+            Residue::from_unsigned_integer(4, 1000003),
+        ] {
+            let mut forged = ciphertext.clone();
+            forged.ephemeral_key = ephemeral_key.clone();
+            assert_eq!(
+                Err(DhiesError::InvalidEphemeralKey),
+                decrypt::<Sha256, _>(&base, &q, private_key, &forged));
+            assert_eq!(
+                Err(DhiesError::InvalidEphemeralKey),
+                decapsulate::<Sha256, _>(&base, &q, private_key, &ephemeral_key, 32));
+        }
+    }
+}
@@ -1,30 +1,62 @@
-use rand::Rng;
+use rand::{CryptoRng, RngCore};
 
+use super::hash::Hash;
+use super::hmac::hkdf;
+use super::integer::UnsignedInteger;
 use super::modular::Residue;
 
-pub fn choose_base(modulus: u128) -> Residue {
-    Residue::primitive_root(modulus)
+pub fn choose_base<T: UnsignedInteger>(modulus: T) -> Residue<T> {
+    choose_base_with_rng(&mut rand::thread_rng(), modulus)
 }
 
-pub fn generate_secret_and_shared_value(base: &Residue) -> (i128, Residue) {
-    let mut rng = rand::thread_rng();
-    let private_secret: i128 = rng.gen();
-    let shared_value = base.pow(private_secret);
+pub fn choose_base_with_rng<T: UnsignedInteger, R: RngCore + CryptoRng + ?Sized>(
+        rng: &mut R, modulus: T) -> Residue<T> {
+    Residue::primitive_root_with_rng(rng, modulus).expect("Modulus has no primitive root.")
+}
+
+pub fn generate_secret_and_shared_value<T: UnsignedInteger>(
+        base: &Residue<T>) -> (T::Signed, Residue<T>) {
+    generate_secret_and_shared_value_with_rng(&mut rand::thread_rng(), base)
+}
+
+/// Draws the secret from [1, p - 1], or from [1, i128::MAX] for u128 moduli
+/// above 2^127, since the secret is a `T::Signed` exponent.
+pub fn generate_secret_and_shared_value_with_rng<T: UnsignedInteger, R: RngCore + CryptoRng + ?Sized>(
+        rng: &mut R, base: &Residue<T>) -> (T::Signed, Residue<T>) {
+    let mut bound = base.modulus.clone() - T::one();
+    if let Some(max) = T::signed_max() {
+        bound = bound.min(max);
+    }
+    let private_secret = (T::random_below(rng, &bound) + T::one()).to_signed();
+    let shared_value = base.pow(private_secret.clone());
     (private_secret, shared_value)
 }
 
-pub fn compute_shared_secret(
-        private_secret: i128, other_shared_value: &Residue) -> Residue {
+pub fn compute_shared_secret<T: UnsignedInteger>(
+        private_secret: T::Signed, other_shared_value: &Residue<T>) -> Residue<T> {
     other_shared_value.pow(private_secret)
 }
 
+/// Turns a shared secret into `length` bytes of key material with HKDF,
+/// binding it to the context in `info` (say, a protocol name and the two
+/// parties' shared values) so that keys for different purposes differ.
+///
+/// The shared secret is a group element, not a uniformly random string, so
+/// it should never be used as a key directly.
+pub fn derive_key<H: Hash, T: UnsignedInteger>(
+        shared_secret: &Residue<T>, salt: &[u8], info: &[u8], length: usize) -> Vec<u8> {
+    hkdf::<H>(salt, &shared_secret.to_bytes(), info, length)
+        .expect("Requested key is too long for HKDF.")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::bigint::BigUint;
 
     #[test]
     fn test_diffie_hellman() {
-        for p in [997, 2677, 454711, 952252135981] {
+        for p in [997u128, 2677, 454711, 952252135981] {
             let base = choose_base(p);
 
             let (alice_secret, alice_shared) =
@@ -37,4 +69,118 @@ mod tests {
                 compute_shared_secret(bob_secret, &alice_shared));
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_diffie_hellman_random_safe_prime() {
+        let p: u128 = crate::primes::random_safe_prime(64);
+        let base = choose_base(p);
+
+        let (alice_secret, alice_shared) = generate_secret_and_shared_value(&base);
+        let (bob_secret, bob_shared) = generate_secret_and_shared_value(&base);
+
+        assert_eq!(
+            compute_shared_secret(alice_secret, &bob_shared),
+            compute_shared_secret(bob_secret, &alice_shared));
+    }
+
+    #[test]
+    fn test_diffie_hellman_modulus_above_i128_max() {
+        // the largest 128-bit prime; secrets drawn below it would not all
+        // fit in an i128
+        let p = u128::MAX - 158;
+        let base = Residue::from_unsigned_integer(3, p);
+
+        for _ in 0..20 {
+            let (alice_secret, alice_shared) = generate_secret_and_shared_value(&base);
+            let (bob_secret, bob_shared) = generate_secret_and_shared_value(&base);
+            assert!(alice_secret > 0 && bob_secret > 0);
+            assert_eq!(
+                compute_shared_secret(alice_secret, &bob_shared),
+                compute_shared_secret(bob_secret, &alice_shared));
+        }
+    }
+
+    #[test]
+    fn test_diffie_hellman_digests_agree() {
+        use crate::hash::{Hash, Sha256};
+
+        let base = choose_base(952252135981u128);
+        let (alice_secret, alice_shared) = generate_secret_and_shared_value(&base);
+        let (bob_secret, bob_shared) = generate_secret_and_shared_value(&base);
+
+        let alice_digest = Sha256::digest(&compute_shared_secret(alice_secret, &bob_shared).to_bytes());
+        let bob_digest = Sha256::digest(&compute_shared_secret(bob_secret, &alice_shared).to_bytes());
+        assert_eq!(alice_digest, bob_digest);
+    }
+
+    #[test]
+    fn test_diffie_hellman_seeded() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let run = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let base = choose_base_with_rng(&mut rng, 952252135981u128);
+            let (alice_secret, alice_shared) = generate_secret_and_shared_value_with_rng(&mut rng, &base);
+            let (bob_secret, bob_shared) = generate_secret_and_shared_value_with_rng(&mut rng, &base);
+            assert_eq!(
+                compute_shared_secret(alice_secret, &bob_shared),
+                compute_shared_secret(bob_secret, &alice_shared));
+            (base, alice_secret, bob_shared)
+        };
+        assert_eq!(run(11), run(11));
+        assert_ne!(run(11), run(12));
+    }
+
+    #[test]
+    fn test_derive_key() {
+        use crate::hash::Sha256;
+
+        let base = choose_base(952252135981u128);
+        let (alice_secret, alice_shared) = generate_secret_and_shared_value(&base);
+        let (bob_secret, bob_shared) = generate_secret_and_shared_value(&base);
+
+        let alice_key = derive_key::<Sha256, _>(
+            &compute_shared_secret(alice_secret, &bob_shared), b"salt", b"encryption", 16);
+        let bob_key = derive_key::<Sha256, _>(
+            &compute_shared_secret(bob_secret, &alice_shared), b"salt", b"encryption", 16);
+        assert_eq!(16, alice_key.len());
+        assert_eq!(alice_key, bob_key);
+
+        let bob_mac_key = derive_key::<Sha256, _>(
+            &compute_shared_secret(bob_secret, &alice_shared), b"salt", b"authentication", 16);
+        assert_ne!(alice_key, bob_mac_key);
+    }
+
+    #[test]
+    fn test_diffie_hellman_rfc3526_group_14() {
+        // 2048-bit MODP group from RFC 3526, section 3, with generator 2
+        let p: BigUint = concat!(
+            "0xFFFFFFFFFFFFFFFFC90FDAA22168C234C4C6628B80DC1CD1",
+            "29024E088A67CC74020BBEA63B139B22514A08798E3404DD",
+            "EF9519B3CD3A431B302B0A6DF25F14374FE1356D6D51C245",
+            "E485B576625E7EC6F44C42E9A637ED6B0BFF5CB6F406B7ED",
+            "EE386BFB5A899FA5AE9F24117C4B1FE649286651ECE45B3D",
+            "C2007CB8A163BF0598DA48361C55D39A69163FA8FD24CF5F",
+            "83655D23DCA3AD961C62F356208552BB9ED529077096966D",
+            "670C354E4ABC9804F1746C08CA18217C32905E462E36CE3B",
+            "E39E772C180E86039B2783A2EC07A28FB5C55DF06F4C52C9",
+            "DE2BCBF6955817183995497CEA956AE515D2261898FA0510",
+            "15728E5A8AACAA68FFFFFFFFFFFFFFFF").parse().unwrap();
+        let base = Residue::from_unsigned_integer(BigUint::from(2u64), p);
+
+        let (alice_secret, alice_shared) =
+            generate_secret_and_shared_value(&base);
+        let (bob_secret, bob_shared) =
+            generate_secret_and_shared_value(&base);
+
+        assert_eq!(
+            compute_shared_secret(alice_secret.clone(), &bob_shared),
+            compute_shared_secret(bob_secret.clone(), &alice_shared));
+
+        let alice_key = derive_key::<crate::hash::Sha512, _>(
+            &compute_shared_secret(alice_secret, &bob_shared), &[], b"group 14", 64);
+        let bob_key = derive_key::<crate::hash::Sha512, _>(
+            &compute_shared_secret(bob_secret, &alice_shared), &[], b"group 14", 64);
+        assert_eq!(alice_key, bob_key);
+    }
+}
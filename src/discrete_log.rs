@@ -0,0 +1,373 @@
+use std::collections::HashMap;
+
+use rand::{CryptoRng, RngCore};
+
+use super::integer::{chinese_remainder, euler_totient, gcd, isqrt, mod_inverse, prime_factorize, UnsignedInteger};
+use super::modular::Residue;
+
+/// Returns x in [0, order) with base^x = target, or None if there is none.
+///
+/// Baby-step giant-step: with m = ceil(sqrt(order)), write x = im + j with
+/// 0 <= i, j < m, tabulate the baby steps base^j, then walk the giant steps
+/// target * base^{-im} until one lands in the table. O(sqrt(order)) time and
+/// memory; order may be any multiple of the order of base.
+pub fn baby_step_giant_step<T: UnsignedInteger>(base: &Residue<T>, target: &Residue<T>, order: &T) -> Option<T> {
+    let mut m = isqrt(order.clone());
+    if m.clone() * m.clone() < *order {
+        m = m + T::one();
+    }
+
+    let mut baby_steps = HashMap::new();
+    let mut step = Residue::from_unsigned_integer(T::one(), base.modulus.clone());
+    let mut j = T::zero();
+    while j < m {
+        baby_steps.entry(step.value.clone()).or_insert_with(|| j.clone());
+        step = step.times(base);
+        j = j + T::one();
+    }
+
+    // base^m has an inverse whenever base does
+    let giant_step = base.pow_unsigned(&m).inv();
+    let mut gamma = target.clone();
+    let mut i = T::zero();
+    while i < m {
+        if let Some(j) = baby_steps.get(&gamma.value) {
+            return Some((i * m + j.clone()) % order.clone());
+        }
+        gamma = gamma.times(&giant_step);
+        i = i + T::one();
+    }
+
+    None
+}
+
+/// Returns x in [0, order) with base^x = target, where order is the order
+/// of base, or None if the walk keeps failing.
+///
+/// Pollard's rho: a pseudorandom walk through elements base^a * target^b,
+/// splitting the group in three by value (multiply by base, by target, or
+/// square). Floyd's cycle finding detects a collision base^a1 target^b1 =
+/// base^a2 target^b2, giving (b1 - b2) x = a2 - a1 (mod order). O(sqrt(order))
+/// time and O(1) memory. Works best for prime order; otherwise each of the
+/// gcd(b1 - b2, order) candidate solutions is tried.
+pub fn pollard_rho<T: UnsignedInteger>(base: &Residue<T>, target: &Residue<T>, order: &T) -> Option<T> {
+    pollard_rho_with_rng(&mut rand::thread_rng(), base, target, order)
+}
+
+/// `pollard_rho` drawing the starting points of its walks from rng.
+pub fn pollard_rho_with_rng<T: UnsignedInteger, R: RngCore + CryptoRng + ?Sized>(
+        rng: &mut R, base: &Residue<T>, target: &Residue<T>, order: &T) -> Option<T> {
+    const ATTEMPTS: u64 = 20;
+    const MAX_CANDIDATES: u64 = 1 << 16;
+
+    let n = order;
+    let three = T::from(3);
+    let step = |(x, a, b): &(Residue<T>, T, T)| -> (Residue<T>, T, T) {
+        match (x.value.clone() % three.clone()).to_u128() {
+            Some(0) => (x.times(x), a.add_mod(a, n), b.add_mod(b, n)),
+            Some(1) => (x.times(base), a.add_mod(&T::one(), n), b.clone()),
+            _ => (x.times(target), a.clone(), b.add_mod(&T::one(), n)),
+        }
+    };
+
+    for _ in 0..ATTEMPTS {
+        let a = T::random_below(rng, n);
+        let b = T::random_below(rng, n);
+        let start = (base.pow_unsigned(&a).times(&target.pow_unsigned(&b)), a, b);
+
+        let mut tortoise = step(&start);
+        let mut hare = step(&tortoise);
+        while tortoise.0 != hare.0 {
+            tortoise = step(&tortoise);
+            hare = step(&step(&hare));
+        }
+
+        // (b1 - b2) x = a2 - a1 (mod n)
+        let (_, a1, b1) = tortoise;
+        let (_, a2, b2) = hare;
+        let r = b1.sub_mod(&b2, n);
+        let s = a2.sub_mod(&a1, n);
+        if r.is_zero() {
+            continue;
+        }
+        let d = gcd(r.clone(), n.clone());
+        if !(s.clone() % d.clone()).is_zero() || d > T::from(MAX_CANDIDATES) {
+            continue;
+        }
+
+        let reduced = n.clone() / d.clone();
+        let x0 = (s / d.clone()).mul_mod(&mod_inverse(r / d.clone(), reduced.clone()).unwrap(), &reduced);
+        let mut candidate = x0;
+        let mut k = T::zero();
+        while k < d {
+            if base.pow_unsigned(&candidate) == *target {
+                return Some(candidate);
+            }
+            candidate = candidate + reduced.clone();
+            k = k + T::one();
+        }
+    }
+
+    None
+}
+
+/// Returns x in [lower, upper] with base^x = target, or None if none is
+/// found or the interval is empty.
+///
+/// Pollard's kangaroo (lambda) method, for exponents known to lie in an
+/// interval of width w: a tame kangaroo starts at base^upper and hops
+/// forward by base^{s(y)} for a jump size s(y) depending on where it is,
+/// leaving a trap where it stops. A wild kangaroo starting at target follows
+/// the same rule; once their paths meet they coincide, so the wild one
+/// falls into the trap. O(sqrt(w)) time and O(1) memory, however large the
+/// group.
+pub fn pollard_kangaroo<T: UnsignedInteger>(
+        base: &Residue<T>, target: &Residue<T>, lower: &T, upper: &T) -> Option<T> {
+    const ATTEMPTS: u64 = 8;
+
+    if lower > upper {
+        return None;
+    }
+    let width = upper.clone() - lower.clone();
+    // jumps 1, 2, 4, ..., 2^(k-1) average about sqrt(w) / 2
+    let k = (width.bits() / 2).max(1) + 1;
+    let jumps: Vec<T> = (0..k).map(|i| T::one() << i).collect();
+    let jump_powers: Vec<Residue<T>> = jumps.iter().map(|s| base.pow_unsigned(s)).collect();
+    let tame_hops = (isqrt(width.clone()) << 1) + T::one();
+
+    for attempt in 0..ATTEMPTS {
+        let index = |y: &Residue<T>| {
+            ((y.value.clone() + T::from(attempt)) % T::from(k as u64)).to_u128().unwrap() as usize
+        };
+
+        // the tame kangaroo, keeping track of how far it went
+        let mut tame = base.pow_unsigned(upper);
+        let mut tame_distance = T::zero();
+        let mut hops = T::zero();
+        while hops < tame_hops {
+            let i = index(&tame);
+            tame_distance = tame_distance + jumps[i].clone();
+            tame = tame.times(&jump_powers[i]);
+            hops = hops + T::one();
+        }
+
+        // the wild one gives up once it has overtaken the trap
+        let mut wild = target.clone();
+        let mut wild_distance = T::zero();
+        let limit = width.clone() + tame_distance.clone();
+        while wild_distance <= limit {
+            if wild == tame {
+                // x + wild_distance = upper + tame_distance
+                let x = upper.clone() + tame_distance - wild_distance;
+                if x >= *lower && x <= *upper {
+                    return Some(x);
+                }
+                break;
+            }
+            let i = index(&wild);
+            wild_distance = wild_distance + jumps[i].clone();
+            wild = wild.times(&jump_powers[i]);
+        }
+    }
+
+    None
+}
+
+/// Returns x in [0, order) with base^x = target, where order is the prime
+/// order of base.
+fn discrete_log_prime_order<T: UnsignedInteger>(base: &Residue<T>, target: &Residue<T>, order: &T) -> Option<T> {
+    if order.bits() <= 32 {
+        baby_step_giant_step(base, target, order)
+    } else {
+        pollard_rho(base, target, order)
+    }
+}
+
+/// Returns the least x >= 0 with base^x = target, given a multiple of the
+/// order of base (such as p - 1 for a prime modulus p), or None if target is
+/// not a power of base or group_order is not a multiple of its order.
+///
+/// Pohlig-Hellman: factor the order with `prime_factorize`, find x modulo
+/// each prime power q^e dividing it one base-q digit at a time (each digit is
+/// a logarithm in the subgroup of order q), and combine with the Chinese
+/// remainder theorem. The work is dominated by sqrt of the largest prime
+/// factor, so a group whose order is smooth offers no security at all, no
+/// matter how large it is.
+pub fn pohlig_hellman<T: UnsignedInteger>(base: &Residue<T>, target: &Residue<T>, group_order: &T) -> Option<T> {
+    let factors = prime_factorize(group_order.clone());
+    let order = base.order_dividing(group_order)?;
+    let one = Residue::from_unsigned_integer(T::one(), base.modulus.clone());
+
+    let mut x = T::zero();
+    let mut modulus = T::one();
+    for (q, _) in factors {
+        // the exponent of q in the order of base
+        let mut prime_power = T::one();
+        while (order.clone() % (prime_power.clone() * q.clone())).is_zero() {
+            prime_power = prime_power * q.clone();
+        }
+        if prime_power == T::one() {
+            continue;
+        }
+
+        // base_q has order q^e and target_q = base_q^(x mod q^e)
+        let cofactor = order.clone() / prime_power.clone();
+        let base_q = base.pow_unsigned(&cofactor);
+        let target_q = target.pow_unsigned(&cofactor);
+        let generator = base_q.pow_unsigned(&(prime_power.clone() / q.clone()));
+
+        // x mod q^e = d_0 + d_1 q + ..., found from the lowest digit up
+        let mut x_q = T::zero();
+        let mut digit_weight = T::one();
+        while digit_weight < prime_power {
+            let remaining = target_q.times(&base_q.pow_unsigned(&x_q).inv());
+            let h = remaining.pow_unsigned(&(prime_power.clone() / (digit_weight.clone() * q.clone())));
+            let digit = if h == one { T::zero() } else { discrete_log_prime_order(&generator, &h, &q)? };
+            x_q = x_q + digit * digit_weight.clone();
+            digit_weight = digit_weight * q.clone();
+        }
+
+        x = chinese_remainder(&x, &modulus, &x_q, &prime_power);
+        modulus = modulus * prime_power;
+    }
+
+    if base.pow_unsigned(&x) == *target { Some(x) } else { None }
+}
+
+/// Returns the least x >= 0 with base^x = target, or None if there is none,
+/// using Pohlig-Hellman over the whole unit group, whose order is
+/// phi(modulus).
+pub fn discrete_log<T: UnsignedInteger>(base: &Residue<T>, target: &Residue<T>) -> Option<T> {
+    assert_eq!(base.modulus, target.modulus, "Base and target have different moduli.");
+    pohlig_hellman(base, target, &euler_totient(base.modulus.clone()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bigint::BigUint;
+    use crate::diffie_hellman::{choose_base, compute_shared_secret, generate_secret_and_shared_value};
+
+    fn residue(value: u128, modulus: u128) -> Residue {
+        Residue::from_unsigned_integer(value, modulus)
+    }
+
+    #[test]
+    fn test_baby_step_giant_step() {
+        // 2 is a primitive root mod 1019
+        let base = residue(2, 1019);
+        for x in [0u128, 1, 2, 500, 1017] {
+            assert_eq!(Some(x), baby_step_giant_step(&base, &base.pow(x as i128), &1018));
+        }
+
+        // 4 only generates the squares
+        let base = residue(4, 1019);
+        assert_eq!(None, baby_step_giant_step(&base, &residue(2, 1019), &509));
+    }
+
+    #[test]
+    fn test_pollard_rho() {
+        // 4 has prime order 509 mod the safe prime 1019
+        let base = residue(4, 1019);
+        for x in [0u128, 1, 100, 508] {
+            assert_eq!(Some(x), pollard_rho(&base, &base.pow(x as i128), &509));
+        }
+
+        // a 33-bit prime order subgroup mod the safe prime p = 2q + 1
+        let q = 4294967681u128;
+        let p = 2 * q + 1;
+        let base = residue(4, p);
+        let x = 3141592653;
+        assert_eq!(Some(x), pollard_rho(&base, &base.pow(x as i128), &q));
+    }
+
+    #[test]
+    fn test_pollard_rho_seeded() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let base = residue(4, 1019);
+        for seed in 0..10 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            assert_eq!(Some(321), pollard_rho_with_rng(&mut rng, &base, &base.pow(321), &509));
+        }
+    }
+
+    #[test]
+    fn test_pollard_kangaroo() {
+        // a 61-bit prime, far too big to search, but with the exponent
+        // known to lie in an interval of width 2^24
+        let p = (1u128 << 61) - 1;
+        let base = residue(37, p);
+        let (lower, upper) = (1u128 << 40, (1u128 << 40) + (1 << 24));
+        for x in [lower, lower + 1, lower + 1234567, upper] {
+            assert_eq!(Some(x), pollard_kangaroo(&base, &base.pow(x as i128), &lower, &upper));
+        }
+
+        // an empty interval, which would otherwise underflow the width
+        assert_eq!(None, pollard_kangaroo(&base, &base.pow(5), &10, &3));
+        assert_eq!(Some(7), pollard_kangaroo(&base, &base.pow(7), &7, &7));
+    }
+
+    /// Returns the least prime of the form k * (product of primes up to
+    /// bound) + 1, so that p - 1 has no large prime factors.
+    fn smooth_prime(bound: u64) -> u128 {
+        let primorial: u128 = crate::integer::primes_up_to(bound).iter().map(|&q| q as u128).product();
+        (1..).map(|k| k * primorial + 1).find(|&p| crate::integer::is_prime(p)).unwrap()
+    }
+
+    #[test]
+    fn test_pohlig_hellman() {
+        let p = smooth_prime(53);
+        let base = choose_base(p);
+        let x = 31415926535897932384626433u128 % (p - 1);
+        assert_eq!(Some(x), pohlig_hellman(&base, &base.pow(x as i128), &(p - 1)));
+        assert_eq!(Some(x), discrete_log(&base, &base.pow(x as i128)));
+
+        // a base of smaller order, with the answer reduced modulo it
+        let square = base.times(&base);
+        let y = pohlig_hellman(&square, &square.pow(x as i128), &(p - 1)).unwrap();
+        assert_eq!(x % ((p - 1) / 2), y);
+
+        assert_eq!(None, pohlig_hellman(&square, &base, &(p - 1)));
+    }
+
+    #[test]
+    fn test_smooth_diffie_hellman_is_broken() {
+        // a 90-bit prime with p - 1 smooth: an eavesdropper recovers Alice's
+        // secret from her public value alone
+        let p = smooth_prime(71);
+        assert!(p.bits() >= 90);
+        let base = choose_base(p);
+        let (alice_secret, alice_shared) = generate_secret_and_shared_value(&base);
+        let (_, bob_shared) = generate_secret_and_shared_value(&base);
+
+        let recovered = discrete_log(&base, &alice_shared).unwrap();
+        assert_eq!(alice_shared, base.pow(recovered as i128));
+        assert_eq!(
+            compute_shared_secret(alice_secret, &bob_shared),
+            compute_shared_secret(recovered as i128, &bob_shared));
+    }
+
+    #[test]
+    fn test_discrete_log_big_integers() {
+        // the RFC 3526 2048-bit group has a safe prime modulus, so 2 has
+        // order (p - 1) / 2, a 2047-bit prime; small exponents are still
+        // easy with the kangaroo
+        let p: BigUint = concat!(
+            "0xFFFFFFFFFFFFFFFFC90FDAA22168C234C4C6628B80DC1CD1",
+            "29024E088A67CC74020BBEA63B139B22514A08798E3404DD",
+            "EF9519B3CD3A431B302B0A6DF25F14374FE1356D6D51C245",
+            "E485B576625E7EC6F44C42E9A637ED6B0BFF5CB6F406B7ED",
+            "EE386BFB5A899FA5AE9F24117C4B1FE649286651ECE45B3D",
+            "C2007CB8A163BF0598DA48361C55D39A69163FA8FD24CF5F",
+            "83655D23DCA3AD961C62F356208552BB9ED529077096966D",
+            "670C354E4ABC9804F1746C08CA18217C32905E462E36CE3B",
+            "E39E772C180E86039B2783A2EC07A28FB5C55DF06F4C52C9",
+            "DE2BCBF6955817183995497CEA956AE515D2261898FA0510",
+            "15728E5A8AACAA68FFFFFFFFFFFFFFFF").parse().unwrap();
+        let base = Residue::from_unsigned_integer(BigUint::from(2u64), p);
+        let x = BigUint::from(987654u64);
+        let target = base.pow_unsigned(&x);
+        assert_eq!(Some(x), pollard_kangaroo(&base, &target, &BigUint::from(0u64), &BigUint::from(1u64 << 20)));
+    }
+}
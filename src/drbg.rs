@@ -0,0 +1,476 @@
+use std::error::Error;
+use std::fmt;
+
+use rand::{CryptoRng, RngCore};
+
+use super::chacha20::chacha20_xor;
+use super::hash::{Hash, Sha512};
+use super::hmac::Hmac;
+
+/// Entropy inputs must carry at least this many bytes, the 256-bit security
+/// strength both generators here are meant for.
+pub const MIN_ENTROPY_LENGTH: usize = 32;
+
+/// The most bytes a single `generate` call may return (2^19 bits, the limit
+/// SP 800-90A sets for HMAC_DRBG).
+pub const MAX_REQUEST_LENGTH: usize = 1 << 16;
+
+/// How many `generate` calls are allowed between reseeds (SP 800-90A,
+/// table 2).
+pub const RESEED_INTERVAL: u64 = 1 << 48;
+
+/// An error from a deterministic random bit generator.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DrbgError {
+    /// The entropy input is shorter than `MIN_ENTROPY_LENGTH`.
+    InsufficientEntropy,
+    /// More than `MAX_REQUEST_LENGTH` bytes were asked for at once.
+    RequestTooLarge,
+    /// `RESEED_INTERVAL` requests have been served since the last reseed.
+    ReseedRequired,
+}
+
+impl fmt::Display for DrbgError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DrbgError::InsufficientEntropy => write!(f, "insufficient entropy"),
+            DrbgError::RequestTooLarge => write!(f, "request too large"),
+            DrbgError::ReseedRequired => write!(f, "reseed required"),
+        }
+    }
+}
+
+impl Error for DrbgError {}
+
+/// Checks a generate request against the limits shared by both generators.
+fn check_request(length: usize, reseed_counter: u64) -> Result<(), DrbgError> {
+    if length > MAX_REQUEST_LENGTH {
+        return Err(DrbgError::RequestTooLarge);
+    }
+    if reseed_counter > RESEED_INTERVAL {
+        return Err(DrbgError::ReseedRequired);
+    }
+    Ok(())
+}
+
+/// HMAC_DRBG from NIST SP 800-90A, section 10.1.2, over hash H.
+///
+/// The state is a key K and a chaining value V, each one digest long. Output
+/// is V = HMAC(K, V) iterated, and after every request K and V are replaced
+/// by a one-way update, so a compromised state doesn't reveal earlier
+/// output (backtracking resistance).
+#[derive(Clone, Debug)]
+pub struct HmacDrbg<H: Hash> {
+    key: Vec<u8>,
+    value: Vec<u8>,
+    reseed_counter: u64,
+    hash: std::marker::PhantomData<H>,
+}
+
+impl<H: Hash> HmacDrbg<H> {
+    /// HMAC_DRBG_Update: mixes provided_data (possibly empty) into K and V.
+    fn update(&mut self, provided_data: &[&[u8]]) {
+        for separator in [0x00, 0x01] {
+            let mut hmac = Hmac::<H>::new(&self.key);
+            hmac.update(&self.value);
+            hmac.update(&[separator]);
+            for data in provided_data {
+                hmac.update(data);
+            }
+            self.key = hmac.finalize();
+            self.value = Hmac::<H>::mac(&self.key, &self.value);
+
+            if provided_data.iter().all(|data| data.is_empty()) {
+                break;
+            }
+        }
+    }
+
+    /// Instantiates the generator from entropy, a nonce (which may be more
+    /// entropy, or something never repeated such as a timestamp) and an
+    /// optional personalization string.
+    pub fn instantiate(entropy: &[u8], nonce: &[u8], personalization: &[u8]) -> Result<HmacDrbg<H>, DrbgError> {
+        if entropy.len() < MIN_ENTROPY_LENGTH {
+            return Err(DrbgError::InsufficientEntropy);
+        }
+
+        let mut drbg = HmacDrbg {
+            key: vec![0x00; H::OUTPUT_SIZE],
+            value: vec![0x01; H::OUTPUT_SIZE],
+            reseed_counter: 1,
+            hash: std::marker::PhantomData,
+        };
+        drbg.update(&[entropy, nonce, personalization]);
+        Ok(drbg)
+    }
+
+    /// Mixes fresh entropy (and optional additional input) into the state.
+    pub fn reseed(&mut self, entropy: &[u8], additional_input: &[u8]) -> Result<(), DrbgError> {
+        if entropy.len() < MIN_ENTROPY_LENGTH {
+            return Err(DrbgError::InsufficientEntropy);
+        }
+        self.update(&[entropy, additional_input]);
+        self.reseed_counter = 1;
+        Ok(())
+    }
+
+    /// Fills output with pseudorandom bytes, after mixing in the optional
+    /// additional input.
+    pub fn generate(&mut self, output: &mut [u8], additional_input: &[u8]) -> Result<(), DrbgError> {
+        check_request(output.len(), self.reseed_counter)?;
+        if !additional_input.is_empty() {
+            self.update(&[additional_input]);
+        }
+
+        for chunk in output.chunks_mut(H::OUTPUT_SIZE) {
+            self.value = Hmac::<H>::mac(&self.key, &self.value);
+            chunk.copy_from_slice(&self.value[..chunk.len()]);
+        }
+
+        self.update(&[additional_input]);
+        self.reseed_counter += 1;
+        Ok(())
+    }
+}
+
+impl<H: Hash> RngCore for HmacDrbg<H> {
+    fn next_u32(&mut self) -> u32 {
+        let mut bytes = [0; 4];
+        self.fill_bytes(&mut bytes);
+        u32::from_le_bytes(bytes)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0; 8];
+        self.fill_bytes(&mut bytes);
+        u64::from_le_bytes(bytes)
+    }
+
+    /// Panics if a reseed is due, which takes 2^48 requests.
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.try_fill_bytes(dest).expect("HMAC_DRBG needs reseeding.")
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        for chunk in dest.chunks_mut(MAX_REQUEST_LENGTH) {
+            self.generate(chunk, &[]).map_err(rand::Error::new)?;
+        }
+        Ok(())
+    }
+}
+
+impl<H: Hash> CryptoRng for HmacDrbg<H> {}
+
+/// A DRBG with the structure of CTR_DRBG (SP 800-90A, section 10.2), with
+/// the ChaCha20 keystream in place of a block cipher in counter mode.
+///
+/// The state is a ChaCha20 key and a 96-bit value V used as the nonce.
+/// Output is the keystream from block 1 on; block 0 is reserved for the
+/// update, which replaces the key and V after every request. Seed material
+/// is condensed with SHA-512 instead of the block cipher derivation
+/// function.
+///
+/// This construction is not one of those SP 800-90A approves, and it has no
+/// official test vectors.
+#[derive(Clone, Debug)]
+pub struct ChaChaDrbg {
+    key: [u8; 32],
+    value: [u8; 12],
+    reseed_counter: u64,
+}
+
+impl ChaChaDrbg {
+    /// Length of the key plus V.
+    const SEED_LENGTH: usize = 44;
+
+    /// Condenses arbitrary-length seed material to SEED_LENGTH bytes.
+    fn derive(parts: &[&[u8]]) -> [u8; ChaChaDrbg::SEED_LENGTH] {
+        let mut hash = Sha512::new();
+        for part in parts {
+            hash.update(part);
+        }
+        hash.finalize()[..ChaChaDrbg::SEED_LENGTH].try_into().unwrap()
+    }
+
+    /// CTR_DRBG_Update: the next key and V are the first block of keystream
+    /// XORed with provided_data.
+    fn update(&mut self, provided_data: &[u8; ChaChaDrbg::SEED_LENGTH]) {
+        let mut temp = *provided_data;
+        chacha20_xor(&self.key, 0, &self.value, &mut temp);
+        self.key.copy_from_slice(&temp[..32]);
+        self.value.copy_from_slice(&temp[32..]);
+    }
+
+    /// Instantiates the generator from entropy, a nonce and an optional
+    /// personalization string.
+    pub fn instantiate(entropy: &[u8], nonce: &[u8], personalization: &[u8]) -> Result<ChaChaDrbg, DrbgError> {
+        if entropy.len() < MIN_ENTROPY_LENGTH {
+            return Err(DrbgError::InsufficientEntropy);
+        }
+
+        let mut drbg = ChaChaDrbg { key: [0; 32], value: [0; 12], reseed_counter: 1 };
+        drbg.update(&ChaChaDrbg::derive(&[entropy, nonce, personalization]));
+        Ok(drbg)
+    }
+
+    /// Mixes fresh entropy (and optional additional input) into the state.
+    pub fn reseed(&mut self, entropy: &[u8], additional_input: &[u8]) -> Result<(), DrbgError> {
+        if entropy.len() < MIN_ENTROPY_LENGTH {
+            return Err(DrbgError::InsufficientEntropy);
+        }
+        self.update(&ChaChaDrbg::derive(&[entropy, additional_input]));
+        self.reseed_counter = 1;
+        Ok(())
+    }
+
+    /// Fills output with pseudorandom bytes, after mixing in the optional
+    /// additional input.
+    pub fn generate(&mut self, output: &mut [u8], additional_input: &[u8]) -> Result<(), DrbgError> {
+        check_request(output.len(), self.reseed_counter)?;
+        let provided_data = if additional_input.is_empty() {
+            [0; ChaChaDrbg::SEED_LENGTH]
+        } else {
+            let derived = ChaChaDrbg::derive(&[additional_input]);
+            self.update(&derived);
+            derived
+        };
+
+        output.fill(0);
+        chacha20_xor(&self.key, 1, &self.value, output);
+
+        self.update(&provided_data);
+        self.reseed_counter += 1;
+        Ok(())
+    }
+}
+
+impl RngCore for ChaChaDrbg {
+    fn next_u32(&mut self) -> u32 {
+        let mut bytes = [0; 4];
+        self.fill_bytes(&mut bytes);
+        u32::from_le_bytes(bytes)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0; 8];
+        self.fill_bytes(&mut bytes);
+        u64::from_le_bytes(bytes)
+    }
+
+    /// Panics if a reseed is due, which takes 2^48 requests.
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.try_fill_bytes(dest).expect("ChaCha DRBG needs reseeding.")
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        for chunk in dest.chunks_mut(MAX_REQUEST_LENGTH) {
+            self.generate(chunk, &[]).map_err(rand::Error::new)?;
+        }
+        Ok(())
+    }
+}
+
+impl CryptoRng for ChaChaDrbg {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::Sha256;
+    use crate::rsa::RsaPrivateKey;
+    use crate::test_util::{bytes, hex};
+
+    /// Generates twice and returns the second output, as the CAVP DRBG
+    /// tests do.
+    fn second_output<F>(mut generate: F, length: usize, additional_inputs: [&[u8]; 2]) -> String
+    where
+        F: FnMut(&mut [u8], &[u8]) -> Result<(), DrbgError>,
+    {
+        let mut output = vec![0; length];
+        for additional_input in additional_inputs {
+            generate(&mut output, additional_input).unwrap();
+        }
+        hex(&output)
+    }
+
+    #[test]
+    fn test_hmac_drbg_cavp() {
+        // HMAC_DRBG.rsp from the CAVP DRBG test vectors (no reseed).
+        // SHA-256, COUNT = 0
+        let entropy = bytes("ca851911349384bffe89de1cbdc46e6831e44d34a4fb935ee285dd14b71a7488");
+        let nonce = bytes("659ba96c601dc69fc902940805ec0ca8");
+        let mut drbg = HmacDrbg::<Sha256>::instantiate(&entropy, &nonce, &[]).unwrap();
+        assert_eq!(
+            concat!("e528e9abf2dece54d47c7e75e5fe302149f817ea9fb4bee6f4199697d04d5b89",
+                    "d54fbb978a15b5c443c9ec21036d2460b6f73ebad0dc2aba6e624abf07745bc1",
+                    "07694bb7547bb0995f70de25d6b29e2d3011bb19d27676c07162c8b5ccde0668",
+                    "961df86803482cb37ed6d5c0bb8d50cf1f50d476aa0458bdaba806f48be9dcb8"),
+            second_output(|output, input| drbg.generate(output, input), 128, [&[], &[]]));
+
+        // SHA-256, PersonalizationStringLen = 256, COUNT = 0
+        let entropy = bytes("5cacc68165a2e2ee20812f35ec73a79dbf30fd475476ac0c44fc6174cdac2b55");
+        let nonce = bytes("6f885496c1e63af620becd9e71ecb824");
+        let personalization = bytes("e72dd8590d4ed5295515c35ed6199e9d211b8f069b3058caa6670b96ef1208d0");
+        let mut drbg = HmacDrbg::<Sha256>::instantiate(&entropy, &nonce, &personalization).unwrap();
+        assert_eq!(
+            concat!("f1012cf543f94533df27fedfbf58e5b79a3dc517a9c402bdbfc9a0c0f721f9d5",
+                    "3faf4aafdc4b8f7a1b580fcaa52338d4bd95f58966a243cdcd3f446ed4bc546d",
+                    "9f607b190dd69954450d16cd0e2d6437067d8b44d19a6af7a7cfa8794e5fbd72",
+                    "8e8fb2f2e8db5dd4ff1aa275f35886098e80ff844886060da8b1e7137846b23b"),
+            second_output(|output, input| drbg.generate(output, input), 128, [&[], &[]]));
+
+        // SHA-512, COUNT = 0
+        let entropy = bytes("35049f389a33c0ecb1293238fd951f8ffd517dfde06041d32945b3e26914ba15");
+        let nonce = bytes("f7328760be6168e6aa9fb54784989a11");
+        let mut drbg = HmacDrbg::<Sha512>::instantiate(&entropy, &nonce, &[]).unwrap();
+        assert_eq!(
+            concat!("e76491b0260aacfded01ad39fbf1a66a88284caa5123368a2ad9330ee48335e3",
+                    "c9c9ba90e6cbc9429962d60c1a6661edcfaa31d972b8264b9d4562cf18494128",
+                    "a092c17a8da6f3113e8a7edfcd4427082bd390675e9662408144971717303d8d",
+                    "c352c9e8b95e7f35fa2ac9f549b292bc7c4bc7f01ee0a577859ef6e82d79ef23",
+                    "892d167c140d22aac32b64ccdfeee2730528a38763b24227f91ac3ffe47fb115",
+                    "38e435307e77481802b0f613f370ffb0dbeab774fe1efbb1a80d01154a9459e7",
+                    "3ad361108bbc86b0914f095136cbe634555ce0bb263618dc5c367291ce082551",
+                    "8987154fe9ecb052b3f0a256fcc30cc14572531c9628973639beda456f2bddf6"),
+            second_output(|output, input| drbg.generate(output, input), 256, [&[], &[]]));
+    }
+
+    #[test]
+    fn test_hmac_drbg_cavp_reseed() {
+        // HMAC_DRBG.rsp from the CAVP DRBG test vectors (prediction resistance
+        // disabled), which reseed between instantiating and generating.
+        // SHA-256, AdditionalInputLen = 256, COUNT = 0
+        let entropy = bytes("05ac9fc4c62a02e3f90840da5616218c6de5743d66b8e0fbf833759c5928b53d");
+        let nonce = bytes("2b89a17904922ed8f017a63044848545");
+        let mut drbg = HmacDrbg::<Sha256>::instantiate(&entropy, &nonce, &[]).unwrap();
+        drbg.reseed(
+            &bytes("2791126b8b52ee1fd9392a0a13e0083bed4186dc649b739607ac70ec8dcecf9b"),
+            &bytes("43bac13bae715092cf7eb280a2e10a962faf7233c41412f69bc74a35a584e54c")).unwrap();
+        let first = bytes("3f2fed4b68d506ecefa21f3f5bb907beb0f17dbc30f6ffbba5e5861408c53a1e");
+        let second = bytes("529030df50f410985fde068df82b935ec23d839cb4b269414c0ede6cffea5b68");
+        assert_eq!(
+            concat!("02ddff5173da2fcffa10215b030d660d61179e61ecc22609b1151a75f1cbcbb4",
+                    "363c3a89299b4b63aca5e581e73c860491010aa35de3337cc6c09ebec8c91a62",
+                    "87586f3a74d9694b462d2720ea2e11bbd02af33adefb4a16e6b370fa0effd57d",
+                    "607547bdcfbb7831f54de7073ad2a7da987a0016a82fa958779a168674b56524"),
+            second_output(|output, input| drbg.generate(output, input), 128, [&first, &second]));
+
+        // SHA-512, COUNT = 0
+        let entropy = bytes("48c121b18733af15c27e1dd9ba66a9a81a5579cdba0f5b657ec53c2b9e90bbf6");
+        let nonce = bytes("bbb7c777428068fad9970891f879b1af");
+        let mut drbg = HmacDrbg::<Sha512>::instantiate(&entropy, &nonce, &[]).unwrap();
+        drbg.reseed(&bytes("e0ffefdadb9ccf990504d568bdb4d862cbe17ccce6e22dfcab8b4804fd21421a"), &[]).unwrap();
+        assert_eq!(
+            concat!("05da6aac7d980da038f65f392841476d37fe70fbd3e369d1f80196e66e54b8fa",
+                    "db1d60e1a0f3d4dc173769d75fc3410549d7a843270a54a068b4fe767d7d9a59",
+                    "604510a875ad1e9731c8afd0fd50b825e2c50d062576175106a9981be37e02ec",
+                    "7c5cd0a69aa0ca65bddaee1b0de532e10cfa1f5bf6a026e47379736a099d6750",
+                    "ab121dbe3622b841baf8bdcbe875c85ba4b586b8b5b57b0fecbec08c12ff2a94",
+                    "53c47c6e32a52103d972c62ab9affb8e728a31fcefbbccc556c0f0a35f4b10ac",
+                    "e2d96b906e36cbb72233201e536d3e13b045187b417d2449cad1edd192e061f1",
+                    "2d22147b0a176ea8d9c4c35404395b6502ef333a813b6586037479e0fa3c6a23"),
+            second_output(|output, input| drbg.generate(output, input), 256, [&[], &[]]));
+    }
+
+    #[test]
+    fn test_hmac_drbg_additional_inputs() {
+        // personalization string and additional inputs, in the CAVP format;
+        // checked against OpenSSL's HMAC-DRBG
+        let entropy: Vec<u8> = (0x00..0x20).collect();
+        let nonce: Vec<u8> = (0x20..0x30).collect();
+        let personalization: Vec<u8> = (0x40..0x60).collect();
+        let (first, second): (Vec<u8>, Vec<u8>) = ((0x60..0x80).collect(), (0x80..0xa0).collect());
+        let additional_inputs = [&first[..], &second[..]];
+        let mut sha256 = HmacDrbg::<Sha256>::instantiate(&entropy, &nonce, &personalization).unwrap();
+        let mut sha512 = HmacDrbg::<Sha512>::instantiate(&entropy, &nonce, &personalization).unwrap();
+
+        assert_eq!(
+            concat!("9de19320af8682520197e71e8972e4a3ccf798a04f2d9ea80f613d2543b04dd3",
+                    "e6559ac792e7aabe240a39b841cab9c9f9c134f8347aa095eb3fe6efa72bd4dd",
+                    "1acd9cacb53a75e1731e30d0e23573da6950144a6daaca08177e05cc85ef4553",
+                    "e76234c7e4f7c96c0b490804ef680cc8ad6a5b91b55b20cad77bf7794557bd4f"),
+            second_output(|output, input| sha256.generate(output, input), 128, additional_inputs));
+        assert_eq!(
+            concat!("3af072928545d10d719da16f0aa0a07db104bd5559fe6874ecfcebb37df785b9",
+                    "d2b8688f0aa16a70ba7f5b4458efaee8875acec9d115b1682f37f2741003f276",
+                    "5d4abf4d907b457046a5347f8dba78ed8ae3f44c1ad90e2177fda7ac37c394dd",
+                    "f2d4d094cffd4647bed595037492fd38ea141cc85812911ea66415d890b59c69",
+                    "92b20b0fb0b531814c8572881ef45f328a0ca31b6cdb101e0ff42219d8d71917",
+                    "d727e900c1acab895924a75fca4ea5f0f36e0c762b9c59df04b874e1e6c805a7",
+                    "443ab4b120f1c660aaf3aa25ce92f1a3eee52ada24e3ff7c8dbdf6e94ed073ed",
+                    "9bf225cd7f9a7387cfa0816c3f19363ca36aa3e1eee3f3d5f6b7b32abf89a323"),
+            second_output(|output, input| sha512.generate(output, input), 256, additional_inputs));
+    }
+
+    #[test]
+    fn test_chacha_drbg() {
+        // known answers from an independent implementation of the same
+        // construction over the Python cryptography package's ChaCha20
+        let entropy: Vec<u8> = (0x00..0x20).collect();
+        let nonce: Vec<u8> = (0x20..0x30).collect();
+        let personalization: Vec<u8> = (0x40..0x60).collect();
+        let first: Vec<u8> = (0x60..0x80).collect();
+        let second: Vec<u8> = (0x80..0xa0).collect();
+        let mut drbg = ChaChaDrbg::instantiate(&entropy, &nonce, &personalization).unwrap();
+
+        assert_eq!(
+            concat!("e499d8dca8d274a4389ca08da60b6f7ca1dd5220896ac6498149db1d47482e3c",
+                    "ade1c7eadb6cc3a14c8c478cad8a66474040e2aeb7a6c4c58227ffe25c45eb5b"),
+            second_output(|output, input| drbg.generate(output, input), 64, [&first, &second]));
+
+        let mut drbg = ChaChaDrbg::instantiate(&entropy, &nonce, &[]).unwrap();
+        let mut output = [0; 64];
+        drbg.generate(&mut output, &[]).unwrap();
+        drbg.reseed(&first, &second).unwrap();
+        drbg.generate(&mut output, &[]).unwrap();
+        assert_eq!(
+            concat!("87317554cc32af8305f6eabb042728c875e5621d7ed361f2cf5cde27d49c2147",
+                    "93f65d7ac390a8539b3251aac14a83104ab0fbcc6197f6d2e1f467aaf5f4c63c"),
+            hex(&output));
+    }
+
+    #[test]
+    fn test_reseed() {
+        let entropy = [1; 32];
+        let mut plain = HmacDrbg::<Sha256>::instantiate(&entropy, &[], &[]).unwrap();
+        let mut reseeded = plain.clone();
+        let mut again = plain.clone();
+        reseeded.reseed(&[2; 32], b"extra").unwrap();
+        again.reseed(&[2; 32], b"extra").unwrap();
+
+        let outputs: Vec<[u8; 32]> = [&mut plain, &mut reseeded, &mut again].into_iter().map(|drbg| {
+            let mut output = [0; 32];
+            drbg.generate(&mut output, &[]).unwrap();
+            output
+        }).collect();
+        assert_ne!(outputs[0], outputs[1]);
+        assert_eq!(outputs[1], outputs[2]);
+
+        assert_eq!(Err(DrbgError::InsufficientEntropy), plain.reseed(&[2; 31], &[]));
+        assert_eq!(Err(DrbgError::InsufficientEntropy), ChaChaDrbg::instantiate(&[0; 16], &[], &[]).map(|_| ()));
+
+        plain.reseed_counter = RESEED_INTERVAL + 1;
+        assert_eq!(Err(DrbgError::ReseedRequired), plain.generate(&mut [0; 16], &[]));
+        plain.reseed(&[3; 32], &[]).unwrap();
+        assert_eq!(Ok(()), plain.generate(&mut [0; 16], &[]));
+        assert_eq!(Err(DrbgError::RequestTooLarge), plain.generate(&mut vec![0; MAX_REQUEST_LENGTH + 1], &[]));
+    }
+
+    #[test]
+    fn test_drbg_drives_key_generation() {
+        let seed = [7; 32];
+        let mut first = HmacDrbg::<Sha256>::instantiate(&seed, b"nonce", b"rsa").unwrap();
+        let mut second = HmacDrbg::<Sha256>::instantiate(&seed, b"nonce", b"rsa").unwrap();
+        let key: RsaPrivateKey = RsaPrivateKey::generate_with_rng(&mut first, 120);
+        assert_eq!(key, RsaPrivateKey::generate_with_rng(&mut second, 120));
+
+        let mut drbg = ChaChaDrbg::instantiate(&seed, b"nonce", b"dh").unwrap();
+        let base = crate::diffie_hellman::choose_base_with_rng(&mut drbg, 952252135981u128);
+        let (secret, shared) = crate::diffie_hellman::generate_secret_and_shared_value_with_rng(&mut drbg, &base);
+        assert_eq!(shared, base.pow(secret));
+
+        // requests longer than MAX_REQUEST_LENGTH are split up
+        let mut long = vec![0; 3 * MAX_REQUEST_LENGTH];
+        drbg.fill_bytes(&mut long);
+        assert!(long[2 * MAX_REQUEST_LENGTH..].iter().any(|&b| b != 0));
+    }
+}
@@ -0,0 +1,298 @@
+use super::integer::{gcd, primes_up_to, UnsignedInteger};
+use super::modular::Residue;
+
+/// A point on a Montgomery curve B y^2 = x^3 + A x^2 + x, in projective
+/// (X : Z) coordinates with the y-coordinate dropped.
+///
+/// Without y we cannot tell P from -P, but x(P + Q) can still be computed
+/// from x(P), x(Q) and x(P - Q), which is all the Montgomery ladder needs.
+#[derive(Clone, Debug)]
+struct Point<T> {
+    x: Residue<T>,
+    z: Residue<T>,
+}
+
+/// A Montgomery curve modulo n, represented by (A + 2) / 4.
+struct Curve<T> {
+    a24: Residue<T>,
+}
+
+impl<T: UnsignedInteger> Curve<T> {
+    /// Returns 2P.
+    fn double(&self, p: &Point<T>) -> Point<T> {
+        let sum = p.x.plus(&p.z);
+        let diff = p.x.minus(&p.z);
+        let sum_squared = sum.times(&sum);
+        let diff_squared = diff.times(&diff);
+        let four_xz = sum_squared.minus(&diff_squared);
+
+        Point {
+            x: sum_squared.times(&diff_squared),
+            z: four_xz.times(&diff_squared.plus(&self.a24.times(&four_xz))),
+        }
+    }
+
+    /// Returns P + Q, given P - Q.
+    fn add(&self, p: &Point<T>, q: &Point<T>, difference: &Point<T>) -> Point<T> {
+        let u = p.x.minus(&p.z).times(&q.x.plus(&q.z));
+        let v = p.x.plus(&p.z).times(&q.x.minus(&q.z));
+        let sum = u.plus(&v);
+        let diff = u.minus(&v);
+
+        Point {
+            x: difference.z.times(&sum.times(&sum)),
+            z: difference.x.times(&diff.times(&diff)),
+        }
+    }
+
+    /// Returns kP, for k >= 1, using the Montgomery ladder.
+    ///
+    /// Maintains the pair (jP, (j+1)P), whose difference is always P, while
+    /// running through the bits of k.
+    fn multiply(&self, p: &Point<T>, k: u64) -> Point<T> {
+        let mut r0 = p.clone();
+        let mut r1 = self.double(p);
+
+        for i in (0..u64::BITS - 1 - k.leading_zeros()).rev() {
+            if (k >> i) & 1 == 1 {
+                r0 = self.add(&r1, &r0, p);
+                r1 = self.double(&r1);
+            } else {
+                r1 = self.add(&r1, &r0, p);
+                r0 = self.double(&r0);
+            }
+        }
+
+        r0
+    }
+}
+
+/// Lenstra's elliptic curve method, with Montgomery curves and a
+/// baby-step giant-step stage 2.
+///
+/// For a prime factor p of n, the group of points on a random curve mod p
+/// has order in [p + 1 - 2 sqrt(p), p + 1 + 2 sqrt(p)]. If that order is
+/// b1-smooth apart from at most one prime in (b1, b2], multiplying a point
+/// by every prime below b2 gives the identity mod p, which shows up as a
+/// Z-coordinate sharing the factor p with n. Every curve is a fresh chance,
+/// unlike with Pollard p-1 where the group order p - 1 is fixed.
+pub struct Ecm {
+    /// Stage 1 bound.
+    pub b1: u64,
+    /// Stage 2 bound; set equal to b1 to skip stage 2.
+    pub b2: u64,
+    /// Number of curves to try before giving up.
+    pub curves: u32,
+}
+
+impl Ecm {
+    /// Spacing of the stage 2 giant steps; 2 * 3 * 5 * 7 so that most baby
+    /// steps can be skipped as non-coprime.
+    const D: u64 = 210;
+
+    /// Returns parameters suited to finding factors of the given number of
+    /// decimal digits, following the table of optimal B1 values and expected
+    /// curve counts from GMP-ECM.
+    pub fn for_factor_digits(digits: u32) -> Ecm {
+        let (b1, curves) = match digits {
+            0..=15 => (2_000, 25),
+            16..=20 => (11_000, 90),
+            21..=25 => (50_000, 300),
+            26..=30 => (250_000, 700),
+            _ => (1_000_000, 1800),
+        };
+        Ecm { b1, b2: 100 * b1, curves }
+    }
+
+    /// Tries up to `curves` random curves, returning a non-trivial factor of
+    /// n if one is found.
+    pub fn factor<T: UnsignedInteger>(&self, n: &T) -> Option<T> {
+        let mut rng = rand::thread_rng();
+
+        for _ in 0..self.curves {
+            // Suyama's parameter must avoid 0, +-1, +-3, +-5, which give
+            // singular or degenerate curves
+            let sigma = T::random_below(&mut rng, &(n.clone() - T::from(6))) + T::from(6);
+            if let Some(d) = self.try_curve(n, &sigma) {
+                return Some(d);
+            }
+        }
+
+        None
+    }
+
+    /// Runs stage 1 and stage 2 on the curve with Suyama parameter sigma.
+    ///
+    /// Suyama's parameterisation sets u = sigma^2 - 5, v = 4 sigma, and takes
+    /// the curve with (A + 2) / 4 = (v - u)^3 (3u + v) / (16 u^3 v) and
+    /// starting point (u^3 : v^3). Its group order is always divisible by
+    /// 12, which improves the odds of it being smooth.
+    pub fn try_curve<T: UnsignedInteger>(&self, n: &T, sigma: &T) -> Option<T> {
+        let residue = |k: u64| Residue::from_unsigned_integer(T::from(k), n.clone());
+        let sigma = Residue::from_unsigned_integer(sigma.clone(), n.clone());
+
+        let u = sigma.times(&sigma).minus(&residue(5));
+        let v = residue(4).times(&sigma);
+        let u_cubed = u.times(&u).times(&u);
+        let v_minus_u = v.minus(&u);
+
+        let numerator = v_minus_u.times(&v_minus_u).times(&v_minus_u)
+            .times(&residue(3).times(&u).plus(&v));
+        let denominator = residue(16).times(&u_cubed).times(&v);
+
+        // a non-invertible denominator is already a (lucky) factor
+        let g = gcd(denominator.value.clone(), n.clone());
+        if g != T::one() {
+            return if g != *n { Some(g) } else { None };
+        }
+
+        let curve = Curve { a24: numerator.times(&denominator.inv()) };
+        let mut point = Point { x: u_cubed, z: v.times(&v).times(&v) };
+
+        // stage 1: multiply by every prime power up to b1
+        let primes = primes_up_to(self.b1);
+        for &p in primes.iter() {
+            let mut pk = p;
+            while pk <= self.b1 / p {
+                pk *= p;
+            }
+            point = curve.multiply(&point, pk);
+        }
+
+        let g = gcd(point.z.value.clone(), n.clone());
+        if g == *n {
+            return None;
+        }
+        if g != T::one() {
+            return Some(g);
+        }
+
+        if self.b2 > self.b1 {
+            return self.stage_2(n, &curve, &point);
+        }
+        None
+    }
+
+    /// Standard continuation: looks for a single prime q in (b1, b2] with
+    /// qQ = O (mod p), where Q is the stage 1 output.
+    ///
+    /// Every such q can be written as q = mD +- j with j < D/2 coprime to D.
+    /// Since x(mDQ) = x(jQ) exactly when mD = +-j modulo the order of Q, it
+    /// suffices to multiply together X_{mD} Z_j - X_j Z_{mD} over all giant
+    /// steps m and baby steps j, and take a single gcd at the end.
+    fn stage_2<T: UnsignedInteger>(
+            &self, n: &T, curve: &Curve<T>, q: &Point<T>) -> Option<T> {
+        let d = Ecm::D;
+
+        // baby steps: jQ for odd j < D/2, built up two at a time
+        let two_q = curve.double(q);
+        let mut babies = vec![(1, q.clone())];
+        let mut previous = q.clone();
+        let mut current = curve.add(&two_q, q, q);  // 3Q
+        for j in (3..d / 2).step_by(2) {
+            babies.push((j, current.clone()));
+            let next = curve.add(&current, &two_q, &previous);
+            previous = current;
+            current = next;
+        }
+        babies.retain(|&(j, _)| gcd(j as u128, d as u128) == 1);
+
+        // giant steps: mDQ for b1/D <= m <= b2/D + 1, each one the previous
+        // plus DQ, with the one before as the difference
+        let d_q = curve.multiply(q, d);
+        let first = (self.b1 / d).max(1);
+        let mut giant = curve.multiply(q, first * d);
+        let mut next_giant = curve.multiply(q, (first + 1) * d);
+
+        let mut product = Residue::from_unsigned_integer(T::one(), n.clone());
+        for _ in first..=(self.b2 / d + 1) {
+            for (_, baby) in babies.iter() {
+                let difference = giant.x.times(&baby.z).minus(&baby.x.times(&giant.z));
+                product = product.times(&difference);
+            }
+            let after = curve.add(&next_giant, &d_q, &giant);
+            giant = next_giant;
+            next_giant = after;
+        }
+
+        let g = gcd(product.value, n.clone());
+        if g != T::one() && g != *n { Some(g) } else { None }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ladder_matches_repeated_addition() {
+        let n = 1000003u128 * 1000033;
+        let r = |k: u128| Residue::from_unsigned_integer(k, n);
+        let curve = Curve { a24: r(12345) };
+        let p = Point { x: r(7), z: r(1) };
+
+        // build kP by successive differential additions
+        let mut multiples = vec![p.clone(), curve.double(&p)];
+        for k in 3..=40 {
+            let next = curve.add(&multiples[k - 2], &p, &multiples[k - 3]);
+            multiples.push(next);
+        }
+
+        for k in 1..=40 {
+            let expected = &multiples[k as usize - 1];
+            let actual = curve.multiply(&p, k);
+            // equal as projective points: X1 Z2 = X2 Z1
+            assert_eq!(
+                expected.x.times(&actual.z),
+                actual.x.times(&expected.z),
+                "mismatch at k = {}", k);
+        }
+    }
+
+    #[test]
+    fn test_ecm() {
+        // 3000000037 - 1 and 3000000037 + 1 both have large prime factors,
+        // so this needs ECM rather than p-1 or p+1
+        let n = 3000000037u128 * 2305843009213706309;
+        let ecm = Ecm { b1: 2000, b2: 200000, curves: 100 };
+
+        match ecm.factor(&n) {
+            Some(d) => assert!(d == 3000000037 || d == 2305843009213706309),
+            None => panic!("ECM did not split {}", n),
+        }
+    }
+
+    #[test]
+    fn test_stage_2_extends_stage_1() {
+        let n = 1000003u128 * 2305843009213706309;
+        let stage_1_only = Ecm { b1: 100, b2: 100, curves: 1 };
+        let both_stages = Ecm { b1: 100, b2: 10000, curves: 1 };
+
+        // Stage 2 never loses a factor stage 1 found, and for some curves
+        // finds one it missed.
+        let mut extended = false;
+        for sigma in 6..500u128 {
+            let first = stage_1_only.try_curve(&n, &sigma);
+            let second = both_stages.try_curve(&n, &sigma);
+            if first.is_some() {
+                assert_eq!(first, second);
+            }
+            if first.is_none() && second == Some(1000003) {
+                extended = true;
+            }
+        }
+        assert!(extended);
+    }
+
+    #[test]
+    fn test_ecm_big_integers() {
+        use crate::bigint::BigUint;
+
+        let p = BigUint::from(1000003u64);
+        let n = &p * &BigUint::from(2305843009213706309u64) * BigUint::from(4294967291u64);
+        let ecm = Ecm { b1: 500, b2: 50000, curves: 50 };
+
+        let d = ecm.factor(&n).expect("ECM did not split n");
+        assert!((&n % &d).is_zero() && d > BigUint::from(1u64) && d < n);
+    }
+}
@@ -1,41 +1,57 @@
-use rand::Rng;
-use std::cmp::min;
+use rand::{CryptoRng, RngCore};
 
+use super::diffie_hellman::generate_secret_and_shared_value_with_rng;
+use super::integer::UnsignedInteger;
 use super::modular::Residue;
 
-pub fn choose_base(modulus: u128) -> Residue {
-    Residue::primitive_root(modulus)
+pub fn choose_base<T: UnsignedInteger>(modulus: T) -> Residue<T> {
+    choose_base_with_rng(&mut rand::thread_rng(), modulus)
 }
 
-pub fn generate_key_pair(base: &Residue) -> (i128, Residue) {
-    let mut rng = rand::thread_rng();
-    let private_key = rng.gen_range(
-        1..min(base.modulus as i128, std::i128::MAX));
-    let public_key = base.pow(private_key);
-    (private_key, public_key)
+pub fn choose_base_with_rng<T: UnsignedInteger, R: RngCore + CryptoRng + ?Sized>(
+        rng: &mut R, modulus: T) -> Residue<T> {
+    Residue::primitive_root_with_rng(rng, modulus).expect("Modulus has no primitive root.")
 }
 
-pub fn encrypt(
-        base: &Residue, message: &Vec<u128>,
-        public_key: &Residue) -> Vec<(Residue, Residue)> {
+pub fn generate_key_pair<T: UnsignedInteger>(base: &Residue<T>) -> (T::Signed, Residue<T>) {
+    generate_key_pair_with_rng(&mut rand::thread_rng(), base)
+}
+
+/// The key pair is a Diffie-Hellman secret and shared value, so the private
+/// key is drawn the same way.
+pub fn generate_key_pair_with_rng<T: UnsignedInteger, R: RngCore + CryptoRng + ?Sized>(
+        rng: &mut R, base: &Residue<T>) -> (T::Signed, Residue<T>) {
+    generate_secret_and_shared_value_with_rng(rng, base)
+}
+
+/// Encrypts each piece of message separately, so every piece must be below
+/// the modulus; `dhies::encrypt` handles arbitrary bytes.
+pub fn encrypt<T: UnsignedInteger>(
+        base: &Residue<T>, message: &[T],
+        public_key: &Residue<T>) -> Vec<(Residue<T>, Residue<T>)> {
+    encrypt_with_rng(&mut rand::thread_rng(), base, message, public_key)
+}
+
+/// `encrypt` drawing the ephemeral keys from rng.
+pub fn encrypt_with_rng<T: UnsignedInteger, R: RngCore + CryptoRng + ?Sized>(
+        rng: &mut R, base: &Residue<T>, message: &[T],
+        public_key: &Residue<T>) -> Vec<(Residue<T>, Residue<T>)> {
     assert_eq!(
         base.modulus, public_key.modulus,
         "Base and public_key have different moduli.");
-    for &piece in message.iter() {
+    for piece in message.iter() {
         assert!(
-            piece < base.modulus,
+            *piece < base.modulus,
             "Message pieces cannot exceed modulus.");
     }
 
-    let mut rng = rand::thread_rng();
-    let random_element = rng.gen_range(
-        1..min(base.modulus as i128, std::i128::MAX));
-
     let mut ret = vec![];
 
-    for &piece in message.iter() {
-        let c1 = base.pow(random_element);
-        let c2 = Residue::from_unsigned_integer(piece, base.modulus)
+    for piece in message.iter() {
+        // each piece needs its own ephemeral key: with a shared one, every
+        // c1 is the same and c2 / c2' = m / m' gives away plaintext ratios
+        let (random_element, c1) = generate_secret_and_shared_value_with_rng(rng, base);
+        let c2 = Residue::from_unsigned_integer(piece.clone(), base.modulus.clone())
             .times(&public_key.pow(random_element));
         ret.push((c1, c2));
     }
@@ -43,14 +59,14 @@ pub fn encrypt(
     ret
 }
 
-pub fn decrypt(
-        ciphertext: &Vec<(Residue, Residue)>, private_key: i128) -> Vec<u128> {
+pub fn decrypt<T: UnsignedInteger>(
+        ciphertext: &[(Residue<T>, Residue<T>)], private_key: T::Signed) -> Vec<T> {
     let mut ret = vec![];
 
     for piece in ciphertext.iter() {
         ret.push(
             piece.0
-            .pow(private_key)
+            .pow(private_key.clone())
             .inv()
             .times(&piece.1)
             .value);
@@ -62,10 +78,12 @@ pub fn decrypt(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::bigint::BigUint;
+    use rand::Rng;
 
     #[test]
     fn test_elgamal() {
-        for p in [997, 2677, 454711, 952252135981] {
+        for p in [997u128, 2677, 454711, 952252135981] {
             let mut rng = rand::thread_rng();
 
             let base = choose_base(p);
@@ -82,4 +100,66 @@ mod tests {
             assert_eq!(message, decrypted_message);
         }
     }
+
+    #[test]
+    fn test_elgamal_seeded() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let run = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let base = choose_base_with_rng(&mut rng, 952252135981u128);
+            let (private_key, public_key) = generate_key_pair_with_rng(&mut rng, &base);
+            let ciphertext = encrypt_with_rng(&mut rng, &base, &[1, 2, 3], &public_key);
+            assert_eq!(vec![1, 2, 3], decrypt(&ciphertext, private_key));
+            ciphertext
+        };
+        assert_eq!(run(5), run(5));
+        assert_ne!(run(5), run(6));
+    }
+
+    #[test]
+    fn test_elgamal_fresh_ephemeral_keys() {
+        let base = choose_base(952252135981u128);
+        let (_, public_key) = generate_key_pair(&base);
+
+        let message: Vec<u128> = (1..=20).map(|i| 1000 * i + 7).collect();
+        let ciphertext = encrypt(&base, &message, &public_key);
+
+        for i in 0..message.len() {
+            for j in i + 1..message.len() {
+                let (c1, c2) = &ciphertext[i];
+                let (d1, d2) = &ciphertext[j];
+                assert_ne!(c1, d1, "pieces {} and {} share an ephemeral key", i, j);
+
+                // with a reused ephemeral key, c2 * m' == d2 * m, so anyone
+                // knowing one plaintext piece learns all the others
+                let m = Residue::from_unsigned_integer(message[i], base.modulus);
+                let n = Residue::from_unsigned_integer(message[j], base.modulus);
+                assert_ne!(c2.times(&n), d2.times(&m), "pieces {} and {} leak their ratio", i, j);
+            }
+        }
+    }
+
+    #[test]
+    fn test_elgamal_rfc3526_group_14() {
+        // 2048-bit MODP group from RFC 3526, section 3, with generator 2
+        let p: BigUint = concat!(
+            "0xFFFFFFFFFFFFFFFFC90FDAA22168C234C4C6628B80DC1CD1",
+            "29024E088A67CC74020BBEA63B139B22514A08798E3404DD",
+            "EF9519B3CD3A431B302B0A6DF25F14374FE1356D6D51C245",
+            "E485B576625E7EC6F44C42E9A637ED6B0BFF5CB6F406B7ED",
+            "EE386BFB5A899FA5AE9F24117C4B1FE649286651ECE45B3D",
+            "C2007CB8A163BF0598DA48361C55D39A69163FA8FD24CF5F",
+            "83655D23DCA3AD961C62F356208552BB9ED529077096966D",
+            "670C354E4ABC9804F1746C08CA18217C32905E462E36CE3B",
+            "E39E772C180E86039B2783A2EC07A28FB5C55DF06F4C52C9",
+            "DE2BCBF6955817183995497CEA956AE515D2261898FA0510",
+            "15728E5A8AACAA68FFFFFFFFFFFFFFFF").parse().unwrap();
+        let base = Residue::from_unsigned_integer(BigUint::from(2u64), p.clone());
+        let (private_key, public_key) = generate_key_pair(&base);
+
+        let message = vec![BigUint::from(42u64), BigUint::from_u128(u128::MAX), p - BigUint::from(1u64)];
+        let ciphertext = encrypt(&base, &message, &public_key);
+        assert_eq!(message, decrypt(&ciphertext, private_key));
+    }
 }
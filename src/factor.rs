@@ -0,0 +1,693 @@
+use std::time::{Duration, Instant};
+
+use super::ecm::Ecm;
+use super::integer::{
+    gcd, is_prime, isqrt, perfect_power, pollard_brent, pollard_brent_with_limit,
+    pow_mod, primes_up_to, UnsignedInteger,
+};
+use super::quadratic_sieve::QuadraticSieve;
+
+/// A method for finding a non-trivial factor of a composite number.
+///
+/// Strategies are allowed to give up (returning None) when their budget runs
+/// out, so that a `FactorPipeline` can move on to the next one.
+pub trait FactorStrategy<T: UnsignedInteger> {
+    /// Short human-readable name, used in `FactorReport`s.
+    fn name(&self) -> &'static str;
+
+    /// Whether this strategy is worth trying on n at all.
+    ///
+    /// Used by `FactorPipeline` to skip methods that cannot work (or are
+    /// hopeless) at a given size.
+    fn is_suitable(&self, _n: &T) -> bool {
+        true
+    }
+
+    /// Tries to find a non-trivial factor of n, which is guaranteed to be odd
+    /// and composite.
+    fn find_factor(&self, n: &T) -> Option<T>;
+}
+
+/// Trial division by 2, 3, 5 and then the integers coprime to 30, up to
+/// the given bound.
+///
+/// The mod-30 wheel skips 11 out of every 15 candidates, leaving only 8
+/// divisions per 30 integers.
+pub struct TrialDivision {
+    pub bound: u64,
+}
+
+impl<T: UnsignedInteger> FactorStrategy<T> for TrialDivision {
+    fn name(&self) -> &'static str {
+        "trial division"
+    }
+
+    fn find_factor(&self, n: &T) -> Option<T> {
+        const WHEEL: [u64; 8] = [4, 2, 4, 2, 4, 6, 2, 6];
+
+        let divides = |d: u64| {
+            let d = T::from(d);
+            d < *n && (n.clone() % d).is_zero()
+        };
+
+        for d in [2, 3, 5] {
+            if d > self.bound { return None; }
+            if divides(d) { return Some(T::from(d)); }
+        }
+
+        let mut d = 7;
+        for &step in WHEEL.iter().cycle() {
+            if d > self.bound || T::from(d) * T::from(d) > *n {
+                return None;
+            }
+            if divides(d) {
+                return Some(T::from(d));
+            }
+            d += step;
+        }
+
+        unreachable!()
+    }
+}
+
+/// Fermat's method: search for n = a^2 - b^2 = (a - b)(a + b), starting
+/// from a = ceil(sqrt(n)).
+///
+/// Finds factors p and q within max_iterations steps when
+/// (p - q)^2 / (8 sqrt(n)) is below that bound, i.e. very quickly when the
+/// two factors are close together (as in carelessly generated RSA moduli).
+pub struct Fermat {
+    pub max_iterations: u64,
+}
+
+impl<T: UnsignedInteger> FactorStrategy<T> for Fermat {
+    fn name(&self) -> &'static str {
+        "Fermat"
+    }
+
+    fn find_factor(&self, n: &T) -> Option<T> {
+        let r = isqrt(n.clone());
+        let remainder = n.clone() - r.clone() * r.clone();
+        if remainder.is_zero() {
+            return if r > T::one() { Some(r) } else { None };
+        }
+
+        // a^2 itself can overflow a fixed-width T once n > (2^64 - 1)^2, so
+        // track b2 = a^2 - n instead, starting from (r + 1)^2 - n
+        // = 2r + 1 - (n - r^2), which is positive as n < (r + 1)^2
+        let mut a = r.clone() + T::one();
+        let mut b2 = (r << 1) + T::one() - remainder;
+        for _ in 0..self.max_iterations {
+            let b = isqrt(b2.clone());
+            if b.clone() * b.clone() == b2 {
+                let d = a.clone() - b;
+                if d > T::one() {
+                    return Some(d);
+                }
+            }
+            // (a + 1)^2 - a^2 = 2a + 1
+            b2 = b2 + (a.clone() << 1) + T::one();
+            a = a + T::one();
+        }
+
+        None
+    }
+}
+
+/// Returns the largest power of p that does not exceed bound.
+fn max_prime_power(p: u64, bound: u64) -> u64 {
+    let mut pk = p;
+    while pk <= bound / p {
+        pk *= p;
+    }
+    pk
+}
+
+/// Pollard's p-1 method (stage 1).
+///
+/// Computes a = 2^M (mod n), where M is the product of all prime powers up
+/// to bound. If some prime factor p of n has p - 1 dividing M (i.e. p - 1 is
+/// bound-smooth), then a = 1 (mod p) by Fermat's little theorem, so p
+/// divides gcd(a - 1, n).
+pub struct PollardPMinusOne {
+    pub bound: u64,
+}
+
+impl<T: UnsignedInteger> FactorStrategy<T> for PollardPMinusOne {
+    fn name(&self) -> &'static str {
+        "Pollard p-1"
+    }
+
+    fn find_factor(&self, n: &T) -> Option<T> {
+        const BATCH: usize = 64;
+
+        let primes = primes_up_to(self.bound);
+        let mut a = T::from(2) % n.clone();
+
+        for chunk in primes.chunks(BATCH) {
+            let checkpoint = a.clone();
+            for &p in chunk {
+                a = pow_mod(&a, &T::from(max_prime_power(p, self.bound)), n);
+            }
+
+            let g = gcd(a.clone().sub_mod(&T::one(), n), n.clone());
+            if g == T::one() {
+                continue;
+            }
+            if g != *n {
+                return Some(g);
+            }
+
+            // Every prime factor was caught in the same batch; replay it one
+            // prime at a time to separate them.
+            a = checkpoint;
+            for &p in chunk {
+                a = pow_mod(&a, &T::from(max_prime_power(p, self.bound)), n);
+                let g = gcd(a.clone().sub_mod(&T::one(), n), n.clone());
+                if g != T::one() {
+                    return if g != *n { Some(g) } else { None };
+                }
+            }
+        }
+
+        None
+    }
+}
+
+/// Returns the Lucas sequence value V_k(a) = r^k + r^{-k} (mod n), where r
+/// is a root of x^2 - a x + 1.
+///
+/// Uses the Montgomery ladder on the pair (V_j, V_{j+1}), with
+///     V_{2j} = V_j^2 - 2,    V_{2j+1} = V_j V_{j+1} - a.
+fn lucas_v<T: UnsignedInteger>(a: &T, k: u64, n: &T) -> T {
+    let two = T::from(2) % n.clone();
+    let mut x = two.clone();
+    let mut y = a.clone();
+
+    for i in (0..u64::BITS - k.leading_zeros()).rev() {
+        if (k >> i) & 1 == 1 {
+            x = x.mul_mod(&y, n).sub_mod(a, n);
+            y = y.mul_mod(&y, n).sub_mod(&two, n);
+        } else {
+            y = x.mul_mod(&y, n).sub_mod(a, n);
+            x = x.mul_mod(&x, n).sub_mod(&two, n);
+        }
+    }
+
+    x
+}
+
+/// Williams' p+1 method (stage 1).
+///
+/// The analogue of p-1 using Lucas sequences: if p + 1 is bound-smooth and
+/// the seed a has a^2 - 4 a quadratic non-residue mod p, then V_M(a) = 2
+/// (mod p). Since we cannot tell which seeds are non-residues without
+/// knowing p, we try a few.
+pub struct WilliamsPPlusOne {
+    pub bound: u64,
+    pub seeds: Vec<u64>,
+}
+
+impl<T: UnsignedInteger> FactorStrategy<T> for WilliamsPPlusOne {
+    fn name(&self) -> &'static str {
+        "Williams p+1"
+    }
+
+    fn find_factor(&self, n: &T) -> Option<T> {
+        let primes = primes_up_to(self.bound);
+        let two = T::from(2) % n.clone();
+
+        for &seed in self.seeds.iter() {
+            let mut v = T::from(seed) % n.clone();
+            for (i, &p) in primes.iter().enumerate() {
+                // V_{jk}(a) = V_j(V_k(a))
+                v = lucas_v(&v, max_prime_power(p, self.bound), n);
+                if i % 64 == 63 || i == primes.len() - 1 {
+                    let g = gcd(v.clone().sub_mod(&two, n), n.clone());
+                    if g == *n {
+                        break;
+                    }
+                    if g != T::one() {
+                        return Some(g);
+                    }
+                }
+            }
+        }
+
+        None
+    }
+}
+
+/// Pollard's rho method with Brent's cycle detection; see
+/// `integer::pollard_brent`.
+pub struct PollardRho {
+    pub max_iterations: u64,
+}
+
+impl<T: UnsignedInteger> FactorStrategy<T> for PollardRho {
+    fn name(&self) -> &'static str {
+        "Pollard rho"
+    }
+
+    fn find_factor(&self, n: &T) -> Option<T> {
+        pollard_brent_with_limit(n, self.max_iterations)
+    }
+}
+
+/// Shanks' square forms factorization (SQUFOF).
+///
+/// Walks the continued fraction expansion of sqrt(kn) until it reaches a
+/// square form, then walks the reverse cycle of that form to an ambiguous
+/// form, which reveals a factor. Runs in O(n^{1/4}) using only numbers of
+/// about half the size of n, so it is the method of choice for n below
+/// about 2^62; this implementation works for n up to 2^100.
+pub struct Squfof;
+
+impl Squfof {
+    /// Small square-free multipliers k; some choices of kn have much shorter
+    /// cycles than others, so we try them in turn.
+    const MULTIPLIERS: [u64; 16] = [
+        1, 3, 5, 7, 11, 3 * 5, 3 * 7, 3 * 11, 5 * 7, 5 * 11, 7 * 11,
+        3 * 5 * 7, 3 * 5 * 11, 3 * 7 * 11, 5 * 7 * 11, 3 * 5 * 7 * 11,
+    ];
+
+    fn with_multiplier(n: u128, k: u64) -> Option<u128> {
+        let kn = (k as u128).checked_mul(n)?;
+        if kn >> 120 != 0 {
+            return None;
+        }
+        let kn = kn as i128;
+
+        let p0 = isqrt(kn as u128) as i128;
+        let mut q0 = 1i128;
+        let mut q = kn - p0 * p0;
+        if q == 0 {
+            return None;
+        }
+        let bound = 6 * isqrt(2 * isqrt(n)) as i128;
+
+        // forward: find a square form Q_i with i even
+        let mut p = p0;
+        let mut r = 0;
+        let mut found = false;
+        for i in 2..bound {
+            let b = (p0 + p) / q;
+            let p_next = b * q - p;
+            let q_next = q0 + b * (p - p_next);
+            q0 = q;
+            q = q_next;
+            p = p_next;
+            if i % 2 == 0 {
+                let s = isqrt(q as u128) as i128;
+                if s * s == q {
+                    r = s;
+                    found = true;
+                    break;
+                }
+            }
+        }
+        if !found {
+            return None;
+        }
+
+        // reverse: start from the square root of the form and walk until P
+        // repeats
+        let b = (p0 - p) / r;
+        let mut p = b * r + p;
+        let mut q0 = r;
+        let mut q = (kn - p * p) / q0;
+        loop {
+            let b = (p0 + p) / q;
+            let p_next = b * q - p;
+            let q_next = q0 + b * (p - p_next);
+            q0 = q;
+            q = q_next;
+            if p_next == p {
+                break;
+            }
+            p = p_next;
+        }
+
+        [p, q0].into_iter()
+            .map(|x| gcd(n, x.unsigned_abs()))
+            .find(|&g| g != 1 && g != n)
+    }
+}
+
+impl<T: UnsignedInteger> FactorStrategy<T> for Squfof {
+    fn name(&self) -> &'static str {
+        "SQUFOF"
+    }
+
+    fn is_suitable(&self, n: &T) -> bool {
+        n.bits() <= 100
+    }
+
+    fn find_factor(&self, n: &T) -> Option<T> {
+        let n = n.to_u128()?;
+        let root = isqrt(n);
+        if root * root == n {
+            return Some(T::from_u128(root));
+        }
+
+        Squfof::MULTIPLIERS.iter()
+            .find_map(|&k| Squfof::with_multiplier(n, k))
+            .map(T::from_u128)
+    }
+}
+
+impl<T: UnsignedInteger> FactorStrategy<T> for Ecm {
+    fn name(&self) -> &'static str {
+        "ECM"
+    }
+
+    fn find_factor(&self, n: &T) -> Option<T> {
+        self.factor(n)
+    }
+}
+
+/// The self-initialising quadratic sieve, with parameters chosen from the
+/// size of n.
+///
+/// Its running time depends only on the size of n, not of its factors, so
+/// it is the method of choice for balanced semiprimes once they are too
+/// large for SQUFOF.
+pub struct Siqs;
+
+impl<T: UnsignedInteger> FactorStrategy<T> for Siqs {
+    fn name(&self) -> &'static str {
+        "SIQS"
+    }
+
+    fn is_suitable(&self, n: &T) -> bool {
+        n.bits() >= 40
+    }
+
+    fn find_factor(&self, n: &T) -> Option<T> {
+        QuadraticSieve::for_bits(n.bits()).factor(n)
+    }
+}
+
+/// One successful split recorded in a `FactorReport`.
+#[derive(Clone, Debug)]
+pub struct FactorStep<T> {
+    /// Name of the stage that found the factor.
+    pub stage: &'static str,
+    /// The composite that was split.
+    pub composite: T,
+    /// The non-trivial factor that was found.
+    pub factor: T,
+    /// Time spent in the successful stage.
+    pub elapsed: Duration,
+}
+
+/// The result of running a `FactorPipeline`.
+#[derive(Clone, Debug)]
+pub struct FactorReport<T> {
+    /// Pairs (p, e) where p^e is a maximal prime power of n, sorted by p.
+    pub factors: Vec<(T, u32)>,
+    /// Composite cofactors that no stage managed to split.
+    pub unfactored: Vec<T>,
+    /// Every split, in the order it happened.
+    pub steps: Vec<FactorStep<T>>,
+    /// Total time, including primality tests and failed stages.
+    pub elapsed: Duration,
+}
+
+/// A sequence of factoring strategies, tried in order on each composite
+/// until one of them finds a factor.
+pub struct FactorPipeline<T: UnsignedInteger> {
+    stages: Vec<Box<dyn FactorStrategy<T>>>,
+}
+
+impl<T: UnsignedInteger> Default for FactorPipeline<T> {
+    fn default() -> FactorPipeline<T> {
+        FactorPipeline::new()
+    }
+}
+
+impl<T: UnsignedInteger> FactorPipeline<T> {
+    /// Returns an empty pipeline; add stages with `with_stage`.
+    pub fn new() -> FactorPipeline<T> {
+        FactorPipeline { stages: vec![] }
+    }
+
+    /// Appends a stage to the pipeline.
+    pub fn with_stage<S: FactorStrategy<T> + 'static>(mut self, stage: S) -> FactorPipeline<T> {
+        self.stages.push(Box::new(stage));
+        self
+    }
+
+    /// Returns a general-purpose pipeline, ordered from cheapest to most
+    /// expensive, with each special-purpose method on a small budget.
+    ///
+    /// Ends with unbounded Pollard rho, so every composite is eventually
+    /// split.
+    pub fn automatic() -> FactorPipeline<T> {
+        FactorPipeline::new()
+            .with_stage(TrialDivision { bound: 1000 })
+            .with_stage(Fermat { max_iterations: 100 })
+            .with_stage(PollardRho { max_iterations: 1 << 12 })
+            .with_stage(Squfof)
+            .with_stage(PollardPMinusOne { bound: 10000 })
+            .with_stage(WilliamsPPlusOne { bound: 2000, seeds: vec![7, 9] })
+            .with_stage(Ecm::for_factor_digits(15))
+            .with_stage(Siqs)
+            .with_stage(Ecm::for_factor_digits(20))
+            .with_stage(Unbounded)
+    }
+
+    /// Factors n into prime powers, recording which stage split what.
+    pub fn factorize(&self, mut n: T) -> FactorReport<T> {
+        assert!(!n.is_zero(), "Cannot factor 0.");
+
+        let start = Instant::now();
+        let mut primes = vec![];
+        let mut unfactored = vec![];
+        let mut steps = vec![];
+
+        // strategies are only ever shown odd composites
+        while n.is_even() {
+            n = n >> 1;
+            primes.push(T::from(2));
+        }
+
+        let mut composites = vec![];
+        if n > T::one() {
+            composites.push(n);
+        }
+        while let Some(m) = composites.pop() {
+            if is_prime(m.clone()) {
+                primes.push(m);
+                continue;
+            }
+            match self.split(&m) {
+                Some(step) => {
+                    composites.push(m / step.factor.clone());
+                    composites.push(step.factor.clone());
+                    steps.push(step);
+                }
+                None => unfactored.push(m),
+            }
+        }
+
+        primes.sort();
+        let mut factors: Vec<(T, u32)> = vec![];
+        for p in primes.into_iter() {
+            match factors.last_mut() {
+                Some((q, e)) if *q == p => *e += 1,
+                _ => factors.push((p, 1)),
+            }
+        }
+
+        FactorReport { factors, unfactored, steps, elapsed: start.elapsed() }
+    }
+
+    fn split(&self, n: &T) -> Option<FactorStep<T>> {
+        // some strategies (the quadratic sieve among them) can't split
+        // prime powers, and this is cheap next to any of them
+        let start = Instant::now();
+        if let Some((root, _)) = perfect_power(n) {
+            return Some(FactorStep {
+                stage: "perfect power",
+                composite: n.clone(),
+                factor: root,
+                elapsed: start.elapsed(),
+            });
+        }
+
+        for stage in self.stages.iter().filter(|s| s.is_suitable(n)) {
+            let start = Instant::now();
+            if let Some(d) = stage.find_factor(n) {
+                // a wrong answer from one stage must not corrupt the whole
+                // factorisation, and checking is cheap next to finding it
+                if d > T::one() && d < *n && (n.clone() % d.clone()).is_zero() {
+                    return Some(FactorStep {
+                        stage: stage.name(),
+                        composite: n.clone(),
+                        factor: d,
+                        elapsed: start.elapsed(),
+                    });
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Pollard rho with no iteration limit, as a last resort that always
+/// succeeds on composites.
+struct Unbounded;
+
+impl<T: UnsignedInteger> FactorStrategy<T> for Unbounded {
+    fn name(&self) -> &'static str {
+        "Pollard rho (unbounded)"
+    }
+
+    fn find_factor(&self, n: &T) -> Option<T> {
+        Some(pollard_brent(n))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bigint::BigUint;
+
+    /// Asserts that the strategy finds a non-trivial factor of n.
+    fn assert_splits<S: FactorStrategy<u128>>(strategy: &S, n: u128) {
+        match strategy.find_factor(&n) {
+            Some(d) => assert!(
+                d > 1 && d < n && n.is_multiple_of(d),
+                "{}: {} is not a proper factor of {}", strategy.name(), d, n),
+            None => panic!("{} did not split {}", strategy.name(), n),
+        }
+    }
+
+    #[test]
+    fn test_trial_division() {
+        let n = 1009 * 1000003;
+        assert_eq!(Some(1009), TrialDivision { bound: 2000 }.find_factor(&n));
+        assert_eq!(None, TrialDivision { bound: 1000 }.find_factor(&n));
+        assert_eq!(Some(7), TrialDivision { bound: 10 }.find_factor(&49u128));
+        assert_eq!(None, TrialDivision { bound: 1000 }.find_factor(&101u128));
+    }
+
+    #[test]
+    fn test_fermat() {
+        // factors very close together
+        assert_splits(&Fermat { max_iterations: 10 }, 4294967291 * 4294967311);
+        assert_splits(&Fermat { max_iterations: 10 }, 1000003 * 1000033);
+        // factors far apart
+        assert_eq!(None, Fermat { max_iterations: 10 }.find_factor(&(1009 * 1000003u128)));
+        assert_eq!(Some(1000003), Fermat { max_iterations: 10 }.find_factor(&(1000003 * 1000003u128)));
+
+        // above (2^64 - 1)^2, where a^2 no longer fits in a u128
+        let n = 1009 * 337247142637203630786297926096895947u128;
+        assert_eq!(None, Fermat { max_iterations: 100 }.find_factor(&n));
+        assert_splits(&Fermat { max_iterations: 10 }, 18446744073709551557 * 18446744073709551533);
+    }
+
+    #[test]
+    fn test_pollard_p_minus_one() {
+        // 299297254481507 - 1 = 2 * 19 * 179 * 233 * 349 * 617 * 877
+        let q = 2305843009213706309;  // q - 1 has a 29-bit prime factor
+        assert_splits(&PollardPMinusOne { bound: 1000 }, 299297254481507 * q);
+        assert_eq!(None, PollardPMinusOne { bound: 100 }.find_factor(&(299297254481507 * q)));
+    }
+
+    #[test]
+    fn test_williams_p_plus_one() {
+        // 83453798781673 + 1 = 2 * 157 * 433 * 797 * 829 * 929
+        let q = 2305843009213706309;  // q + 1 has a 38-bit prime factor
+        let strategy = WilliamsPPlusOne { bound: 1000, seeds: vec![3, 5, 7, 9, 11] };
+        assert_splits(&strategy, 83453798781673 * q);
+    }
+
+    #[test]
+    fn test_pollard_rho() {
+        assert_splits(&PollardRho { max_iterations: 1 << 16 }, 1000003 * 2305843009213706309);
+    }
+
+    #[test]
+    fn test_squfof() {
+        for n in [11111u128, 1000003 * 1000033, 4294967291 * 1000003, 2305843009213706309 * 101] {
+            assert_splits(&Squfof, n);
+        }
+        assert!(!FactorStrategy::<u128>::is_suitable(&Squfof, &u128::MAX));
+    }
+
+    #[test]
+    fn test_ecm() {
+        // 3000000037 - 1 and 3000000037 + 1 both have large prime factors
+        assert_splits(&Ecm::for_factor_digits(10), 3000000037 * 2305843009213706309);
+    }
+
+    #[test]
+    fn test_siqs() {
+        assert_splits(&Siqs, 1000000000000000003 * 1000000000000000009);
+        assert!(!FactorStrategy::<u128>::is_suitable(&Siqs, &(1009 * 1000003)));
+    }
+
+    #[test]
+    fn test_pipeline_report() {
+        let n = 8 * 997 * 1000003u128 * 1000033;
+        let report = FactorPipeline::automatic().factorize(n);
+
+        assert_eq!(
+            vec![(2, 3), (997, 1), (1000003, 1), (1000033, 1)],
+            report.factors);
+        assert!(report.unfactored.is_empty());
+        assert_eq!("trial division", report.steps[0].stage);
+        assert_eq!(997, report.steps[0].factor);
+        assert_eq!("Fermat", report.steps[1].stage);
+        assert_eq!(1000003 * 1000033, report.steps[1].composite);
+    }
+
+    #[test]
+    fn test_pipeline_unfactored() {
+        let n = 1000003u128 * 2305843009213706309;
+        let report = FactorPipeline::new()
+            .with_stage(TrialDivision { bound: 1000 })
+            .factorize(n);
+
+        assert_eq!(Vec::<(u128, u32)>::new(), report.factors);
+        assert_eq!(vec![n], report.unfactored);
+    }
+
+    #[test]
+    fn test_pipeline_rejects_non_divisors() {
+        /// A broken strategy that always claims 7 is a factor.
+        struct Seven;
+
+        impl FactorStrategy<u128> for Seven {
+            fn name(&self) -> &'static str {
+                "seven"
+            }
+
+            fn find_factor(&self, _n: &u128) -> Option<u128> {
+                Some(7)
+            }
+        }
+
+        let report = FactorPipeline::new()
+            .with_stage(Seven)
+            .with_stage(TrialDivision { bound: 2000 })
+            .factorize(1009 * 1000003u128);
+        assert_eq!(vec![(1009, 1), (1000003, 1)], report.factors);
+        assert_eq!("trial division", report.steps[0].stage);
+
+        let report = FactorPipeline::new().with_stage(Seven).factorize(1009 * 1000003u128);
+        assert!(report.factors.is_empty());
+        assert_eq!(vec![1009 * 1000003], report.unfactored);
+    }
+
+    #[test]
+    fn test_pipeline_big_integers() {
+        let p = BigUint::from(1000003u64);
+        let q = BigUint::from(4294967291u64);
+        let report = FactorPipeline::automatic().factorize(&p * &p * &q);
+
+        assert_eq!(vec![(p, 2), (q, 1)], report.factors);
+    }
+}
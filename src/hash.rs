@@ -0,0 +1,444 @@
+/// A cryptographic hash function with a streaming interface.
+pub trait Hash: Clone + Default {
+    /// Digest length in bytes.
+    const OUTPUT_SIZE: usize;
+
+    /// Length in bytes of the blocks the compression function consumes.
+    const BLOCK_SIZE: usize;
+
+    fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feeds more data into the hash.
+    fn update(&mut self, data: &[u8]);
+
+    /// Returns the digest of everything passed to `update`.
+    fn finalize(self) -> Vec<u8>;
+
+    /// Returns the digest of data in one go.
+    fn digest(data: &[u8]) -> Vec<u8> {
+        let mut hash = Self::new();
+        hash.update(data);
+        hash.finalize()
+    }
+}
+
+/// Appends data to the partial block in buffer, passing each completed block
+/// to compress, and leaves the remainder in buffer.
+fn update_blocks<F: FnMut(&[u8])>(buffer: &mut Vec<u8>, mut data: &[u8], block_size: usize, mut compress: F) {
+    if !buffer.is_empty() {
+        let take = data.len().min(block_size - buffer.len());
+        buffer.extend_from_slice(&data[..take]);
+        data = &data[take..];
+        if buffer.len() < block_size {
+            return;
+        }
+        compress(buffer);
+        buffer.clear();
+    }
+
+    let mut blocks = data.chunks_exact(block_size);
+    for block in blocks.by_ref() {
+        compress(block);
+    }
+    buffer.extend_from_slice(blocks.remainder());
+}
+
+/// The Merkle-Damgard padding shared by SHA-1 and the SHA-2 family: a 1 bit, zeros up
+/// to the length field, and the message length in bits, big-endian, filling
+/// the last `length_size` bytes of a block.
+fn padding(buffered: usize, bit_length: u128, block_size: usize, length_size: usize) -> Vec<u8> {
+    let mut padding = vec![0x80];
+    let used = (buffered + 1) % block_size;
+    let zeros = (2 * block_size - length_size - used) % block_size;
+    padding.resize(1 + zeros, 0);
+    padding.extend_from_slice(&bit_length.to_be_bytes()[16 - length_size..]);
+    padding
+}
+
+/// SHA-1, from FIPS 180-4. Collisions for it have been found, so it is only
+/// here to check against published vectors that use it, such as the PKCS #1
+/// v2.1 RSA-PSS examples.
+#[derive(Clone, Debug)]
+pub struct Sha1 {
+    state: [u32; 5],
+    /// Bytes not yet compressed; always shorter than a block.
+    buffer: Vec<u8>,
+    /// Total number of bytes passed to `update`.
+    length: u64,
+}
+
+impl Sha1 {
+    const INITIAL_STATE: [u32; 5] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0];
+
+    fn compress(&mut self, block: &[u8]) {
+        let mut w = [0u32; 80];
+        for (i, word) in block.chunks_exact(4).enumerate() {
+            w[i] = u32::from_be_bytes(word.try_into().unwrap());
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = self.state;
+        for (i, &word) in w.iter().enumerate() {
+            let (f, k) = match i / 20 {
+                0 => ((b & c) | (!b & d), 0x5a827999),
+                1 => (b ^ c ^ d, 0x6ed9eba1),
+                2 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+                _ => (b ^ c ^ d, 0xca62c1d6),
+            };
+            let t = a.rotate_left(5).wrapping_add(f).wrapping_add(e)
+                .wrapping_add(k).wrapping_add(word);
+
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = t;
+        }
+
+        for (s, x) in self.state.iter_mut().zip([a, b, c, d, e]) {
+            *s = s.wrapping_add(x);
+        }
+    }
+}
+
+impl Default for Sha1 {
+    fn default() -> Sha1 {
+        Sha1 { state: Sha1::INITIAL_STATE, buffer: vec![], length: 0 }
+    }
+}
+
+impl Hash for Sha1 {
+    const OUTPUT_SIZE: usize = 20;
+    const BLOCK_SIZE: usize = 64;
+
+    fn update(&mut self, data: &[u8]) {
+        self.length += data.len() as u64;
+        let mut buffer = std::mem::take(&mut self.buffer);
+        update_blocks(&mut buffer, data, Sha1::BLOCK_SIZE, |block| self.compress(block));
+        self.buffer = buffer;
+    }
+
+    fn finalize(mut self) -> Vec<u8> {
+        let bit_length = self.length as u128 * 8;
+        self.update(&padding(self.buffer.len(), bit_length, Sha1::BLOCK_SIZE, 8));
+
+        self.state.iter().flat_map(|s| s.to_be_bytes()).collect()
+    }
+}
+
+/// SHA-256, from FIPS 180-4.
+#[derive(Clone, Debug)]
+pub struct Sha256 {
+    state: [u32; 8],
+    /// Bytes not yet compressed; always shorter than a block.
+    buffer: Vec<u8>,
+    /// Total number of bytes passed to `update`.
+    length: u64,
+}
+
+impl Sha256 {
+    /// The first 32 bits of the fractional parts of the square roots of the
+    /// first 8 primes.
+    const INITIAL_STATE: [u32; 8] = [
+        0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a,
+        0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+    ];
+
+    /// The first 32 bits of the fractional parts of the cube roots of the
+    /// first 64 primes.
+    const ROUND_CONSTANTS: [u32; 64] = [
+        0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+        0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+        0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+        0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+        0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+        0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+        0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+        0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+    ];
+
+    fn compress(&mut self, block: &[u8]) {
+        let mut w = [0u32; 64];
+        for (i, word) in block.chunks_exact(4).enumerate() {
+            w[i] = u32::from_be_bytes(word.try_into().unwrap());
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = self.state;
+        for (&k, &word) in Sha256::ROUND_CONSTANTS.iter().zip(w.iter()) {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let choose = (e & f) ^ (!e & g);
+            let t1 = h.wrapping_add(s1).wrapping_add(choose)
+                .wrapping_add(k).wrapping_add(word);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let majority = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(majority);
+
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+
+        for (s, x) in self.state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *s = s.wrapping_add(x);
+        }
+    }
+}
+
+impl Default for Sha256 {
+    fn default() -> Sha256 {
+        Sha256 { state: Sha256::INITIAL_STATE, buffer: vec![], length: 0 }
+    }
+}
+
+impl Hash for Sha256 {
+    const OUTPUT_SIZE: usize = 32;
+    const BLOCK_SIZE: usize = 64;
+
+    fn update(&mut self, data: &[u8]) {
+        self.length += data.len() as u64;
+        let mut buffer = std::mem::take(&mut self.buffer);
+        update_blocks(&mut buffer, data, Sha256::BLOCK_SIZE, |block| self.compress(block));
+        self.buffer = buffer;
+    }
+
+    fn finalize(mut self) -> Vec<u8> {
+        let bit_length = self.length as u128 * 8;
+        self.update(&padding(self.buffer.len(), bit_length, Sha256::BLOCK_SIZE, 8));
+
+        self.state.iter().flat_map(|s| s.to_be_bytes()).collect()
+    }
+}
+
+/// SHA-512, from FIPS 180-4: SHA-256's structure with 64-bit words, 80
+/// rounds and different rotation amounts.
+#[derive(Clone, Debug)]
+pub struct Sha512 {
+    state: [u64; 8],
+    /// Bytes not yet compressed; always shorter than a block.
+    buffer: Vec<u8>,
+    /// Total number of bytes passed to `update`.
+    length: u128,
+}
+
+impl Sha512 {
+    /// The first 64 bits of the fractional parts of the square roots of the
+    /// first 8 primes.
+    const INITIAL_STATE: [u64; 8] = [
+        0x6a09e667f3bcc908, 0xbb67ae8584caa73b, 0x3c6ef372fe94f82b, 0xa54ff53a5f1d36f1,
+        0x510e527fade682d1, 0x9b05688c2b3e6c1f, 0x1f83d9abfb41bd6b, 0x5be0cd19137e2179,
+    ];
+
+    /// The first 64 bits of the fractional parts of the cube roots of the
+    /// first 80 primes.
+    const ROUND_CONSTANTS: [u64; 80] = [
+        0x428a2f98d728ae22, 0x7137449123ef65cd, 0xb5c0fbcfec4d3b2f, 0xe9b5dba58189dbbc,
+        0x3956c25bf348b538, 0x59f111f1b605d019, 0x923f82a4af194f9b, 0xab1c5ed5da6d8118,
+        0xd807aa98a3030242, 0x12835b0145706fbe, 0x243185be4ee4b28c, 0x550c7dc3d5ffb4e2,
+        0x72be5d74f27b896f, 0x80deb1fe3b1696b1, 0x9bdc06a725c71235, 0xc19bf174cf692694,
+        0xe49b69c19ef14ad2, 0xefbe4786384f25e3, 0x0fc19dc68b8cd5b5, 0x240ca1cc77ac9c65,
+        0x2de92c6f592b0275, 0x4a7484aa6ea6e483, 0x5cb0a9dcbd41fbd4, 0x76f988da831153b5,
+        0x983e5152ee66dfab, 0xa831c66d2db43210, 0xb00327c898fb213f, 0xbf597fc7beef0ee4,
+        0xc6e00bf33da88fc2, 0xd5a79147930aa725, 0x06ca6351e003826f, 0x142929670a0e6e70,
+        0x27b70a8546d22ffc, 0x2e1b21385c26c926, 0x4d2c6dfc5ac42aed, 0x53380d139d95b3df,
+        0x650a73548baf63de, 0x766a0abb3c77b2a8, 0x81c2c92e47edaee6, 0x92722c851482353b,
+        0xa2bfe8a14cf10364, 0xa81a664bbc423001, 0xc24b8b70d0f89791, 0xc76c51a30654be30,
+        0xd192e819d6ef5218, 0xd69906245565a910, 0xf40e35855771202a, 0x106aa07032bbd1b8,
+        0x19a4c116b8d2d0c8, 0x1e376c085141ab53, 0x2748774cdf8eeb99, 0x34b0bcb5e19b48a8,
+        0x391c0cb3c5c95a63, 0x4ed8aa4ae3418acb, 0x5b9cca4f7763e373, 0x682e6ff3d6b2b8a3,
+        0x748f82ee5defb2fc, 0x78a5636f43172f60, 0x84c87814a1f0ab72, 0x8cc702081a6439ec,
+        0x90befffa23631e28, 0xa4506cebde82bde9, 0xbef9a3f7b2c67915, 0xc67178f2e372532b,
+        0xca273eceea26619c, 0xd186b8c721c0c207, 0xeada7dd6cde0eb1e, 0xf57d4f7fee6ed178,
+        0x06f067aa72176fba, 0x0a637dc5a2c898a6, 0x113f9804bef90dae, 0x1b710b35131c471b,
+        0x28db77f523047d84, 0x32caab7b40c72493, 0x3c9ebe0a15c9bebc, 0x431d67c49c100d4c,
+        0x4cc5d4becb3e42b6, 0x597f299cfc657e2a, 0x5fcb6fab3ad6faec, 0x6c44198c4a475817,
+    ];
+
+    fn compress(&mut self, block: &[u8]) {
+        let mut w = [0u64; 80];
+        for (i, word) in block.chunks_exact(8).enumerate() {
+            w[i] = u64::from_be_bytes(word.try_into().unwrap());
+        }
+        for i in 16..80 {
+            let s0 = w[i - 15].rotate_right(1) ^ w[i - 15].rotate_right(8) ^ (w[i - 15] >> 7);
+            let s1 = w[i - 2].rotate_right(19) ^ w[i - 2].rotate_right(61) ^ (w[i - 2] >> 6);
+            w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = self.state;
+        for (&k, &word) in Sha512::ROUND_CONSTANTS.iter().zip(w.iter()) {
+            let s1 = e.rotate_right(14) ^ e.rotate_right(18) ^ e.rotate_right(41);
+            let choose = (e & f) ^ (!e & g);
+            let t1 = h.wrapping_add(s1).wrapping_add(choose)
+                .wrapping_add(k).wrapping_add(word);
+            let s0 = a.rotate_right(28) ^ a.rotate_right(34) ^ a.rotate_right(39);
+            let majority = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(majority);
+
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+
+        for (s, x) in self.state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *s = s.wrapping_add(x);
+        }
+    }
+}
+
+impl Default for Sha512 {
+    fn default() -> Sha512 {
+        Sha512 { state: Sha512::INITIAL_STATE, buffer: vec![], length: 0 }
+    }
+}
+
+impl Hash for Sha512 {
+    const OUTPUT_SIZE: usize = 64;
+    const BLOCK_SIZE: usize = 128;
+
+    fn update(&mut self, data: &[u8]) {
+        self.length += data.len() as u128;
+        let mut buffer = std::mem::take(&mut self.buffer);
+        update_blocks(&mut buffer, data, Sha512::BLOCK_SIZE, |block| self.compress(block));
+        self.buffer = buffer;
+    }
+
+    fn finalize(mut self) -> Vec<u8> {
+        let bit_length = self.length * 8;
+        self.update(&padding(self.buffer.len(), bit_length, Sha512::BLOCK_SIZE, 16));
+
+        self.state.iter().flat_map(|s| s.to_be_bytes()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::hex;
+
+    // the examples from FIPS 180-4 (via NIST's "Examples with Intermediate
+    // Values"), plus the empty message
+    const SHORT_MESSAGE: &[u8] = b"abc";
+    const TWO_BLOCKS_256: &[u8] = b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq";
+    const TWO_BLOCKS_512: &[u8] = concat!(
+        "abcdefghbcdefghicdefghijdefghijkefghijklfghijklmghijklmnhijklmno",
+        "ijklmnopjklmnopqklmnopqrlmnopqrsmnopqrstnopqrstu").as_bytes();
+
+    #[test]
+    fn test_sha1() {
+        assert_eq!("da39a3ee5e6b4b0d3255bfef95601890afd80709", hex(&Sha1::digest(b"")));
+        assert_eq!("a9993e364706816aba3e25717850c26c9cd0d89d", hex(&Sha1::digest(SHORT_MESSAGE)));
+        assert_eq!("84983e441c3bd26ebaae4aa1f95129e5e54670f1", hex(&Sha1::digest(TWO_BLOCKS_256)));
+        assert_eq!("a49b2446a02c645bf419f995b67091253a04a259", hex(&Sha1::digest(TWO_BLOCKS_512)));
+    }
+
+    #[test]
+    fn test_sha256() {
+        assert_eq!(
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+            hex(&Sha256::digest(b"")));
+        assert_eq!(
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            hex(&Sha256::digest(SHORT_MESSAGE)));
+        assert_eq!(
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1",
+            hex(&Sha256::digest(TWO_BLOCKS_256)));
+        assert_eq!(
+            "cf5b16a778af8380036ce59e7b0492370b249b11e8f07a51afac45037afee9d1",
+            hex(&Sha256::digest(TWO_BLOCKS_512)));
+    }
+
+    #[test]
+    fn test_sha512() {
+        assert_eq!(
+            concat!(
+                "cf83e1357eefb8bdf1542850d66d8007d620e4050b5715dc83f4a921d36ce9ce",
+                "47d0d13c5d85f2b0ff8318d2877eec2f63b931bd47417a81a538327af927da3e"),
+            hex(&Sha512::digest(b"")));
+        assert_eq!(
+            concat!(
+                "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a",
+                "2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f"),
+            hex(&Sha512::digest(SHORT_MESSAGE)));
+        assert_eq!(
+            concat!(
+                "204a8fc6dda82f0a0ced7beb8e08a41657c16ef468b228a8279be331a703c335",
+                "96fd15c13b1b07f9aa1d3bea57789ca031ad85c7a71dd70354ec631238ca3445"),
+            hex(&Sha512::digest(TWO_BLOCKS_256)));
+        assert_eq!(
+            concat!(
+                "8e959b75dae313da8cf4f72814fc143f8f7779c6eb9f7fa17299aeadb6889018",
+                "501d289e4900f7e4331b99dec4b5433ac7d329eeb6dd26545e96e55b874be909"),
+            hex(&Sha512::digest(TWO_BLOCKS_512)));
+    }
+
+    #[test]
+    fn test_million_a() {
+        // the long message example from FIPS 180-2, fed in uneven chunks
+        let mut sha1 = Sha1::new();
+        let mut sha256 = Sha256::new();
+        let mut sha512 = Sha512::new();
+        for chunk in vec![b'a'; 1_000_000].chunks(999) {
+            sha1.update(chunk);
+            sha256.update(chunk);
+            sha512.update(chunk);
+        }
+        assert_eq!("34aa973cd4c4daa4f61eeb2bdbad27316534016f", hex(&sha1.finalize()));
+        assert_eq!(
+            "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0",
+            hex(&sha256.finalize()));
+        assert_eq!(
+            concat!(
+                "e718483d0ce769644e2e42c7bc15b4638e1f98b13b2044285632a803afa973eb",
+                "de0ff244877ea60a4cb0432ce577c31beb009c5c2c49aa2e4eadb217ad8cc09b"),
+            hex(&sha512.finalize()));
+    }
+
+    #[test]
+    fn test_sha256_streaming() {
+        let data: Vec<u8> = (0..1000u32).map(|i| (i * 7 % 256) as u8).collect();
+        let expected = Sha256::digest(&data);
+
+        for chunk_size in [1, 3, 55, 56, 63, 64, 65, 500] {
+            let mut hash = Sha256::new();
+            for chunk in data.chunks(chunk_size) {
+                hash.update(chunk);
+            }
+            assert_eq!(expected, hash.finalize(), "chunk size {}", chunk_size);
+        }
+    }
+
+    #[test]
+    fn test_sha512_streaming() {
+        // lengths around the 112-byte point where padding spills into an
+        // extra block
+        for length in [111, 112, 113, 127, 128, 129, 300] {
+            let data: Vec<u8> = (0..length as u32).map(|i| (i * 13 % 256) as u8).collect();
+            let expected = Sha512::digest(&data);
+
+            let mut hash = Sha512::new();
+            for chunk in data.chunks(7) {
+                hash.update(chunk);
+            }
+            assert_eq!(expected, hash.finalize(), "length {}", length);
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use super::hash::Hash;
+
+/// HMAC over any of the crate's hash functions, from RFC 2104.
+#[derive(Clone, Debug)]
+pub struct Hmac<H: Hash> {
+    inner: H,
+    outer: H,
+}
+
+impl<H: Hash> Hmac<H> {
+    const INNER_PAD: u8 = 0x36;
+    const OUTER_PAD: u8 = 0x5c;
+
+    /// Starts a MAC under the given key. Keys longer than a block are hashed
+    /// first, as the RFC requires.
+    pub fn new(key: &[u8]) -> Hmac<H> {
+        let mut block = if key.len() > H::BLOCK_SIZE { H::digest(key) } else { key.to_vec() };
+        block.resize(H::BLOCK_SIZE, 0);
+
+        let mut inner = H::new();
+        inner.update(&block.iter().map(|b| b ^ Self::INNER_PAD).collect::<Vec<u8>>());
+        let mut outer = H::new();
+        outer.update(&block.iter().map(|b| b ^ Self::OUTER_PAD).collect::<Vec<u8>>());
+        Hmac { inner, outer }
+    }
+
+    /// Feeds more data into the MAC.
+    pub fn update(&mut self, data: &[u8]) {
+        self.inner.update(data);
+    }
+
+    /// Returns the tag of everything passed to `update`.
+    pub fn finalize(self) -> Vec<u8> {
+        let mut outer = self.outer;
+        outer.update(&self.inner.finalize());
+        outer.finalize()
+    }
+
+    /// Returns the tag of data under key in one go.
+    pub fn mac(key: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut hmac = Hmac::<H>::new(key);
+        hmac.update(data);
+        hmac.finalize()
+    }
+
+    /// Checks a tag without leaking, through timing, how much of it matched.
+    pub fn verify(key: &[u8], data: &[u8], tag: &[u8]) -> bool {
+        constant_time_eq(&Hmac::<H>::mac(key, data), tag)
+    }
+}
+
+/// Compares two byte strings in time depending only on their lengths.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b.iter()).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// HKDF-Extract from RFC 5869: concentrates the entropy of the input keying
+/// material into a pseudorandom key of H::OUTPUT_SIZE bytes. An empty salt
+/// stands for a block of zeros.
+pub fn hkdf_extract<H: Hash>(salt: &[u8], ikm: &[u8]) -> Vec<u8> {
+    Hmac::<H>::mac(salt, ikm)
+}
+
+/// HKDF-Expand from RFC 5869: stretches a pseudorandom key into `length`
+/// bytes bound to the context in `info`. Returns None if more than
+/// 255 * H::OUTPUT_SIZE bytes are asked for.
+pub fn hkdf_expand<H: Hash>(prk: &[u8], info: &[u8], length: usize) -> Option<Vec<u8>> {
+    if length > 255 * H::OUTPUT_SIZE {
+        return None;
+    }
+
+    let mut okm = Vec::with_capacity(length);
+    let mut block = vec![];
+    for counter in 1..=length.div_ceil(H::OUTPUT_SIZE) as u8 {
+        let mut hmac = Hmac::<H>::new(prk);
+        hmac.update(&block);
+        hmac.update(info);
+        hmac.update(&[counter]);
+        block = hmac.finalize();
+        okm.extend_from_slice(&block);
+    }
+    okm.truncate(length);
+    Some(okm)
+}
+
+/// Extract followed by expand.
+pub fn hkdf<H: Hash>(salt: &[u8], ikm: &[u8], info: &[u8], length: usize) -> Option<Vec<u8>> {
+    hkdf_expand::<H>(&hkdf_extract::<H>(salt, ikm), info, length)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::{Sha256, Sha512};
+    use crate::test_util::hex;
+
+    #[test]
+    fn test_hmac_rfc4231() {
+        // test cases 1, 2, 3, 4, 6 and 7 from RFC 4231 (5 truncates the output)
+        let long_key = [0xaa; 131];
+        let cases: [(&[u8], &[u8], &str, &str); 6] = [
+            (&[0x0b; 20], b"Hi There",
+             "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7",
+             concat!("87aa7cdea5ef619d4ff0b4241a1d6cb02379f4e2ce4ec2787ad0b30545e17cde",
+                     "daa833b7d6b8a702038b274eaea3f4e4be9d914eeb61f1702e696c203a126854")),
+            (b"Jefe", b"what do ya want for nothing?",
+             "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+             concat!("164b7a7bfcf819e2e395fbe73b56e0a387bd64222e831fd610270cd7ea250554",
+                     "9758bf75c05a994a6d034f65f8f0e6fdcaeab1a34d4a6b4b636e070a38bce737")),
+            (&[0xaa; 20], &[0xdd; 50],
+             "773ea91e36800e46854db8ebd09181a72959098b3ef8c122d9635514ced565fe",
+             concat!("fa73b0089d56a284efb0f0756c890be9b1b5dbdd8ee81a3655f83e33b2279d39",
+                     "bf3e848279a722c806b485a47e67c807b946a337bee8942674278859e13292fb")),
+            (&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25],
+             &[0xcd; 50],
+             "82558a389a443c0ea4cc819899f2083a85f0faa3e578f8077a2e3ff46729665b",
+             concat!("b0ba465637458c6990e5a8c5f61d4af7e576d97ff94b872de76f8050361ee3db",
+                     "a91ca5c11aa25eb4d679275cc5788063a5f19741120c4f2de2adebeb10a298dd")),
+            (&long_key, b"Test Using Larger Than Block-Size Key - Hash Key First",
+             "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54",
+             concat!("80b24263c7c1a3ebb71493c1dd7be8b49b46d1f41b4aeec1121b013783f8f352",
+                     "6b56d037e05f2598bd0fd2215d6a1e5295e64f73f63f0aec8b915a985d786598")),
+            (&long_key,
+             concat!("This is a test using a larger than block-size key and a larger ",
+                     "than block-size data. The key needs to be hashed before being ",
+                     "used by the HMAC algorithm.").as_bytes(),
+             "9b09ffa71b942fcb27635fbcd5b0e944bfdc63644f0713938a7f51535c3a35e2",
+             concat!("e37b6a775dc87dbaa4dfa9f96e5e3ffddebd71f8867289865df5a32d20cdc944",
+                     "b6022cac3c4982b10d5eeb55c3e4de15134676fb6de0446065c97440fa8c6a58")),
+        ];
+
+        for (key, data, sha256, sha512) in cases {
+            assert_eq!(sha256, hex(&Hmac::<Sha256>::mac(key, data)));
+            assert_eq!(sha512, hex(&Hmac::<Sha512>::mac(key, data)));
+        }
+    }
+
+    #[test]
+    fn test_hmac_streaming_and_verify() {
+        let mut hmac = Hmac::<Sha256>::new(b"Jefe");
+        for piece in [&b"what do ya"[..], b" want ", b"for nothing?"] {
+            hmac.update(piece);
+        }
+        let tag = hmac.finalize();
+        assert_eq!(Hmac::<Sha256>::mac(b"Jefe", b"what do ya want for nothing?"), tag);
+
+        assert!(Hmac::<Sha256>::verify(b"Jefe", b"what do ya want for nothing?", &tag));
+        assert!(!Hmac::<Sha256>::verify(b"Jefe", b"what do ya want for nothing!", &tag));
+        assert!(!Hmac::<Sha256>::verify(b"Jefe", b"what do ya want for nothing?", &tag[..31]));
+    }
+
+    #[test]
+    fn test_hkdf_rfc5869() {
+        // test cases 1, 2 and 3 from RFC 5869, appendix A
+        let ikm = [0x0b; 22];
+        let salt: Vec<u8> = (0x00..=0x0c).collect();
+        let info: Vec<u8> = (0xf0..=0xf9).collect();
+        let prk = hkdf_extract::<Sha256>(&salt, &ikm);
+        assert_eq!("077709362c2e32df0ddc3f0dc47bba6390b6c73bb50f9c3122ec844ad7c2b3e5", hex(&prk));
+        assert_eq!(
+            "3cb25f25faacd57a90434f64d0362f2a2d2d0a90cf1a5a4c5db02d56ecc4c5bf34007208d5b887185865",
+            hex(&hkdf_expand::<Sha256>(&prk, &info, 42).unwrap()));
+
+        let ikm: Vec<u8> = (0x00..=0x4f).collect();
+        let salt: Vec<u8> = (0x60..=0xaf).collect();
+        let info: Vec<u8> = (0xb0..=0xff).collect();
+        assert_eq!(
+            concat!("b11e398dc80327a1c8e7f78c596a49344f012eda2d4efad8a050cc4c19afa97c",
+                    "59045a99cac7827271cb41c65e590e09da3275600c2f09b8367793a9aca3db71",
+                    "cc30c58179ec3e87c14c01d5c1f3434f1d87"),
+            hex(&hkdf::<Sha256>(&salt, &ikm, &info, 82).unwrap()));
+
+        let ikm = [0x0b; 22];
+        assert_eq!(
+            "19ef24a32c717b167f33a91d6f648bdf96596776afdb6377ac434c1c293ccb04",
+            hex(&hkdf_extract::<Sha256>(&[], &ikm)));
+        assert_eq!(
+            "8da4e775a563c18f715f802a063c5a31b8a11f5c5ee1879ec3454e5f3c738d2d9d201395faa4b61a96c8",
+            hex(&hkdf::<Sha256>(&[], &ikm, &[], 42).unwrap()));
+    }
+
+    #[test]
+    fn test_hkdf_length_limit() {
+        let prk = hkdf_extract::<Sha256>(b"salt", b"key");
+        assert_eq!(255 * 32, hkdf_expand::<Sha256>(&prk, &[], 255 * 32).unwrap().len());
+        assert_eq!(None, hkdf_expand::<Sha256>(&prk, &[], 255 * 32 + 1));
+        assert_eq!(Some(vec![]), hkdf_expand::<Sha256>(&prk, &[], 0));
+    }
+}
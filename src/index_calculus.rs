@@ -0,0 +1,373 @@
+use std::collections::BTreeMap;
+
+use rand::{CryptoRng, RngCore};
+
+use super::integer::{chinese_remainder, gcd, is_prime, mod_inverse, pow_mod, prime_factorize, primes_up_to, UnsignedInteger};
+use super::modular::Residue;
+
+/// A random power g^k that factors over the factor base, giving the linear
+/// relation k = sum e_i log(p_i) (mod p - 1) between the unknown logarithms.
+#[derive(Clone, Debug)]
+struct Relation<T> {
+    exponent: T,
+    /// Pairs (i, e_i) for the factor base primes dividing g^k mod p.
+    factors: Vec<(usize, u32)>,
+}
+
+/// The index calculus method for discrete logarithms in Z_p^*.
+///
+/// Unlike the generic algorithms in `discrete_log`, this uses the fact that
+/// elements of Z_p^* are integers, which can be factored. Precomputation
+/// finds the logarithms of all primes up to a smoothness bound B:
+///
+/// 1. Collect random powers g^k mod p that are B-smooth. Each factors as
+///    g^k = prod p_i^{e_i}, i.e. k = sum e_i log(p_i) (mod p - 1).
+/// 2. Solve this sparse linear system modulo each prime power q^e dividing
+///    p - 1, and combine the solutions with the Chinese remainder theorem.
+///
+/// Then the logarithm of any target h is one more smooth value away: find s
+/// with h g^s = prod p_i^{f_i}, so log(h) = sum f_i log(p_i) - s. Reducing
+/// the target to the factor base like this is the (one-step) descent.
+///
+/// With B = L_p[1/2, c] = exp(c sqrt(ln p ln ln p)) the whole attack takes
+/// L_p[1/2, c'] time: subexponential, unlike the sqrt(p) of the generic
+/// algorithms. This is why prime-field Diffie-Hellman needs a much larger
+/// modulus than elliptic curve Diffie-Hellman, where no such attack is known.
+///
+/// The implementation only does trial division and dense pivot search over
+/// sparse rows, so it is a demonstration for moduli up to about 50 bits
+/// rather than a record-setting tool.
+#[derive(Clone, Debug)]
+pub struct IndexCalculus<T = u128> {
+    base: Residue<T>,
+    factor_base: Vec<u64>,
+    /// log(factor_base[i]) to the base `base`, modulo p - 1.
+    logs: Vec<T>,
+}
+
+impl<T: UnsignedInteger> IndexCalculus<T> {
+    /// Relations to collect beyond the number of unknowns at first.
+    const EXTRA_RELATIONS: usize = 10;
+
+    /// How many times to extend the system before giving up. Each time it
+    /// grows by half, since the largest primes in the factor base divide so
+    /// few smooth values that it takes two or three times as many relations
+    /// as unknowns before every one of them has turned up.
+    const ATTEMPTS: usize = 10;
+
+    /// Returns the smoothness bound exp(sqrt(ln p ln ln p / 2)), that is
+    /// L_p[1/2, 1/sqrt(2)], but at least 30.
+    ///
+    /// The asymptotically optimal constant is 1/2, but for small p that
+    /// gives too few primes for smooth values to be found at a useful rate.
+    pub fn smoothness_bound(modulus: &T) -> u64 {
+        let ln_p = modulus.bits() as f64 * std::f64::consts::LN_2;
+        let bound = (ln_p * ln_p.ln() / 2.0).sqrt().exp();
+        (bound as u64).max(30)
+    }
+
+    /// Computes the logarithms of the primes up to bound to the given base,
+    /// which must be a primitive root modulo a prime, or returns None if
+    /// base is not a primitive root or the linear algebra keeps failing.
+    pub fn new(base: &Residue<T>, bound: u64) -> Option<IndexCalculus<T>> {
+        IndexCalculus::new_with_rng(&mut rand::thread_rng(), base, bound)
+    }
+
+    /// `new` drawing the exponents of its relations from rng.
+    pub fn new_with_rng<R: RngCore + CryptoRng + ?Sized>(
+            rng: &mut R, base: &Residue<T>, bound: u64) -> Option<IndexCalculus<T>> {
+        let p = &base.modulus;
+        assert!(is_prime(p.clone()), "Index calculus needs a prime modulus.");
+        let order = p.clone() - T::one();
+        let order_factors = prime_factorize(order.clone());
+        if order_factors.iter().any(|(q, _)| pow_mod(&base.value, &(order.clone() / q.clone()), p) == T::one()) {
+            return None;
+        }
+
+        let factor_base: Vec<u64> = primes_up_to(bound).into_iter()
+            .filter(|&q| T::from(q) < *p)
+            .collect();
+
+        let mut relations = vec![];
+        let mut wanted = factor_base.len() + Self::EXTRA_RELATIONS;
+        for _ in 0..Self::ATTEMPTS {
+            while relations.len() < wanted {
+                let k = T::random_below(rng, &order);
+                if let Some(factors) = factor_over(&pow_mod(&base.value, &k, p), &factor_base) {
+                    relations.push(Relation { exponent: k, factors });
+                }
+            }
+            wanted += wanted / 2;
+
+            let logs = match solve_modulo_order(&relations, factor_base.len(), &order_factors) {
+                Some(logs) => logs,
+                None => continue,
+            };
+            // a system with a unique solution mod every q^e can't be wrong,
+            // but this is cheap next to collecting the relations
+            if factor_base.iter().zip(logs.iter()).all(|(&q, x)| pow_mod(&base.value, x, p) == T::from(q)) {
+                return Some(IndexCalculus { base: base.clone(), factor_base, logs });
+            }
+        }
+
+        None
+    }
+
+    /// Returns the factor base, the primes up to the smoothness bound.
+    pub fn factor_base(&self) -> &[u64] {
+        &self.factor_base
+    }
+
+    /// Returns the logarithms of the factor base primes, in the same order.
+    pub fn logs(&self) -> &[T] {
+        &self.logs
+    }
+
+    /// Returns the x in [0, p - 1) with base^x = target.
+    pub fn log(&self, target: &Residue<T>) -> T {
+        self.log_with_rng(&mut rand::thread_rng(), target)
+    }
+
+    /// `log` drawing the shifts that make the target smooth from rng.
+    pub fn log_with_rng<R: RngCore + CryptoRng + ?Sized>(&self, rng: &mut R, target: &Residue<T>) -> T {
+        assert_eq!(self.base.modulus, target.modulus, "Base and target have different moduli.");
+        assert!(!target.value.is_zero(), "Zero has no logarithm.");
+        let p = &self.base.modulus;
+        let order = p.clone() - T::one();
+
+        // target * base^s is as likely to be smooth as the values in the
+        // relations, and a primitive root reaches every unit, so this ends
+        loop {
+            let s = T::random_below(rng, &order);
+            let shifted = target.value.mul_mod(&pow_mod(&self.base.value, &s, p), p);
+            if let Some(factors) = factor_over(&shifted, &self.factor_base) {
+                let log_shifted = factors.iter().fold(T::zero(), |sum, (i, e)| {
+                    sum.add_mod(&self.logs[*i].mul_mod(&T::from(*e as u64), &order), &order)
+                });
+                return log_shifted.sub_mod(&s, &order);
+            }
+        }
+    }
+}
+
+/// Returns the x in [0, p - 1) with base^x = target for a primitive root
+/// base modulo a prime p, by index calculus with the default smoothness
+/// bound, or None if base is not a primitive root.
+pub fn index_calculus<T: UnsignedInteger>(base: &Residue<T>, target: &Residue<T>) -> Option<T> {
+    index_calculus_with_rng(&mut rand::thread_rng(), base, target)
+}
+
+/// `index_calculus` drawing all its randomness from rng.
+pub fn index_calculus_with_rng<T: UnsignedInteger, R: RngCore + CryptoRng + ?Sized>(
+        rng: &mut R, base: &Residue<T>, target: &Residue<T>) -> Option<T> {
+    let bound = IndexCalculus::smoothness_bound(&base.modulus);
+    IndexCalculus::new_with_rng(rng, base, bound).map(|index_calculus| index_calculus.log_with_rng(rng, target))
+}
+
+/// Returns the factorisation of n as pairs (i, e) meaning factor_base[i]^e,
+/// or None if n has a prime factor outside the factor base.
+fn factor_over<T: UnsignedInteger>(n: &T, factor_base: &[u64]) -> Option<Vec<(usize, u32)>> {
+    let mut n = n.clone();
+    let mut factors = vec![];
+    for (i, &q) in factor_base.iter().enumerate() {
+        let q = T::from(q);
+        if q.clone() * q.clone() > n {
+            break;
+        }
+        let mut e = 0;
+        while (n.clone() % q.clone()).is_zero() {
+            n = n / q.clone();
+            e += 1;
+        }
+        if e > 0 {
+            factors.push((i, e));
+        }
+    }
+
+    // what is left is 1, or a prime, which is smooth if it is in the factor
+    // base
+    if n != T::one() {
+        let i = factor_base.binary_search_by(|q| T::from(*q).cmp(&n)).ok()?;
+        factors.push((i, 1));
+    }
+    Some(factors)
+}
+
+/// Solves the relations for the logarithms modulo the group order, one prime
+/// power at a time.
+fn solve_modulo_order<T: UnsignedInteger>(
+        relations: &[Relation<T>], unknowns: usize, order_factors: &[(T, u32)]) -> Option<Vec<T>> {
+    let mut logs = vec![T::zero(); unknowns];
+    let mut modulus = T::one();
+    for (q, e) in order_factors {
+        let prime_power = (0..*e).fold(T::one(), |power, _| power * q.clone());
+        let solution = solve_modulo_prime_power(relations, unknowns, &prime_power)?;
+        for (log, x) in logs.iter_mut().zip(solution.iter()) {
+            *log = chinese_remainder(log, &modulus, x, &prime_power);
+        }
+        modulus = modulus * prime_power;
+    }
+    Some(logs)
+}
+
+/// Solves the relations modulo a prime power m, or returns None if they
+/// don't determine every unknown.
+///
+/// Gaussian elimination on sparse rows. Modulo a prime power not every
+/// nonzero coefficient can be divided by, so a pivot must be a unit; among
+/// those the shortest row is picked, which keeps the fill-in down. The
+/// rows are only eliminated below the pivots, and the solution comes from
+/// back substitution.
+fn solve_modulo_prime_power<T: UnsignedInteger>(
+        relations: &[Relation<T>], unknowns: usize, m: &T) -> Option<Vec<T>> {
+    let mut rows: Vec<(BTreeMap<usize, T>, T)> = relations.iter()
+        .map(|relation| {
+            let row = relation.factors.iter()
+                .map(|&(i, e)| (i, T::from(e as u64) % m.clone()))
+                .filter(|(_, e)| !e.is_zero())
+                .collect();
+            (row, relation.exponent.clone() % m.clone())
+        })
+        .collect();
+
+    let mut pivots = vec![];
+    for column in 0..unknowns {
+        let pivot = rows.iter()
+            .enumerate()
+            .filter(|(_, (row, _))| row.get(&column).is_some_and(|a| gcd(a.clone(), m.clone()) == T::one()))
+            .min_by_key(|(_, (row, _))| row.len())
+            .map(|(r, _)| r)?;
+        let (mut pivot_row, mut pivot_rhs) = rows.swap_remove(pivot);
+
+        let inverse = mod_inverse(pivot_row[&column].clone(), m.clone()).unwrap();
+        for a in pivot_row.values_mut() {
+            *a = a.mul_mod(&inverse, m);
+        }
+        pivot_rhs = pivot_rhs.mul_mod(&inverse, m);
+
+        for (row, rhs) in rows.iter_mut() {
+            let a = match row.remove(&column) {
+                Some(a) => a,
+                None => continue,
+            };
+            for (&j, b) in pivot_row.iter().filter(|&(&j, _)| j != column) {
+                let entry = row.entry(j).or_insert_with(T::zero);
+                *entry = entry.sub_mod(&a.mul_mod(b, m), m);
+                if entry.is_zero() {
+                    row.remove(&j);
+                }
+            }
+            *rhs = rhs.sub_mod(&a.mul_mod(&pivot_rhs, m), m);
+        }
+        pivots.push((column, pivot_row, pivot_rhs));
+    }
+
+    // each pivot row only involves its own column and later ones
+    let mut solution = vec![T::zero(); unknowns];
+    for (column, row, rhs) in pivots.into_iter().rev() {
+        solution[column] = row.iter()
+            .filter(|&(&j, _)| j != column)
+            .fold(rhs, |x, (&j, a)| x.sub_mod(&a.mul_mod(&solution[j], m), m));
+    }
+    Some(solution)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bigint::BigUint;
+    use crate::diffie_hellman::{choose_base, compute_shared_secret, generate_secret_and_shared_value};
+    use crate::discrete_log::pohlig_hellman;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_smoothness_bound() {
+        assert_eq!(30, IndexCalculus::smoothness_bound(&1019u128));
+        assert_eq!(988, IndexCalculus::smoothness_bound(&(1u128 << 40)));
+        assert_eq!(10528, IndexCalculus::smoothness_bound(&(1u128 << 64)));
+    }
+
+    #[test]
+    fn test_factor_over() {
+        let factor_base = primes_up_to(30);
+        // 2^3 * 3 * 29 = 696
+        assert_eq!(Some(vec![(0, 3), (1, 1), (9, 1)]), factor_over(&696u128, &factor_base));
+        assert_eq!(Some(vec![]), factor_over(&1u128, &factor_base));
+        assert_eq!(None, factor_over(&(2 * 31u128), &factor_base));
+        assert_eq!(None, factor_over(&(37 * 41u128), &factor_base));
+    }
+
+    #[test]
+    fn test_solve_modulo_prime_power() {
+        // x0 + 2 x1 = 7, 3 x0 + x1 = 6, 2 x0 = 2 (mod 9): x0 = 1, x1 = 3
+        let relation = |exponent, factors| Relation { exponent, factors };
+        let relations = [
+            relation(7u128, vec![(0, 1), (1, 2)]),
+            relation(6, vec![(0, 3), (1, 1)]),
+            relation(2, vec![(0, 2)]),
+        ];
+        assert_eq!(Some(vec![1, 3]), solve_modulo_prime_power(&relations, 2, &9));
+
+        // one equation can't determine two unknowns, and 3 x0 isn't
+        // invertible modulo 9, so it can't be a pivot for x0
+        assert_eq!(None, solve_modulo_prime_power(&relations[..1], 2, &9));
+        let relations = [relation(6u128, vec![(1, 2)]), relation(6, vec![(0, 3), (1, 1)])];
+        assert_eq!(None, solve_modulo_prime_power(&relations, 2, &9));
+    }
+
+    #[test]
+    fn test_index_calculus() {
+        // p = 2q + 1 for a prime q around 2^39
+        let p = 1099511652683u128;
+        let base = choose_base(p);
+        let index_calculus = IndexCalculus::new(&base, IndexCalculus::smoothness_bound(&p)).unwrap();
+        for (&q, log) in index_calculus.factor_base().iter().zip(index_calculus.logs()) {
+            assert_eq!(q as u128, base.pow(*log as i128).value);
+        }
+
+        for x in [0u128, 1, 2, 987654321098, p - 2] {
+            assert_eq!(x, index_calculus.log(&base.pow(x as i128)));
+        }
+        let target = Residue::from_unsigned_integer(1000003, p);
+        assert_eq!(target, base.pow(index_calculus.log(&target) as i128));
+    }
+
+    #[test]
+    fn test_index_calculus_prime_powers() {
+        // p - 1 = 2^4 * 3^2 * 5^3 * 3818123
+        let p = 68726214001u128;
+        let base = choose_base(p);
+        let x = 12345678901 % (p - 1);
+        let target = base.pow(x as i128);
+        assert_eq!(Some(x), index_calculus(&base, &target));
+        assert_eq!(Some(x), pohlig_hellman(&base, &target, &(p - 1)));
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+        assert_eq!(Some(x), index_calculus_with_rng(&mut rng, &base, &target));
+
+        // 4 is a square, so it can't be a primitive root
+        assert!(IndexCalculus::new(&Residue::from_unsigned_integer(4, p), 100).is_none());
+    }
+
+    #[test]
+    fn test_index_calculus_breaks_diffie_hellman() {
+        let p = 1073742623u128;
+        let base = choose_base(p);
+        let (alice_secret, alice_shared) = generate_secret_and_shared_value(&base);
+        let (_, bob_shared) = generate_secret_and_shared_value(&base);
+
+        let recovered = index_calculus(&base, &alice_shared).unwrap();
+        assert_eq!(
+            compute_shared_secret(alice_secret, &bob_shared),
+            compute_shared_secret(recovered as i128, &bob_shared));
+    }
+
+    #[test]
+    fn test_index_calculus_big_integers() {
+        let p = BigUint::from(1073742623u64);
+        let base = Residue::primitive_root(p.clone()).unwrap();
+        let x = BigUint::from(123456789u64);
+        let target = Residue::from_unsigned_integer(pow_mod(&base.value, &x, &p), p);
+        assert_eq!(Some(x), index_calculus(&base, &target));
+    }
+}
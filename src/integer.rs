@@ -1,115 +1,977 @@
-/// Determines whether a given positiv integer is prime.
+use rand::Rng;
+use std::cmp::min;
+use std::fmt::{Debug, Display};
+use std::hash::Hash;
+use std::ops::{Add, Div, Mul, Neg, Rem, Shl, Shr, Sub};
+
+use super::bigint::BigUint;
+
+/// An unsigned integer type that the number-theoretic functions in this crate
+/// (and `modular::Residue`) can operate on.
+///
+/// Implemented for `u128` and for the arbitrary-precision `bigint::BigUint`.
+pub trait UnsignedInteger:
+    Clone + Debug + Display + Eq + Ord + Hash + From<u64>
+    + Add<Output = Self> + Sub<Output = Self> + Mul<Output = Self>
+    + Div<Output = Self> + Rem<Output = Self>
+    + Shl<u32, Output = Self> + Shr<u32, Output = Self>
+{
+    /// The matching signed type, used for Bezout coefficients and exponents.
+    type Signed: SignedInteger<Unsigned = Self>;
+
+    fn zero() -> Self {
+        Self::from(0)
+    }
+
+    fn one() -> Self {
+        Self::from(1)
+    }
+
+    fn is_zero(&self) -> bool;
+
+    fn is_even(&self) -> bool;
+
+    /// Returns the number of significant bits (0 for zero).
+    fn bits(&self) -> u32;
+
+    /// Returns whether bit i (counting from the least significant) is set.
+    fn bit(&self, i: u32) -> bool;
+
+    /// Converts to the signed type. Panics if the value is above
+    /// `signed_max()`.
+    fn to_signed(&self) -> Self::Signed;
+
+    /// The largest value `to_signed` can convert, or None if it can convert
+    /// every value. For u128 this is i128::MAX.
+    fn signed_max() -> Option<Self> {
+        None
+    }
+
+    /// Returns the value as a u128, if it fits.
+    fn to_u128(&self) -> Option<u128>;
+
+    fn from_u128(n: u128) -> Self;
+
+    fn to_biguint(&self) -> BigUint;
+
+    /// Converts back from a `BigUint`, which must fit in Self.
+    fn from_biguint(n: &BigUint) -> Self;
+
+    /// Returns (self + other) mod modulus, for self, other < modulus.
+    fn add_mod(&self, other: &Self, modulus: &Self) -> Self;
+
+    /// Returns (self - other) mod modulus, for self, other < modulus.
+    fn sub_mod(&self, other: &Self, modulus: &Self) -> Self {
+        if other.is_zero() {
+            self.clone()
+        } else {
+            self.add_mod(&(modulus.clone() - other.clone()), modulus)
+        }
+    }
+
+    /// Returns (self * other) mod modulus, for self, other < modulus.
+    fn mul_mod(&self, other: &Self, modulus: &Self) -> Self;
+
+    /// Returns a uniformly random integer in [0, bound).
+    fn random_below<R: Rng + ?Sized>(rng: &mut R, bound: &Self) -> Self;
+}
+
+/// The signed counterpart of an `UnsignedInteger`.
+pub trait SignedInteger:
+    Clone + Debug + Display + Eq + Ord + From<i64>
+    + Add<Output = Self> + Sub<Output = Self> + Mul<Output = Self>
+    + Div<Output = Self> + Rem<Output = Self> + Neg<Output = Self>
+{
+    type Unsigned: UnsignedInteger<Signed = Self>;
+
+    fn is_negative(&self) -> bool;
+
+    fn unsigned_abs(&self) -> Self::Unsigned;
+
+    /// Returns the least non-negative residue of self modulo the given
+    /// modulus.
+    fn modulo(&self, modulus: &Self::Unsigned) -> Self::Unsigned;
+}
+
+impl UnsignedInteger for u128 {
+    type Signed = i128;
+
+    fn is_zero(&self) -> bool {
+        *self == 0
+    }
+
+    fn is_even(&self) -> bool {
+        self & 1 == 0
+    }
+
+    fn bits(&self) -> u32 {
+        u128::BITS - self.leading_zeros()
+    }
+
+    fn bit(&self, i: u32) -> bool {
+        i < u128::BITS && (self >> i) & 1 == 1
+    }
+
+    fn to_signed(&self) -> i128 {
+        i128::try_from(*self).expect("u128 value does not fit in an i128.")
+    }
+
+    fn signed_max() -> Option<u128> {
+        Some(i128::MAX as u128)
+    }
+
+    fn to_u128(&self) -> Option<u128> {
+        Some(*self)
+    }
+
+    fn from_u128(n: u128) -> u128 {
+        n
+    }
+
+    fn to_biguint(&self) -> BigUint {
+        BigUint::from(*self)
+    }
+
+    fn from_biguint(n: &BigUint) -> u128 {
+        n.to_u128().expect("BigUint does not fit in a u128.")
+    }
+
+    fn add_mod(&self, other: &u128, modulus: &u128) -> u128 {
+        // self + other < 2 * modulus, so at most one subtraction is needed,
+        // but the sum itself may not fit in 128 bits
+        let (sum, overflow) = self.overflowing_add(*other);
+        if overflow || sum >= *modulus {
+            sum.wrapping_sub(*modulus)
+        } else {
+            sum
+        }
+    }
+
+    fn mul_mod(&self, other: &u128, modulus: &u128) -> u128 {
+        let (hi, lo) = widening_mul(*self, *other);
+        rem_u256(hi, lo, *modulus)
+    }
+
+    fn random_below<R: Rng + ?Sized>(rng: &mut R, bound: &u128) -> u128 {
+        rng.gen_range(0..*bound)
+    }
+}
+
+impl SignedInteger for i128 {
+    type Unsigned = u128;
+
+    fn is_negative(&self) -> bool {
+        *self < 0
+    }
+
+    fn unsigned_abs(&self) -> u128 {
+        i128::unsigned_abs(*self)
+    }
+
+    fn modulo(&self, modulus: &u128) -> u128 {
+        if *modulus > i128::MAX as u128 {
+            // the modulus does not fit in an i128, but a non-negative self is
+            // already reduced and a negative one is a single wrap away
+            return if *self >= 0 {
+                *self as u128
+            } else {
+                modulus - i128::unsigned_abs(*self)
+            };
+        }
+        self.rem_euclid(*modulus as i128) as u128
+    }
+}
+
+/// Returns the full 256-bit product of two u128 values as (high, low) halves.
+///
+/// Schoolbook multiplication on 64-bit halves, so that every partial product
+/// fits in a u128.
+pub fn widening_mul(a: u128, b: u128) -> (u128, u128) {
+    const MASK: u128 = u64::MAX as u128;
+    let (a1, a0) = (a >> 64, a & MASK);
+    let (b1, b0) = (b >> 64, b & MASK);
+
+    let p00 = a0 * b0;
+    let p01 = a0 * b1;
+    let p10 = a1 * b0;
+    let p11 = a1 * b1;
+
+    // middle column, which is less than 3 * 2^64
+    let mid = (p00 >> 64) + (p01 & MASK) + (p10 & MASK);
+    let lo = (p00 & MASK) | mid << 64;
+    let hi = p11 + (p01 >> 64) + (p10 >> 64) + (mid >> 64);
+
+    (hi, lo)
+}
+
+/// Returns (hi * 2^128 + lo) mod m, for hi < m.
+///
+/// The condition hi < m always holds for the product of two residues mod m,
+/// and guarantees that every partial quotient below fits in 64 bits.
+fn rem_u256(hi: u128, lo: u128, m: u128) -> u128 {
+    if hi == 0 {
+        return lo % m;
+    }
+
+    if m >> 64 == 0 {
+        // every partial remainder is below 2^64, so we can bring down 64 bits
+        // at a time without overflow
+        let r = (hi << 64 | lo >> 64) % m;
+        return (r << 64 | lo & u64::MAX as u128) % m;
+    }
+
+    // Normalize so the top bit of m is set (shift < 64 since m >= 2^64), then
+    // do two steps of long division in base 2^64.
+    let shift = m.leading_zeros();
+    let m = m << shift;
+    let (hi, lo) = if shift == 0 {
+        (hi, lo)
+    } else {
+        (hi << shift | lo >> (128 - shift), lo << shift)
+    };
+
+    let r = rem_192_by_128(hi, (lo >> 64) as u64, m);
+    let r = rem_192_by_128(r, lo as u64, m);
+
+    r >> shift
+}
+
+/// Returns (hi * 2^64 + lo) mod m, for m with its top bit set and hi < m.
+///
+/// This is one step of Knuth's Algorithm D: the quotient digit estimated from
+/// the top limbs is at most 2 too large, so we correct it by subtracting m
+/// from the trial product until it no longer exceeds the dividend.
+fn rem_192_by_128(hi: u128, lo: u64, m: u128) -> u128 {
+    let (m_hi, m_lo) = (m >> 64, m as u64);
+
+    let q_hat = min(hi / m_hi, u64::MAX as u128);
+
+    // 192-bit values are represented as (top 128 bits, bottom 64 bits)
+    let low_product = q_hat * m_lo as u128;
+    let mut p = (q_hat * m_hi + (low_product >> 64), low_product as u64);
+
+    while p > (hi, lo) {
+        let (p_lo, borrow) = p.1.overflowing_sub(m_lo);
+        p = (p.0 - m_hi - borrow as u128, p_lo);
+    }
+
+    let (r_lo, borrow) = lo.overflowing_sub(p.1);
+    let r_hi = hi - p.0 - borrow as u128;
+
+    r_hi << 64 | r_lo as u128
+}
+
+/// The primes below 100, used to quickly dispose of most composites before
+/// running any of the probabilistic tests.
+const SMALL_PRIMES: [u64; 25] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71,
+    73, 79, 83, 89, 97,
+];
+
+/// Miller-Rabin witnesses that correctly classify every n < 2^64
+/// (Jim Sinclair's set; see https://miller-rabin.appspot.com).
+const WITNESSES_U64: [u64; 7] = [
+    2, 325, 9375, 28178, 450775, 9780504, 1795265022,
+];
+
+/// The first 13 primes, which are Miller-Rabin witnesses for every
+/// n < 3317044064679887385961981 (Sorenson and Webster, 2015).
+const WITNESSES_SMALL_PRIMES: [u64; 13] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41,
+];
+
+/// Upper bound (exclusive) for which `WITNESSES_SMALL_PRIMES` is proven.
+const WITNESSES_SMALL_PRIMES_BOUND: u128 = 3317044064679887385961981;
+
+/// Determines whether a given positive integer is prime.
+///
+/// Dispatches by size:
+///     n < 100^2: trial division by the primes below 100 is conclusive;
+///     n < 2^64: Miller-Rabin with a 7-base witness set, proven correct;
+///     n < 3.3 * 10^24: Miller-Rabin with the first 13 primes, proven correct;
+///     otherwise: Baillie-PSW.
+/// No deterministic witness set is known for all of u128, so the last case
+/// is only "industrial strength": no Baillie-PSW pseudoprime is known, and
+/// none exist below 2^64, but there is no proof that none exist.
+pub fn is_prime<T: UnsignedInteger>(n: T) -> bool {
+    for &p in SMALL_PRIMES.iter() {
+        let p = T::from(p);
+        if n == p {
+            return true;
+        }
+        if (n.clone() % p).is_zero() {
+            return false;
+        }
+    }
+    if n < T::from(2) {
+        return false;
+    }
+    if n < T::from(100 * 100) {
+        return true;
+    }
+
+    match n.to_u128() {
+        Some(m) if m <= u64::MAX as u128 =>
+            miller_rabin_with_witnesses(n, &WITNESSES_U64),
+        Some(m) if m < WITNESSES_SMALL_PRIMES_BOUND =>
+            miller_rabin_with_witnesses(n, &WITNESSES_SMALL_PRIMES),
+        _ => baillie_psw(n),
+    }
+}
+
+/// Determines whether a given positive integer is prime by trial division.
 ///
 /// This function uses a simple sieve algorithm, with the "6k+k1"
-/// optimization. Time complexity O(sqrt(n)), space O(1).
-pub fn is_prime(n: u128) -> bool {
-    if n == 0 || n == 1 || n > 2 && n % 2 == 0 || n > 3 && n % 3 == 0 {
+/// optimization. Time complexity O(sqrt(n)), space O(1). Only practical for
+/// small n, but useful as a reference for the faster tests.
+pub fn is_prime_by_trial_division<T: UnsignedInteger>(n: T) -> bool {
+    let (two, three) = (T::from(2), T::from(3));
+    if n < two {
+        return false;
+    }
+    if n == two || n == three {
+        return true;
+    }
+    if n.is_even() || (n.clone() % three).is_zero() {
         return false;
     }
 
-    let mut i: u128 = 5;
-    while i.pow(2) <= n {
-        if n % i == 0 || n % (i + 2) == 0 {
+    let mut i = T::from(5);
+    while i.clone() * i.clone() <= n {
+        if (n.clone() % i.clone()).is_zero()
+            || (n.clone() % (i.clone() + two.clone())).is_zero() {
             return false;
         }
-        i += 6;
+        i = i + T::from(6);
     }
 
     true
 }
 
+/// Returns base^e mod m, by square-and-multiply.
+pub fn pow_mod<T: UnsignedInteger>(base: &T, e: &T, m: &T) -> T {
+    let mut b = T::one() % m.clone();
+    let base = base.clone() % m.clone();
+
+    for i in (0..e.bits()).rev() {
+        b = b.mul_mod(&b, m);
+        if e.bit(i) {
+            b = b.mul_mod(&base, m);
+        }
+    }
+
+    b
+}
+
+/// Returns x / 2 mod m, for odd m and x < m.
+fn half_mod<T: UnsignedInteger>(x: &T, m: &T) -> T {
+    if x.is_even() {
+        x.clone() >> 1
+    } else {
+        // (x + m) / 2, computed without overflowing
+        (x.clone() >> 1) + (m.clone() >> 1) + T::one()
+    }
+}
+
+/// Runs one round of the Miller-Rabin test on odd n > 2 with the given base.
+///
+/// Writes n - 1 = d * 2^s with d odd. Then n is a strong probable prime to
+/// base a if a^d = 1 (mod n) or a^{d * 2^r} = -1 (mod n) for some
+/// 0 <= r < s. Every prime passes; a composite passes for at most 1/4 of all
+/// bases.
+pub fn is_strong_probable_prime<T: UnsignedInteger>(n: &T, base: &T) -> bool {
+    let one = T::one();
+    let minus_one = n.clone() - one.clone();
+
+    let mut s = 0;
+    let mut d = minus_one.clone();
+    while d.is_even() {
+        d = d >> 1;
+        s += 1;
+    }
+
+    let mut x = pow_mod(base, &d, n);
+    if x == one || x == minus_one {
+        return true;
+    }
+    for _ in 1..s {
+        x = x.mul_mod(&x, n);
+        if x == minus_one {
+            return true;
+        }
+        if x == one {
+            return false;
+        }
+    }
+
+    false
+}
+
+/// Miller-Rabin test with the given list of bases.
+///
+/// Bases that are multiples of n are skipped, as they say nothing about n.
+pub fn miller_rabin_with_witnesses<T: UnsignedInteger>(
+        n: T, witnesses: &[u64]) -> bool {
+    if n < T::from(4) {
+        return n >= T::from(2);
+    }
+    if n.is_even() {
+        return false;
+    }
+
+    witnesses.iter().all(|&a| {
+        let a = T::from(a) % n.clone();
+        a.is_zero() || is_strong_probable_prime(&n, &a)
+    })
+}
+
+/// Probabilistic Miller-Rabin test with the given number of random bases.
+///
+/// A prime is always reported as prime. A composite is reported as prime
+/// with probability at most 4^{-rounds}.
+pub fn miller_rabin<T: UnsignedInteger>(n: T, rounds: u32) -> bool {
+    if n < T::from(4) {
+        return n >= T::from(2);
+    }
+    if n.is_even() {
+        return false;
+    }
+
+    let mut rng = rand::thread_rng();
+    let two = T::from(2);
+    (0..rounds).all(|_| {
+        // random base in [2, n - 2]
+        let a = T::random_below(&mut rng, &(n.clone() - T::from(3))) + two.clone();
+        is_strong_probable_prime(&n, &a)
+    })
+}
+
+/// Returns the Jacobi symbol (a/n), for odd positive n.
+///
+/// Uses quadratic reciprocity and the supplementary law for (2/n), so it
+/// never needs to factor n.
+pub fn jacobi_symbol<T: UnsignedInteger>(a: T, n: T) -> i8 {
+    assert!(!n.is_even(), "The Jacobi symbol is only defined for odd n.");
+
+    let mut a = a % n.clone();
+    let mut n = n;
+    let mut result = 1;
+
+    while !a.is_zero() {
+        while a.is_even() {
+            a = a >> 1;
+            // (2/n) = -1 iff n = 3, 5 (mod 8)
+            let n_mod_8 = (n.clone() % T::from(8)).to_u128().unwrap();
+            if n_mod_8 == 3 || n_mod_8 == 5 {
+                result = -result;
+            }
+        }
+        std::mem::swap(&mut a, &mut n);
+        // (a/n)(n/a) = -1 iff a = n = 3 (mod 4)
+        let four = T::from(4);
+        if (a.clone() % four.clone()).to_u128() == Some(3)
+            && (n.clone() % four).to_u128() == Some(3) {
+            result = -result;
+        }
+        a = a % n.clone();
+    }
+
+    if n == T::one() { result } else { 0 }
+}
+
+/// Returns a square root of a modulo the prime p, or None if a is not a
+/// quadratic residue.
+///
+/// Uses the Tonelli-Shanks algorithm: write p - 1 = q * 2^s with q odd, start
+/// from the guess r = a^{(q+1)/2}, and fix up the error t = a^q, whose order
+/// is a power of two, using powers of a fixed non-residue z.
+pub fn sqrt_mod_prime<T: UnsignedInteger>(a: &T, p: &T) -> Option<T> {
+    let a = a.clone() % p.clone();
+    if a.is_zero() || *p == T::from(2) {
+        return Some(a);
+    }
+    if jacobi_symbol(a.clone(), p.clone()) != 1 {
+        return None;
+    }
+
+    let one = T::one();
+    let mut s = 0;
+    let mut q = p.clone() - one.clone();
+    while q.is_even() {
+        q = q >> 1;
+        s += 1;
+    }
+
+    // any non-residue will do; half of all candidates are
+    let mut z = T::from(2);
+    while jacobi_symbol(z.clone(), p.clone()) != -1 {
+        z = z + one.clone();
+    }
+
+    let mut m = s;
+    let mut c = pow_mod(&z, &q, p);
+    let mut t = pow_mod(&a, &q, p);
+    let mut r = pow_mod(&a, &((q + one.clone()) >> 1), p);
+
+    while t != one {
+        // least i with t^{2^i} = 1
+        let mut i = 0;
+        let mut t_power = t.clone();
+        while t_power != one {
+            t_power = t_power.mul_mod(&t_power, p);
+            i += 1;
+        }
+
+        let mut b = c;
+        for _ in 0..(m - i - 1) {
+            b = b.mul_mod(&b, p);
+        }
+        m = i;
+        c = b.mul_mod(&b, p);
+        t = t.mul_mod(&c, p);
+        r = r.mul_mod(&b, p);
+    }
+
+    Some(r)
+}
+
+/// Strong Lucas probable prime test on odd n > 2, with Selfridge's
+/// parameters.
+///
+/// Chooses the first D in 5, -7, 9, -11, ... with Jacobi symbol (D/n) = -1,
+/// and sets P = 1, Q = (1 - D) / 4. Writing n + 1 = d * 2^s with d odd, n is
+/// a strong Lucas probable prime if U_d = 0 (mod n) or V_{d * 2^r} = 0
+/// (mod n) for some 0 <= r < s.
+pub fn is_strong_lucas_probable_prime<T: UnsignedInteger>(n: &T) -> bool {
+    // A perfect square has no D with (D/n) = -1, so the search below would
+    // never end.
+    let root = isqrt(n.clone());
+    if root.clone() * root == *n {
+        return false;
+    }
+
+    // find D, tracking its absolute value and sign
+    let mut abs_d = 5u64;
+    let mut d_negative = false;
+    loop {
+        let d_mod_n = if d_negative {
+            T::zero().sub_mod(&(T::from(abs_d) % n.clone()), n)
+        } else {
+            T::from(abs_d) % n.clone()
+        };
+        match jacobi_symbol(d_mod_n.clone(), n.clone()) {
+            -1 => break,
+            0 if T::from(abs_d) % n.clone() != T::zero() => return false,
+            _ => {}
+        }
+        abs_d += 2;
+        d_negative = !d_negative;
+    }
+
+    // Q = (1 - D) / 4, reduced mod n
+    let q_mod_n = if d_negative {
+        T::from(abs_d.div_ceil(4)) % n.clone()  // (1 + |D|) / 4
+    } else {
+        T::zero().sub_mod(&(T::from(abs_d / 4) % n.clone()), n)  // -(|D| - 1) / 4
+    };
+    let d_mod_n = if d_negative {
+        T::zero().sub_mod(&(T::from(abs_d) % n.clone()), n)
+    } else {
+        T::from(abs_d) % n.clone()
+    };
+
+    let mut s = 0;
+    let mut d = n.clone() + T::one();
+    while d.is_even() {
+        d = d >> 1;
+        s += 1;
+    }
+
+    // Compute U_d, V_d and Q^d by running through the bits of d, using
+    //     U_{2k} = U_k V_k,    V_{2k} = V_k^2 - 2 Q^k,
+    //     U_{k+1} = (P U_k + V_k) / 2,    V_{k+1} = (D U_k + P V_k) / 2,
+    // with P = 1.
+    let mut u = T::zero();
+    let mut v = T::from(2) % n.clone();
+    let mut q_k = T::one();
+    for i in (0..d.bits()).rev() {
+        u = u.mul_mod(&v, n);
+        v = v.mul_mod(&v, n).sub_mod(&q_k.add_mod(&q_k, n), n);
+        q_k = q_k.mul_mod(&q_k, n);
+        if d.bit(i) {
+            let new_u = half_mod(&u.add_mod(&v, n), n);
+            v = half_mod(&d_mod_n.mul_mod(&u, n).add_mod(&v, n), n);
+            u = new_u;
+            q_k = q_k.mul_mod(&q_mod_n, n);
+        }
+    }
+
+    if u.is_zero() || v.is_zero() {
+        return true;
+    }
+    for _ in 1..s {
+        v = v.mul_mod(&v, n).sub_mod(&q_k.add_mod(&q_k, n), n);
+        if v.is_zero() {
+            return true;
+        }
+        q_k = q_k.mul_mod(&q_k, n);
+    }
+
+    false
+}
+
+/// Baillie-PSW primality test: a base-2 strong probable prime test followed
+/// by a strong Lucas test.
+///
+/// The two tests fail on very different composites, and no number is known
+/// that passes both; it has been verified that none exist below 2^64.
+pub fn baillie_psw<T: UnsignedInteger>(n: T) -> bool {
+    for &p in SMALL_PRIMES.iter() {
+        let p = T::from(p);
+        if n == p {
+            return true;
+        }
+        if (n.clone() % p).is_zero() {
+            return false;
+        }
+    }
+    if n < T::from(2) {
+        return false;
+    }
+
+    is_strong_probable_prime(&n, &T::from(2)) && is_strong_lucas_probable_prime(&n)
+}
+
 /// Returns the greatest common divisor of the given numbers.
 ///
 /// Uses the Euclidean algorithm.
-pub fn gcd(x: u128, y: u128) -> u128 {
+pub fn gcd<T: UnsignedInteger>(x: T, y: T) -> T {
     let (mut a, mut b) = if x >= y { (x, y) } else { (y, x) };
 
     loop {
-        let r = a % b;
+        if b.is_zero() { break a; }
+
+        let r = a % b.clone();
 
         a = b;
         b = r;
-
-        if b == 0 { break a; }
     }
 }
 
 /// Given integers (x, y), returns (g, u, v) such that ux + vy = g = gcd(x, y).
 ///
-/// Uses the Extended Euclidean Algorithm.
-pub fn gcd_with_coefficients(x: u128, y: u128) -> (u128, i128, i128) {
-    let mut u = 1i128;
+/// Uses the Extended Euclidean Algorithm. Intermediate values live in
+/// T::Signed, so for u128 both inputs must be at most i128::MAX; see
+/// `mod_inverse` for a variant without that restriction.
+pub fn gcd_with_coefficients<T: UnsignedInteger>(
+        x: T, y: T) -> (T, T::Signed, T::Signed) {
+    let (x, y) = (x.to_signed(), y.to_signed());
+    let mut u = T::Signed::from(1);
     let v;
-    let mut g = x as i128;
-    let mut a = 0i128;
-    let mut b = y as i128;
+    let mut g = x.clone();
+    let mut a = T::Signed::from(0);
+    let mut b = y.clone();
 
     loop {
-        if b == 0 {
-            v = (g - x as i128 * u) / y as i128;
+        if b == T::Signed::from(0) {
+            v = (g.clone() - x * u.clone()) / y;
             break;
         }
-        let (q, t) = (g / b, g % b);  // division with remainder
-        let s = u - q * a;
+        // division with remainder
+        let (q, t) = (g.clone() / b.clone(), g % b.clone());
+        let s = u - q * a.clone();
         u = a;
         g = b;
         a = s;
         b = t;
     }
 
-    (g as u128, u, v)
+    (g.unsigned_abs(), u, v)
 }
 
-/// Integer square root
+/// Returns the inverse of a modulo m, or None if gcd(a, m) != 1.
 ///
-/// Adapted from:
-///     https://en.wikipedia.org/wiki/Methods_of_computing_square_roots#Binary_numeral_system_(base_2)
-pub fn isqrt(n: u128) -> u128 {
-    let mut x = n;
-    let mut c = 0u128;
-    let mut d = 1 << 126;  // second-to-top bit set
+/// Runs the Extended Euclidean Algorithm, but tracks only the coefficient of
+/// a, and keeps it reduced modulo m. Unlike `gcd_with_coefficients`, this
+/// never needs a signed type wide enough to hold m.
+pub fn mod_inverse<T: UnsignedInteger>(a: T, m: T) -> Option<T> {
+    let (mut r0, mut r1) = (m.clone(), a % m.clone());
+    let (mut t0, mut t1) = (T::zero(), T::one() % m.clone());
 
-    while d > n { d >>= 2; }
+    while !r1.is_zero() {
+        let q = r0.clone() / r1.clone();
+        let r = r0 - q.clone() * r1.clone();
+        r0 = r1;
+        r1 = r;
 
-    while d > 0 {
-        if x >= c + d {
-            x -= c + d;
-            c = (c >> 1) + d;
-        } else {
-            c >>= 1;
+        // t0 - q * t1 (mod m)
+        let qt = (q % m.clone()).mul_mod(&t1, &m);
+        let t = if qt.is_zero() { t0 } else { t0.add_mod(&(m.clone() - qt), &m) };
+        t0 = t1;
+        t1 = t;
+    }
+
+    if r0 == T::one() { Some(t0) } else { None }
+}
+
+/// Returns the x in [0, m1 * m2) with x = a1 (mod m1) and x = a2 (mod m2),
+/// for coprime m1 and m2.
+///
+/// Garner's formula: x = a1 + m1 * ((a2 - a1) / m1 mod m2).
+pub fn chinese_remainder<T: UnsignedInteger>(a1: &T, m1: &T, a2: &T, m2: &T) -> T {
+    let m1_inverse = mod_inverse(m1.clone() % m2.clone(), m2.clone())
+        .expect("Moduli are not coprime.");
+    let a1 = a1.clone() % m1.clone();
+    let difference = (a2.clone() % m2.clone()).sub_mod(&(a1.clone() % m2.clone()), m2);
+    a1 + m1.clone() * difference.mul_mod(&m1_inverse, m2)
+}
+
+/// Integer square root, i.e. the largest r such that r^2 <= n.
+///
+/// Uses Newton's method starting from a power of two above sqrt(n), so the
+/// iterates decrease monotonically to the answer.
+pub fn isqrt<T: UnsignedInteger>(n: T) -> T {
+    if n.is_zero() {
+        return n;
+    }
+
+    let mut x = T::one() << n.bits().div_ceil(2);
+    loop {
+        let y = (x.clone() + n.clone() / x.clone()) >> 1;
+        if y >= x { break x; }
+        x = y;
+    }
+}
+
+/// Integer k-th root, i.e. the largest r such that r^k <= n, for k >= 1.
+///
+/// Builds r one bit at a time from the top, keeping each bit whose
+/// inclusion leaves r^k <= n. The powers are compared against n as they are
+/// built up, so nothing ever exceeds n and fixed-width types can't overflow.
+pub fn integer_root<T: UnsignedInteger>(n: &T, k: u32) -> T {
+    assert!(k >= 1, "There is no 0th root.");
+    let at_most_n = |r: &T| {
+        let mut power = T::one();
+        for _ in 0..k {
+            if power > n.clone() / r.clone() {
+                return false;
+            }
+            power = power * r.clone();
+        }
+        true
+    };
+
+    let mut root = T::zero();
+    for i in (0..n.bits().div_ceil(k)).rev() {
+        let candidate = root.clone() + (T::one() << i);
+        if at_most_n(&candidate) {
+            root = candidate;
+        }
+    }
+    root
+}
+
+/// If n = r^k for some k >= 2, returns (r, k) with k as large as possible;
+/// otherwise None.
+///
+/// Only prime k need checking, since an r^k with composite k is also a
+/// p-th power for any prime p dividing k. The largest k is found by
+/// repeating on r.
+pub fn perfect_power<T: UnsignedInteger>(n: &T) -> Option<(T, u32)> {
+    if *n <= T::one() {
+        return None;
+    }
+
+    let bits = n.bits() as u64;
+    for p in primes_up_to(bits) {
+        let p = p as u32;
+        let root = integer_root(n, p);
+        if (0..p).fold(T::one(), |power, _| power * root.clone()) == *n {
+            return match perfect_power(&root) {
+                Some((r, k)) => Some((r, k * p)),
+                None => Some((root, p)),
+            };
+        }
+    }
+    None
+}
+
+/// Finds a non-trivial factor of the composite number n.
+///
+/// Uses Pollard's rho method with Brent's cycle detection: iterate the
+/// pseudo-random map x -> x^2 + c (mod n), which modulo any prime factor p
+/// of n falls into a cycle after about sqrt(p) steps. Brent's variant finds
+/// the cycle by comparing against a checkpoint that moves at powers of two,
+/// and batches the differences into a single product so that only one gcd
+/// is needed every BATCH steps. Expected time O(p^{1/2}) for the smallest
+/// prime factor p, i.e. at most O(n^{1/4}).
+///
+/// Does not terminate if n is prime, and returns 2 for even n.
+pub fn pollard_brent<T: UnsignedInteger>(n: &T) -> T {
+    pollard_brent_with_limit(n, u64::MAX).unwrap()
+}
+
+/// Like `pollard_brent`, but gives up and returns None after roughly
+/// max_iterations evaluations of the pseudo-random map.
+pub fn pollard_brent_with_limit<T: UnsignedInteger>(
+        n: &T, max_iterations: u64) -> Option<T> {
+    const BATCH: u64 = 128;
+
+    if n.is_even() {
+        return Some(T::from(2));
+    }
+
+    let mut rng = rand::thread_rng();
+    let one = T::one();
+    let abs_diff = |a: &T, b: &T| {
+        if a > b { a.clone() - b.clone() } else { b.clone() - a.clone() }
+    };
+    let mut iterations = 0u64;
+
+    loop {
+        let c = T::random_below(&mut rng, &(n.clone() - one.clone())) + one.clone();
+        let f = |x: &T| x.mul_mod(x, n).add_mod(&c, n);
+
+        let mut y = T::random_below(&mut rng, n);
+        let mut x = y.clone();
+        let mut saved_y = y.clone();
+        let mut product = one.clone();
+        let mut g = one.clone();
+        let mut r = 1u64;
+
+        while g == one {
+            if iterations >= max_iterations {
+                return None;
+            }
+
+            x = y.clone();
+            for _ in 0..r {
+                y = f(&y);
+            }
+
+            let mut k = 0;
+            while k < r && g == one {
+                saved_y = y.clone();
+                for _ in 0..min(BATCH, r - k) {
+                    y = f(&y);
+                    product = product.mul_mod(&abs_diff(&x, &y), n);
+                }
+                g = gcd(product.clone(), n.clone());
+                k += BATCH;
+            }
+            iterations = iterations.saturating_add(2 * r);
+            r *= 2;
         }
-        d >>= 2;
+
+        if g == *n {
+            // the batch overshot (or the product hit 0); redo it one step at
+            // a time from the last checkpoint
+            loop {
+                saved_y = f(&saved_y);
+                g = gcd(abs_diff(&x, &saved_y), n.clone());
+                if g != one {
+                    break;
+                }
+            }
+        }
+
+        if g != *n {
+            return Some(g);
+        }
+        // x and y collided modulo every factor at once; try another c
+    }
+}
+
+/// Converts a big-endian byte string to an integer.
+pub fn from_be_bytes<T: UnsignedInteger>(bytes: &[u8]) -> T {
+    bytes.iter().fold(T::zero(), |x, &b| (x << 8) + T::from(b as u64))
+}
+
+/// Converts an integer to a big-endian byte string of the given length, or
+/// returns None if it does not fit.
+pub fn to_be_bytes<T: UnsignedInteger>(x: &T, length: usize) -> Option<Vec<u8>> {
+    if x.bits() as usize > 8 * length {
+        return None;
     }
 
-    c
+    let mut x = x.clone();
+    let mut bytes = vec![0; length];
+    for b in bytes.iter_mut().rev() {
+        *b = (x.clone() % T::from(256)).to_u128().unwrap() as u8;
+        x = x >> 8;
+    }
+    Some(bytes)
+}
+
+/// Returns all primes p <= n, in increasing order.
+///
+/// Sieve of Eratosthenes, O(n log log n) time and O(n) space.
+pub fn primes_up_to(n: u64) -> Vec<u64> {
+    let n = n as usize;
+    let mut is_composite = vec![false; n + 1];
+    let mut primes = vec![];
+
+    for i in 2..=n {
+        if is_composite[i] {
+            continue;
+        }
+        primes.push(i as u64);
+        for j in (i * i..=n).step_by(i) {
+            is_composite[j] = true;
+        }
+    }
+
+    primes
 }
 
 /// Factors the given positive integer into prime powers.
 ///
-/// Returns a vector of pairs (p, e) where p^e is a maximal prime power of n.
+/// Returns a vector of pairs (p, e) where p^e is a maximal prime power of n,
+/// sorted by p.
 ///
-/// TODO replace with a faster algorithm (currently using a slow, naïve one)
-pub fn prime_factorize(mut n: u128) -> Vec<(u128, u32)> {
-    assert!(n > 0, "Cannot factor 0.");
+/// Divides out the primes below 100, splits off perfect powers, and splits
+/// whatever composites remain with `pollard_brent`. The running time is then
+/// dominated by the second-largest prime factor q, at roughly sqrt(q) steps;
+/// anything up to about 2^100 with a second-largest factor below 2^50
+/// factors in well under a second. For harder numbers, and to see which
+/// method split what, use `factor::FactorPipeline::automatic()`.
+pub fn prime_factorize<T: UnsignedInteger>(mut n: T) -> Vec<(T, u32)> {
+    assert!(!n.is_zero(), "Cannot factor 0.");
 
-    let mut ret = vec![];
+    let mut primes = vec![];
 
-    for i in 2..(isqrt(n) + 1) {
-        let mut e = 0;  // will be maximal e such that i^e divides current n
-        while n % i == 0 {
-            // i must be prime, since all powers of all smaller primes were
-            // divided out of n in previous iterations
-            e += 1;
-            n /= i;
+    for &p in SMALL_PRIMES.iter() {
+        let p = T::from(p);
+        while (n.clone() % p.clone()).is_zero() {
+            n = n / p.clone();
+            primes.push(p.clone());
         }
-        if e > 0 {
-            ret.push((i, e));
+    }
+
+    let mut composites = vec![];
+    if n > T::one() {
+        composites.push(n);
+    }
+    while let Some(m) = composites.pop() {
+        if is_prime(m.clone()) {
+            primes.push(m);
+        } else if let Some((root, k)) = perfect_power(&m) {
+            composites.extend(std::iter::repeat_n(root, k as usize));
+        } else {
+            let d = pollard_brent(&m);
+            composites.push(m / d.clone());
+            composites.push(d);
         }
     }
 
-    if n > 1 {
-        // n is prime
-        ret.push((n, 1));
+    primes.sort();
+
+    let mut ret: Vec<(T, u32)> = vec![];
+    for p in primes.into_iter() {
+        match ret.last_mut() {
+            Some((q, e)) if *q == p => *e += 1,
+            _ => ret.push((p, 1)),
+        }
     }
 
     ret
@@ -119,88 +981,384 @@ pub fn prime_factorize(mut n: u128) -> Vec<(u128, u32)> {
 ///
 /// This algorithm depends on Euler's product formula:
 ///     phi(n) = n \prod_{p|n} (1 - 1/p)
-/// To avoid floating point arithmetic,
-pub fn euler_totient(mut n: u128) -> u128 {
-    let prime_factors = prime_factorize(n);
+/// To avoid floating point arithmetic, each factor is applied as
+/// phi -= phi / p, which is exact since p divides phi at every step.
+pub fn euler_totient<T: UnsignedInteger>(n: T) -> T {
+    let prime_factors = prime_factorize(n.clone());
     let mut phi = n;
 
-    for &(p, e) in prime_factors.iter() {
-        n /= p.pow(e);
-        phi -= phi / p;
+    for (p, _) in prime_factors.into_iter() {
+        phi = phi.clone() - phi / p;
     }
 
     phi
 }
 
+/// Carmichael's function lambda(n), the exponent of the unit group
+/// (Z/nZ)^*: the least m > 0 with a^m = 1 (mod n) for every a coprime to n.
+///
+/// lambda(p^k) = phi(p^k) = p^{k-1}(p - 1) for odd primes p, as the units
+/// mod p^k form a cyclic group, and the same holds for 2 and 4. For k >= 3,
+/// (Z/2^kZ)^* is the product of cyclic groups of orders 2 and 2^{k-2}, so
+/// lambda(2^k) = 2^{k-2}. By the Chinese remainder theorem, lambda of a
+/// product of coprime factors is the lcm of their lambdas; it always
+/// divides phi(n).
+pub fn carmichael_lambda<T: UnsignedInteger>(n: T) -> T {
+    let mut lambda = T::one();
+
+    for (p, k) in prime_factorize(n).into_iter() {
+        let mut prime_power_lambda = p.clone() - T::one();
+        for _ in 1..k {
+            prime_power_lambda = prime_power_lambda * p.clone();
+        }
+        if p == T::from(2) && k >= 3 {
+            prime_power_lambda = prime_power_lambda >> 1;
+        }
+        lambda = lambda.clone() / gcd(lambda.clone(), prime_power_lambda.clone()) * prime_power_lambda;
+    }
+
+    lambda
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::bigint::{BigInt, BigUint};
 
     #[test]
     fn test_is_prime() {
-        assert_eq!(true, is_prime(2));
-        assert_eq!(true, is_prime(3));
-        assert_eq!(true, is_prime(17));
+        assert!(is_prime(2u128));
+        assert!(is_prime(3u128));
+        assert!(is_prime(17u128));
+
+        assert!(!is_prime(0u128));
+        assert!(!is_prime(1u128));
+        assert!(!is_prime(57u128));
+
+        // agrees with trial division on small inputs
+        for n in 0..20000u128 {
+            assert_eq!(is_prime_by_trial_division(n), is_prime(n), "{}", n);
+        }
+
+        // Mersenne primes and near-misses, on both sides of 2^64
+        assert!(is_prime((1u128 << 61) - 1));
+        assert!(is_prime((1u128 << 89) - 1));
+        assert!(is_prime((1u128 << 127) - 1));
+        assert!(is_prime(u128::MAX - 158));  // largest 128-bit prime
+        assert!(!is_prime((1u128 << 67) - 1));
+        assert!(!is_prime(((1u128 << 61) - 1) * ((1u128 << 31) - 1)));
+        assert!(!is_prime(u128::MAX));
+
+        // strong pseudoprimes to many small bases
+        assert!(!is_prime(3215031751u128));
+        assert!(!is_prime(3825123056546413051u128));
+        assert!(!is_prime(318665857834031151167461u128));
+
+        // 2^521 - 1 is prime, 2^523 - 1 is not
+        let one = BigUint::from(1u64);
+        assert!(is_prime((one.clone() << 521) - one.clone()));
+        assert!(!is_prime((one.clone() << 523) - one));
+    }
 
-        assert_eq!(false, is_prime(0));
-        assert_eq!(false, is_prime(1));
-        assert_eq!(false, is_prime(57));
+    #[test]
+    fn test_miller_rabin() {
+        // 2047 = 23 * 89 is the smallest strong pseudoprime to base 2
+        assert!(is_strong_probable_prime(&2047u128, &2));
+        assert!(!is_strong_probable_prime(&2047u128, &3));
+
+        // 318665857834031151167461 fools the first 12 primes, but not 41
+        let n = 318665857834031151167461u128;
+        assert!(miller_rabin_with_witnesses(n, &WITNESSES_SMALL_PRIMES[..12]));
+        assert!(!miller_rabin_with_witnesses(n, &WITNESSES_SMALL_PRIMES));
+
+        assert!(miller_rabin((1u128 << 127) - 1, 20));
+        assert!(!miller_rabin(561u128, 20));  // Carmichael number
+        assert!(!miller_rabin(41041u128, 20));
+        assert!(miller_rabin(2u128, 20));
+        assert!(miller_rabin(3u128, 20));
+        assert!(!miller_rabin(1u128, 20));
+    }
+
+    #[test]
+    fn test_baillie_psw() {
+        // strong Lucas pseudoprimes are caught by the base-2 test
+        for n in [5459u128, 5777, 10877] {
+            assert!(is_strong_lucas_probable_prime(&n));
+            assert!(!baillie_psw(n));
+        }
+
+        // strong base-2 pseudoprimes are caught by the Lucas test
+        for n in [2047u128, 3215031751, 3825123056546413051] {
+            assert!(!is_strong_lucas_probable_prime(&n));
+            assert!(!baillie_psw(n));
+        }
+
+        for n in 0..5000u128 {
+            assert_eq!(is_prime_by_trial_division(n), baillie_psw(n), "{}", n);
+        }
+        assert!(baillie_psw(u128::MAX - 158));
+        assert!(!baillie_psw(10403u128 * 10403));  // perfect square
+    }
+
+    #[test]
+    fn test_jacobi_symbol() {
+        // format: (a, n, (a/n))
+        let test_cases: Vec<(u128, u128, i8)> = vec![
+            (1, 1, 1),
+            (0, 3, 0),
+            (2, 7, 1),
+            (3, 7, -1),
+            (5, 21, 1),
+            (6, 21, 0),
+            (1001, 9907, -1),
+            (19, 45, 1),
+        ];
+
+        for &(a, n, j) in test_cases.iter() {
+            assert_eq!(j, jacobi_symbol(a, n));
+        }
     }
 
     #[test]
     fn test_gcd() {
-        assert_eq!(2, gcd(2, 6));
-        assert_eq!(2, gcd(6, 2));
-        assert_eq!(1, gcd(3, 2));
-        assert_eq!(3, gcd(24, 15));
-        assert_eq!(3, gcd(15, 24));
-        assert_eq!(7, gcd(7, 7));
-        assert_eq!(9, gcd(9, 27));
+        assert_eq!(2, gcd(2u128, 6));
+        assert_eq!(2, gcd(6u128, 2));
+        assert_eq!(1, gcd(3u128, 2));
+        assert_eq!(3, gcd(24u128, 15));
+        assert_eq!(3, gcd(15u128, 24));
+        assert_eq!(7, gcd(7u128, 7));
+        assert_eq!(9, gcd(9u128, 27));
     }
 
     #[test]
     fn test_gcd_with_coefficients() {
-        assert_eq!((17, -31, 13), gcd_with_coefficients(527, 1258));
-        assert_eq!((12, -37, 8), gcd_with_coefficients(228, 1056));
-        assert_eq!((7, 4517, -4430), gcd_with_coefficients(163961, 167181));
-        assert_eq!((1, 59789, -970295), gcd_with_coefficients(3892394, 239847));
+        assert_eq!((17, -31, 13), gcd_with_coefficients(527u128, 1258));
+        assert_eq!((12, -37, 8), gcd_with_coefficients(228u128, 1056));
+        assert_eq!((7, 4517, -4430), gcd_with_coefficients(163961u128, 167181));
+        assert_eq!((1, 59789, -970295), gcd_with_coefficients(3892394u128, 239847));
+    }
+
+    #[test]
+    fn test_to_signed() {
+        assert_eq!(i128::MAX, (i128::MAX as u128).to_signed());
+        assert_eq!(Some(i128::MAX as u128), u128::signed_max());
+        assert_eq!(None, BigUint::signed_max());
+        assert_eq!(BigInt::from(5i64), BigUint::from(5u64).to_signed());
+    }
+
+    #[test]
+    #[should_panic(expected = "does not fit in an i128")]
+    fn test_to_signed_overflow() {
+        (1u128 << 127).to_signed();
+    }
+
+    #[test]
+    fn test_widening_mul() {
+        assert_eq!((0, 6), widening_mul(2, 3));
+        assert_eq!((1, 0), widening_mul(1 << 64, 1 << 64));
+        assert_eq!((u128::MAX - 1, 1), widening_mul(u128::MAX, u128::MAX));
+    }
+
+    #[test]
+    fn test_mod_inverse() {
+        assert_eq!(Some(4), mod_inverse(2u128, 7));
+        assert_eq!(Some(5), mod_inverse(5u128, 6));
+        assert_eq!(None, mod_inverse(4u128, 6));
+        assert_eq!(Some(0), mod_inverse(3u128, 1));
+        assert_eq!(Some(u128::MAX - 1), mod_inverse(u128::MAX - 1, u128::MAX));
+    }
+
+    #[test]
+    fn test_chinese_remainder() {
+        assert_eq!(8, chinese_remainder(&2u128, &3, &3, &5));
+        assert_eq!(23, chinese_remainder(&23u128, &105, &0, &1));
+        let (m1, m2) = (u64::MAX as u128, (1u128 << 61) - 1);
+        let x = chinese_remainder(&12345u128, &m1, &67890, &m2);
+        assert_eq!((12345, 67890), (x % m1, x % m2));
+
+        let x = chinese_remainder(&BigUint::from(5u64), &BigUint::from(7u64), &BigUint::from(1u64), &BigUint::from(4u64));
+        assert_eq!(BigUint::from(5u64), x);
     }
 
     #[test]
     fn test_isqrt() {
-        assert_eq!(0, isqrt(0));
-        assert_eq!(1, isqrt(1));
-        assert_eq!(1, isqrt(2));
-        assert_eq!(3, isqrt(9));
-        assert_eq!(485725, isqrt(235928775625));
+        assert_eq!(0, isqrt(0u128));
+        assert_eq!(1, isqrt(1u128));
+        assert_eq!(1, isqrt(2u128));
+        assert_eq!(3, isqrt(9u128));
+        assert_eq!(485725, isqrt(235928775625u128));
+    }
+
+    #[test]
+    fn test_integer_root() {
+        assert_eq!(0, integer_root(&0u128, 3));
+        assert_eq!(1, integer_root(&7u128, 3));
+        assert_eq!(2, integer_root(&8u128, 3));
+        assert_eq!(12345, integer_root(&12345u128, 1));
+        assert_eq!(u64::MAX as u128, integer_root(&u128::MAX, 2));
+        assert_eq!(1 << 42, integer_root(&(1u128 << 126), 3));
+        assert_eq!(6981463658331, integer_root(&u128::MAX, 3));
+        assert_eq!(1, integer_root(&u128::MAX, 200));
+
+        let n = BigUint::from(10u64).pow(60) - BigUint::from(1u64);
+        assert_eq!(BigUint::from(999999999999u64), integer_root(&n, 5));
+    }
+
+    #[test]
+    fn test_perfect_power() {
+        assert_eq!(None, perfect_power(&1u128));
+        assert_eq!(None, perfect_power(&2u128));
+        assert_eq!(None, perfect_power(&12u128));
+        assert_eq!(Some((2, 10)), perfect_power(&1024u128));
+        assert_eq!(Some((6, 2)), perfect_power(&36u128));
+        assert_eq!(Some((3, 6)), perfect_power(&729u128));
+        assert_eq!(Some((10, 38)), perfect_power(&10u128.pow(38)));
+
+        let p = 4398046511093u128;
+        assert_eq!(Some((p, 3)), perfect_power(&(p * p * p)));
+        assert_eq!(None, perfect_power(&(p * p * (p + 2))));
     }
 
     #[test]
     fn test_prime_factorize() {
-        assert_eq!(Vec::<(u128, u32)>::new(), prime_factorize(1));
-        assert_eq!(vec![(2, 1)], prime_factorize(2));
-        assert_eq!(vec![(2, 2)], prime_factorize(4));
-        assert_eq!(vec![(2, 7)], prime_factorize(128));
-        assert_eq!(vec![(2, 2), (3, 1)], prime_factorize(12));
-        assert_eq!(vec![(7, 1), (13, 1)], prime_factorize(91));
-        assert_eq!(vec![(97, 1)], prime_factorize(97));
+        assert_eq!(Vec::<(u128, u32)>::new(), prime_factorize(1u128));
+        assert_eq!(vec![(2, 1)], prime_factorize(2u128));
+        assert_eq!(vec![(2, 2)], prime_factorize(4u128));
+        assert_eq!(vec![(2, 7)], prime_factorize(128u128));
+        assert_eq!(vec![(2, 2), (3, 1)], prime_factorize(12u128));
+        assert_eq!(vec![(7, 1), (13, 1)], prime_factorize(91u128));
+        assert_eq!(vec![(97, 1)], prime_factorize(97u128));
+        assert_eq!(vec![(101, 2), (103, 1)], prime_factorize(101u128 * 101 * 103));
+
+        assert_eq!(
+            vec![(3, 1), (5, 1), (17, 1), (257, 1), (641, 1), (65537, 1),
+                 (274177, 1), (6700417, 1), (67280421310721, 1)],
+            prime_factorize(u128::MAX));
+        assert_eq!(
+            vec![(4294967291, 1), (4294967311, 1)],
+            prime_factorize(4294967291u128 * 4294967311));
+        // three 40-bit primes, beyond the reach of the cheap early stages
+        assert_eq!(
+            vec![(1099511627689, 1), (2199023255531, 1), (4398046511093, 1)],
+            prime_factorize(1099511627689u128 * 2199023255531 * 4398046511093));
+        assert_eq!(
+            vec![(1000003, 3), ((1 << 61) - 1, 1)],
+            prime_factorize(1000003u128.pow(3) * ((1 << 61) - 1)));
+        // above (2^64 - 1)^2, where Fermat's method used to overflow
+        assert_eq!(
+            vec![(1009, 1), (337247142637203630786297926096895947, 1)],
+            prime_factorize(1009 * 337247142637203630786297926096895947u128));
+        // a 126-bit prime cube, which the quadratic sieve can't split
+        let p = 4398046511093u128;
+        assert_eq!(vec![(p, 3)], prime_factorize(p * p * p));
+    }
+
+    #[test]
+    fn test_sqrt_mod_prime() {
+        for p in [2u128, 3, 5, 7, 13, 17, 41, 97, 257, 65537, (1 << 61) - 1, u128::MAX - 158] {
+            for a in [0u128, 1, 2, 3, 4, 5, 10, 12345] {
+                let a = a % p;
+                match sqrt_mod_prime(&a, &p) {
+                    Some(r) => assert_eq!(a, r.mul_mod(&r, &p), "sqrt({}) mod {}", a, p),
+                    None => assert_eq!(-1, jacobi_symbol(a, p)),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_primes_up_to() {
+        assert_eq!(Vec::<u64>::new(), primes_up_to(1));
+        assert_eq!(vec![2], primes_up_to(2));
+        assert_eq!(vec![2, 3, 5, 7, 11, 13, 17, 19], primes_up_to(20));
+        assert_eq!(1229, primes_up_to(10000).len());
+    }
+
+    #[test]
+    fn test_pollard_brent() {
+        for n in [91u128, 8051, 10403, 455459, 1000003 * 1000033] {
+            let d = pollard_brent(&n);
+            assert!(d > 1 && d < n && n % d == 0, "{} is not a factor of {}", d, n);
+        }
+        assert_eq!(2, pollard_brent(&(1u128 << 100)));
     }
 
     #[test]
     fn test_euler_totient() {
         // if p is prime, then phi(p) = p - 1
-        assert_eq!(1, euler_totient(2));
-        assert_eq!(2, euler_totient(3));
-        assert_eq!(52, euler_totient(53));
-        assert_eq!(96, euler_totient(97));
+        assert_eq!(1, euler_totient(2u128));
+        assert_eq!(2, euler_totient(3u128));
+        assert_eq!(52, euler_totient(53u128));
+        assert_eq!(96, euler_totient(97u128));
 
         // if p is prime, then phi(p^k) = p^k - p^{k-1}
-        assert_eq!(6, euler_totient(9));
-        assert_eq!(18, euler_totient(27));
-        assert_eq!(54, euler_totient(81));
+        assert_eq!(6, euler_totient(9u128));
+        assert_eq!(18, euler_totient(27u128));
+        assert_eq!(54, euler_totient(81u128));
 
         // if m and n are relatively prime, then phi(mn) = phi(m)phi(n)
-        assert_eq!(4, euler_totient(12));
-        assert_eq!(40, euler_totient(100));
+        assert_eq!(4, euler_totient(12u128));
+        assert_eq!(40, euler_totient(100u128));
+        assert_eq!(
+            (4294967291 - 1) * (4294967311 - 1),
+            euler_totient(4294967291u128 * 4294967311));
+    }
+
+    #[test]
+    fn test_carmichael_lambda() {
+        assert_eq!(1, carmichael_lambda(1u128));
+        assert_eq!(1, carmichael_lambda(2u128));
+        assert_eq!(2, carmichael_lambda(4u128));
+        assert_eq!(2, carmichael_lambda(8u128));
+        assert_eq!(1 << 62, carmichael_lambda(1u128 << 64));
+        assert_eq!(96, carmichael_lambda(97u128));
+        assert_eq!(54, carmichael_lambda(81u128));
+
+        // 561 = 3 * 11 * 17 is a Carmichael number: lambda(n) divides n - 1
+        assert_eq!(80, carmichael_lambda(561u128));
+        assert_eq!(780, carmichael_lambda(61u128 * 53));
+        // gcd(p - 1, q - 1) = 10
+        assert_eq!(
+            (4294967291 - 1) * (4294967311 - 1) / 10,
+            carmichael_lambda(4294967291u128 * 4294967311));
+
+        // agrees with the definition on small inputs
+        for n in 1..200u128 {
+            let units: Vec<u128> = (1..=n).filter(|&a| gcd(a, n) == 1).collect();
+            let lambda = (1..=n)
+                .find(|&m| units.iter().all(|a| pow_mod(a, &m, &n) == 1 % n))
+                .unwrap();
+            assert_eq!(lambda, carmichael_lambda(n), "{}", n);
+            assert!(euler_totient(n).is_multiple_of(lambda));
+        }
+
+        let m89 = (BigUint::from(1u64) << 89) - BigUint::from(1u64);
+        assert_eq!(
+            m89.clone() - BigUint::from(1u64),
+            carmichael_lambda(m89.clone() * BigUint::from(4u64)));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_big_integers() {
+        // 2^127 - 1 and 2^89 - 1 are Mersenne primes
+        let m127 = (BigUint::from(1u64) << 127) - BigUint::from(1u64);
+        let m89 = (BigUint::from(1u64) << 89) - BigUint::from(1u64);
+        let product = m127.clone() * m89.clone();
+
+        assert_eq!(m89, gcd(product.clone(), m89.clone() * BigUint::from(6u64)));
+        assert_eq!(m127, isqrt(m127.clone() * m127.clone()));
+        assert_eq!(m127, isqrt(m127.clone() * m127.clone() + m127.clone()));
+        assert!(is_prime(BigUint::from(1000003u64)));
+        assert!(!is_prime(BigUint::from(1000001u64)));
+
+        let (g, u, v) = gcd_with_coefficients(product.clone(), m127.clone() + BigUint::from(2u64));
+        assert_eq!(BigUint::from(1u64), g);
+        assert_eq!(
+            BigInt::from(1i64),
+            u * BigInt::from(product) + v * BigInt::from(m127 + BigUint::from(2u64)));
+
+        assert_eq!(
+            vec![(BigUint::from(2u64), 3), (BigUint::from(3u64), 1), (BigUint::from(10007u64), 2)],
+            prime_factorize(BigUint::from(8 * 3 * 10007 * 10007u64)));
+    }
+}
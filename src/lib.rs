@@ -1,5 +1,24 @@
+pub mod aead;
+pub mod aes;
+pub mod bigint;
+pub mod blum_blum_shub;
+pub mod chacha20;
+pub mod dhies;
+pub mod discrete_log;
 pub mod diffie_hellman;
+pub mod drbg;
+pub mod ecm;
 pub mod elgamal;
+pub mod factor;
+pub mod hash;
+pub mod hmac;
+pub mod index_calculus;
 pub mod integer;
 pub mod modular;
+pub mod primes;
+pub mod quadratic_sieve;
 pub mod rsa;
+pub mod unit_group;
+
+#[cfg(test)]
+mod test_util;
@@ -1,13 +1,9 @@
-mod diffie_hellman;
-mod elgamal;
-mod integer;
-mod modular;
-mod rsa;
+use bad_roll::{integer, modular};
 
 fn main() {
-    println!("is 91 prime? {}", integer::is_prime(91));
+    println!("is 91 prime? {}", integer::is_prime(91u128));
 
-    let modulus = 4;
+    let modulus = 4u128;
     let val1 = 3;
     let val2 = 2;
     let res1 = modular::Residue::from_unsigned_integer(val1, modulus);
@@ -21,14 +17,14 @@ fn main() {
     println!("-{} = {:?}", val1, res1.neg());
     assert_eq!(res1.scalar_times(-1), res1.neg());
 
-    println!("(527, 1258) -> {:?}", integer::gcd_with_coefficients(527, 1258));
-    println!("(228, 1056) -> {:?}", integer::gcd_with_coefficients(228, 1056));
+    println!("(527, 1258) -> {:?}", integer::gcd_with_coefficients(527u128, 1258));
+    println!("(228, 1056) -> {:?}", integer::gcd_with_coefficients(228u128, 1056));
     println!(
         "(163961, 167181) -> {:?}",
-        integer::gcd_with_coefficients(163961, 167181));
+        integer::gcd_with_coefficients(163961u128, 167181));
     println!(
         "(3892394, 239847) -> {:?}",
-        integer::gcd_with_coefficients(3892394, 239847));
+        integer::gcd_with_coefficients(3892394u128, 239847));
 
     for i in 1..11 {
         let res = modular::Residue::from_signed_integer(i, 11u128);
@@ -1,26 +1,31 @@
-use rand::Rng;
-use crate::integer::is_prime;
+use rand::{CryptoRng, RngCore};
 
-use super::integer::{gcd_with_coefficients, euler_totient, prime_factorize};
+use super::integer::{
+    carmichael_lambda, euler_totient, gcd, mod_inverse, pow_mod,
+    prime_factorize, to_be_bytes, SignedInteger, UnsignedInteger,
+};
 
 /// Represents a residue modulo n
-#[derive(Clone, Debug, PartialEq, Eq)]
-pub struct Residue {
-    pub value: u128,    // value in 0, 1, ..., n-1
-    pub modulus: u128,  // the modulus
+///
+/// Generic over the underlying integer type, which defaults to `u128`; use
+/// `Residue<BigUint>` for moduli that do not fit in 128 bits.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Residue<T = u128> {
+    pub value: T,    // value in 0, 1, ..., n-1
+    pub modulus: T,  // the modulus
 }
 
-impl Residue {
-    pub fn from_unsigned_integer(n: u128, modulus: u128) -> Residue {
+impl<T: UnsignedInteger> Residue<T> {
+    pub fn from_unsigned_integer(n: T, modulus: T) -> Residue<T> {
         Residue {
-            value: n.rem_euclid(modulus),
+            value: n % modulus.clone(),
             modulus
         }
     }
 
-    pub fn from_signed_integer(n: i128, modulus: u128) -> Residue {
+    pub fn from_signed_integer(n: T::Signed, modulus: T) -> Residue<T> {
         Residue {
-            value: n.rem_euclid(modulus as i128) as u128,
+            value: n.modulo(&modulus),
             modulus
         }
     }
@@ -32,154 +37,324 @@ impl Residue {
             self.value, self.modulus);
     }
 
-    pub fn plus(&self, other: &Residue) -> Residue {
+    pub fn plus(&self, other: &Residue<T>) -> Residue<T> {
         self.assert_valid();
         other.assert_valid();
         assert_eq!(self.modulus, other.modulus);
 
-        Residue::from_unsigned_integer(self.value + other.value, self.modulus)
+        Residue {
+            value: self.value.add_mod(&other.value, &self.modulus),
+            modulus: self.modulus.clone()
+        }
     }
 
-    pub fn times(&self, other: &Residue) -> Residue {
+    pub fn minus(&self, other: &Residue<T>) -> Residue<T> {
         self.assert_valid();
         other.assert_valid();
         assert_eq!(self.modulus, other.modulus);
 
-        Residue::from_unsigned_integer(self.value * other.value, self.modulus)
+        Residue {
+            value: self.value.sub_mod(&other.value, &self.modulus),
+            modulus: self.modulus.clone()
+        }
     }
 
-    pub fn scalar_times(&self, scalar: i128) -> Residue {
+    pub fn times(&self, other: &Residue<T>) -> Residue<T> {
         self.assert_valid();
+        other.assert_valid();
+        assert_eq!(self.modulus, other.modulus);
 
-        Residue::from_signed_integer(scalar * self.value as i128, self.modulus)
+        Residue {
+            value: self.value.mul_mod(&other.value, &self.modulus),
+            modulus: self.modulus.clone()
+        }
     }
 
-    pub fn neg(&self) -> Residue {
+    pub fn scalar_times(&self, scalar: T::Signed) -> Residue<T> {
         self.assert_valid();
 
-        self.scalar_times(-1)
+        self.times(&Residue::from_signed_integer(scalar, self.modulus.clone()))
     }
 
-    pub fn inv(&self) -> Residue {
+    pub fn neg(&self) -> Residue<T> {
         self.assert_valid();
 
-        let (g, u, _) = gcd_with_coefficients(
-            self.value, self.modulus);
+        self.scalar_times(T::Signed::from(-1))
+    }
+
+    pub fn inv(&self) -> Residue<T> {
+        self.assert_valid();
 
-        if g == 1 {
-            return Residue::from_signed_integer(u, self.modulus);
-        } else {
-            panic!(
+        match mod_inverse(self.value.clone(), self.modulus.clone()) {
+            Some(u) => Residue { value: u, modulus: self.modulus.clone() },
+            None => panic!(
                 "Tried to invert non-unit {} (mod {})",
-                self.value, self.modulus);
+                self.value, self.modulus),
         }
     }
 
     /// Returns self raised to an integer power.
     ///
     /// This method uses a version of the "square then halve the exponent"
-    /// method for fast squaring. Requires O(sqrt(e)) time, O(1) space.
-    pub fn pow(&self, mut e: i128) -> Residue {
+    /// method for fast squaring. Requires O(log(e)) time, O(1) space.
+    pub fn pow(&self, e: T::Signed) -> Residue<T> {
         self.assert_valid();
 
-        if e < 0 {
+        if e.is_negative() {
             return self.inv().pow(-e);
         }
 
+        let mut e = e.unsigned_abs();
         let mut a = self.clone();
-        let mut b = Residue::from_unsigned_integer(1, self.modulus);
+        let mut b = Residue::from_unsigned_integer(T::one(), self.modulus.clone());
 
-        while e > 0 {
-            if e % 2 == 1 {
+        while !e.is_zero() {
+            if !e.is_even() {
                 b = b.times(&a);
             }
             a = a.times(&a);
-            e /= 2;
+            e = e >> 1;
         }
 
         b
     }
 
-    /// Returns a primitive root for the given modulus.
+    /// Returns self raised to an unsigned power, for exponents such as group
+    /// orders that may not fit in the signed type `pow` takes.
+    pub fn pow_unsigned(&self, e: &T) -> Residue<T> {
+        self.assert_valid();
+        Residue::from_unsigned_integer(pow_mod(&self.value, e, &self.modulus), self.modulus.clone())
+    }
+
+    /// Returns the value as a big-endian byte string, zero-padded to the
+    /// length of the largest residue n - 1, so that every residue mod n
+    /// encodes to the same number of bytes. This is the form to hash.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let largest = self.modulus.clone() - T::one();
+        let length = largest.bits().div_ceil(8) as usize;
+        to_be_bytes(&self.value, length).unwrap()
+    }
+
+    /// Returns the multiplicative order of self, the least k > 0 with
+    /// self^k = 1, or None if self is not a unit and so has no order.
+    ///
+    /// The order divides the exponent of the unit group, Carmichael's
+    /// lambda(n), so this starts from lambda(n) and divides out each of its
+    /// prime factors q for as long as self^{k/q} is still 1. Finding those
+    /// factors means factoring p - 1 for each prime p dividing n, which is
+    /// the expensive part.
+    pub fn order(&self) -> Option<T> {
+        self.assert_valid();
+        if gcd(self.value.clone(), self.modulus.clone()) != T::one() {
+            return None;
+        }
+
+        self.order_dividing(&carmichael_lambda(self.modulus.clone()))
+    }
+
+    /// Returns the multiplicative order of self given a multiple of it, such
+    /// as the order of a subgroup known to contain self, or None if
+    /// self^multiple != 1.
+    ///
+    /// This is `order` without the factoring of lambda(n): only the multiple
+    /// is factored, which is what makes it usable for large moduli whose
+    /// p - 1 can't be factored but whose subgroup orders are known.
+    pub fn order_dividing(&self, multiple: &T) -> Option<T> {
+        self.assert_valid();
+        let one = T::one() % self.modulus.clone();
+        if pow_mod(&self.value, multiple, &self.modulus) != one {
+            return None;
+        }
+
+        let mut order = multiple.clone();
+        for (q, _) in prime_factorize(multiple.clone()) {
+            while (order.clone() % q.clone()).is_zero()
+                    && pow_mod(&self.value, &(order.clone() / q.clone()), &self.modulus) == one {
+                order = order / q.clone();
+            }
+        }
+
+        Some(order)
+    }
+
+    /// Returns whether self generates the subgroup of order q, i.e. whether
+    /// its order is exactly q.
+    ///
+    /// For a prime q this is just self != 1 and self^q = 1, the check a
+    /// Diffie-Hellman or ElGamal public value in a prime-order subgroup
+    /// should pass before it is used: anything else lies in a small subgroup
+    /// that leaks the private exponent modulo its order. Otherwise q is
+    /// factored, and self^{q/r} != 1 is checked for each prime r dividing
+    /// it.
+    pub fn is_generator_of_subgroup(&self, q: &T) -> bool {
+        self.assert_valid();
+        let one = T::one() % self.modulus.clone();
+        if q.is_zero() || pow_mod(&self.value, q, &self.modulus) != one {
+            return false;
+        }
+
+        prime_factorize(q.clone())
+            .into_iter()
+            .all(|(r, _)| pow_mod(&self.value, &(q.clone() / r), &self.modulus) != one)
+    }
+
+    /// Returns whether (Z/nZ)^* is cyclic, i.e. whether n has a primitive
+    /// root: exactly when n = 1, 2, 4, p^k or 2p^k for an odd prime p.
+    ///
+    /// For any other n, the unit group splits into two factors whose orders
+    /// are both even (two odd prime powers, or a factor of 4 and an odd prime
+    /// power, or 2^k with k >= 3), so every element has order at most half
+    /// of phi(n).
+    pub fn has_primitive_root(modulus: &T) -> bool {
+        let four = T::from(4);
+        if *modulus <= four {
+            return !modulus.is_zero();
+        }
+
+        let factors = prime_factorize(modulus.clone());
+        match factors.as_slice() {
+            [(p, _)] => *p != T::from(2),
+            [(two, 1), (_, _)] => *two == T::from(2),
+            _ => false,
+        }
+    }
+
+    /// Returns a random primitive root for the given modulus, a generator of
+    /// the whole unit group, or None if there is none.
     ///
     /// Uses the method of guessing random integers between 1 and n-1 and
     /// testing each as follows:
-    ///     x is primitive if and only if x^{phi(n)/p} != 1 (mod n)
+    ///     x is primitive if and only if x is a unit and
+    ///     x^{phi(n)/p} != 1 (mod n)
     ///     for all prime factors p of phi(n), where phi(n) is Euler's
     ///     totient function.
     ///
-    /// Since n / phi(n-1) is O(log(log(n))), it should not take too many
-    /// guesses in order to find a primitive root.
+    /// The phi(phi(n)) primitive roots make up a fraction of at least about
+    /// 1 / log(log(n)) of the candidates, so it should not take too many
+    /// guesses in order to find one.
     ///
-    /// Warning: currently only works for n prime. In general, primitive roots
-    /// exist if and only if
+    /// Primitive roots exist if and only if
     ///     n = 1, 2, 4, p^k, or 2p^k,
     /// where p is an odd prime and k is a positive integer.
-    /// TODO: handle other cases
-    pub fn primitive_root(modulus: u128) -> Residue {
-        if !is_prime(modulus) {
-            panic!("Residue::primitive_root() only supports integer moduli.")
+    pub fn primitive_root(modulus: T) -> Option<Residue<T>> {
+        Residue::primitive_root_with_rng(&mut rand::thread_rng(), modulus)
+    }
+
+    /// `primitive_root` drawing its candidates from rng.
+    pub fn primitive_root_with_rng<R: RngCore + CryptoRng + ?Sized>(
+            rng: &mut R, modulus: T) -> Option<Residue<T>> {
+        if !Residue::has_primitive_root(&modulus) {
+            return None;
+        }
+        // the unit group mod 1 is {0}, and mod 2 it is {1}
+        if modulus <= T::from(2) {
+            return Some(Residue::from_unsigned_integer(T::one(), modulus));
         }
 
-        let mut rng = rand::thread_rng();
-        let phi = euler_totient(modulus);
-        let primes: Vec<u128> = prime_factorize(phi)
-            .iter()
-            .map(|&(p, _)| p)
+        let phi = euler_totient(modulus.clone());
+        let primes: Vec<T> = prime_factorize(phi.clone())
+            .into_iter()
+            .map(|(p, _)| p)
             .collect();
-        let one = Residue::from_unsigned_integer(1, modulus);
 
-        'outer: loop {
+        loop {
             let n = Residue::from_unsigned_integer(
-                rng.gen_range(1..modulus), modulus);
-            for &p in primes.iter() {
-                if n.pow((phi / p) as i128) == one { continue 'outer; }
+                T::random_below(rng, &(modulus.clone() - T::one())) + T::one(),
+                modulus.clone());
+            if n.is_primitive_root(&phi, &primes) {
+                return Some(n);
             }
-            return n;
         }
     }
+
+    /// Returns every primitive root for the given modulus, or None if there
+    /// are none.
+    ///
+    /// If g is the smallest primitive root, the others are the g^k for k
+    /// coprime to phi(n), and they are produced in that order rather than
+    /// sorted; there are phi(phi(n)) of them.
+    pub fn primitive_roots(modulus: T) -> Option<impl Iterator<Item = Residue<T>>> {
+        if !Residue::has_primitive_root(&modulus) {
+            return None;
+        }
+
+        let phi = euler_totient(modulus.clone());
+        let primes: Vec<T> = prime_factorize(phi.clone())
+            .into_iter()
+            .map(|(p, _)| p)
+            .collect();
+        let mut candidate = Residue::from_unsigned_integer(T::one(), modulus.clone());
+        while !candidate.is_primitive_root(&phi, &primes) {
+            candidate.value = candidate.value + T::one();
+        }
+
+        let root = candidate;
+        let mut k = T::one();
+        Some(std::iter::from_fn(move || {
+            while k <= phi {
+                let e = k.clone();
+                k = k.clone() + T::one();
+                if gcd(e.clone(), phi.clone()) == T::one() {
+                    return Some(Residue::from_unsigned_integer(
+                        pow_mod(&root.value, &e, &root.modulus), root.modulus.clone()));
+                }
+            }
+            None
+        }))
+    }
+
+    /// Returns whether self is a unit with order phi, given phi = phi(n)
+    /// and its prime factors.
+    fn is_primitive_root(&self, phi: &T, primes: &[T]) -> bool {
+        let one = T::one() % self.modulus.clone();
+        gcd(self.value.clone(), self.modulus.clone()) == T::one()
+            && primes.iter().all(|p| pow_mod(&self.value, &(phi.clone() / p.clone()), &self.modulus) != one)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::integer::gcd;
+    use rand::Rng;
+    use crate::bigint::{BigInt, BigUint};
 
     #[test]
     fn test_from_unsigned_integer() {
         assert_eq!(
             Residue { value: 0, modulus: 5 },
-            Residue::from_unsigned_integer(0, 5));
+            Residue::from_unsigned_integer(0, 5u128));
         assert_eq!(
             Residue { value: 2, modulus: 5 },
-            Residue::from_unsigned_integer(2, 5));
+            Residue::from_unsigned_integer(2, 5u128));
         assert_eq!(
             Residue { value: 2, modulus: 5 },
-            Residue::from_unsigned_integer(7, 5));
+            Residue::from_unsigned_integer(7, 5u128));
         assert_eq!(
             Residue { value: 3, modulus: 5 },
-            Residue::from_unsigned_integer(10000000003, 5));
+            Residue::from_unsigned_integer(10000000003, 5u128));
     }
 
     #[test]
     fn test_from_signed_integer() {
         assert_eq!(
             Residue { value: 0, modulus: 5 },
-            Residue::from_signed_integer(0, 5));
+            Residue::from_signed_integer(0, 5u128));
         assert_eq!(
             Residue { value: 2, modulus: 5 },
-            Residue::from_signed_integer(2, 5));
+            Residue::from_signed_integer(2, 5u128));
         assert_eq!(
             Residue { value: 2, modulus: 5 },
-            Residue::from_signed_integer(7, 5));
+            Residue::from_signed_integer(7, 5u128));
         assert_eq!(
             Residue { value: 3, modulus: 5 },
-            Residue::from_signed_integer(-2, 5));
+            Residue::from_signed_integer(-2, 5u128));
         assert_eq!(
             Residue { value: 0, modulus: 5 },
-            Residue::from_signed_integer(-5, 5));
+            Residue::from_signed_integer(-5, 5u128));
         assert_eq!(
             Residue { value: 4, modulus: 5 },
-            Residue::from_signed_integer(99999999999, 5));
+            Residue::from_signed_integer(99999999999, 5u128));
     }
 
     #[test]
@@ -207,7 +382,27 @@ mod tests {
     #[should_panic]
     fn test_plus_panics_unequal_moduli () {
         Residue::from_unsigned_integer(2, 3).plus(
-            &Residue::from_unsigned_integer(2, 5));
+            &Residue::from_unsigned_integer(2, 5u128));
+    }
+
+    #[test]
+    fn test_minus() {
+        // format: (modulus, x_val, y_val, z_val) where x - y == z
+        let test_cases: Vec<(u128, u128, u128, u128)> = vec![
+            (7, 0, 0, 0),
+            (7, 5, 3, 2),
+            (7, 3, 5, 5),
+            (7, 0, 6, 1),
+            (6, 2, 4, 4),
+            (u128::MAX, 0, u128::MAX - 1, 1),
+        ];
+
+        for &(modulus, x_val, y_val, z_val) in test_cases.iter() {
+            assert_eq!(
+                Residue::from_unsigned_integer(z_val, modulus),
+                Residue::from_unsigned_integer(x_val, modulus)
+                    .minus(&Residue::from_unsigned_integer(y_val, modulus)));
+        }
     }
 
     #[test]
@@ -237,7 +432,7 @@ mod tests {
     #[should_panic]
     fn test_times_panics_unequal_moduli () {
         Residue::from_unsigned_integer(2, 3).times(
-            &Residue::from_unsigned_integer(2, 5));
+            &Residue::from_unsigned_integer(2, 5u128));
     }
 
     #[test]
@@ -333,34 +528,297 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_arithmetic_near_u128_max() {
+        let mut rng = rand::thread_rng();
+        let moduli = [
+            u128::MAX,
+            u128::MAX - 1,
+            u128::MAX - 158,  // 2^128 - 159, the largest 128-bit prime
+            1 << 127,
+            (1 << 127) + 1,
+            (1 << 64) + 1,
+            (1 << 64) - 1,
+            (1 << 64) - 59,
+            rng.gen_range((1 << 100)..u128::MAX),
+        ];
+
+        // compare against the arbitrary-precision integers as a reference
+        for &m in moduli.iter() {
+            let big_m = BigUint::from(m);
+            for _ in 0..200 {
+                let (x, y) = (rng.gen_range(0..m), rng.gen_range(0..m));
+                let (rx, ry) = (
+                    Residue::from_unsigned_integer(x, m),
+                    Residue::from_unsigned_integer(y, m));
+                let (bx, by) = (BigUint::from(x), BigUint::from(y));
+
+                assert_eq!(
+                    Some(rx.times(&ry).value),
+                    ((&bx * &by) % &big_m).to_u128());
+                assert_eq!(
+                    Some(rx.plus(&ry).value),
+                    ((&bx + &by) % &big_m).to_u128());
+                assert_eq!(0, rx.plus(&rx.neg()).value);
+            }
+        }
+
+        // extremes
+        let m = u128::MAX;
+        let minus_one = Residue::from_unsigned_integer(m - 1, m);
+        assert_eq!(1, minus_one.times(&minus_one).value);
+        assert_eq!(m - 2, minus_one.plus(&minus_one).value);
+        assert_eq!(minus_one, minus_one.scalar_times(-1).scalar_times(-1));
+        assert_eq!(1 << 127, minus_one.scalar_times(i128::MIN).value);
+        assert_eq!(minus_one, Residue::from_signed_integer(-1, m));
+    }
+
+    #[test]
+    fn test_inv_and_pow_near_u128_max() {
+        let mut rng = rand::thread_rng();
+        let p = u128::MAX - 158;
+        let one = Residue::from_unsigned_integer(1, p);
+
+        for _ in 0..20 {
+            let x = Residue::from_unsigned_integer(rng.gen_range(1..p), p);
+            assert_eq!(one, x.times(&x.inv()));
+            assert_eq!(x.inv(), x.pow(-1));
+            // Fermat's little theorem; p - 1 does not fit in an i128, so
+            // split the exponent
+            assert_eq!(one, x.pow(((p - 1) / 2) as i128).pow(2));
+        }
+
+        // 2^128 - 1 is composite, so only units have inverses
+        let m = u128::MAX;
+        for _ in 0..20 {
+            let x = Residue::from_unsigned_integer(rng.gen_range(1..m), m);
+            if gcd(x.value, m) == 1 {
+                assert_eq!(Residue::from_unsigned_integer(1, m), x.times(&x.inv()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_pow_big_modulus() {
+        // Fermat's little theorem for the 2048-bit RFC 3526 group 14 prime
+        let p: BigUint = concat!(
+            "0xFFFFFFFFFFFFFFFFC90FDAA22168C234C4C6628B80DC1CD1",
+            "29024E088A67CC74020BBEA63B139B22514A08798E3404DD",
+            "EF9519B3CD3A431B302B0A6DF25F14374FE1356D6D51C245",
+            "E485B576625E7EC6F44C42E9A637ED6B0BFF5CB6F406B7ED",
+            "EE386BFB5A899FA5AE9F24117C4B1FE649286651ECE45B3D",
+            "C2007CB8A163BF0598DA48361C55D39A69163FA8FD24CF5F",
+            "83655D23DCA3AD961C62F356208552BB9ED529077096966D",
+            "670C354E4ABC9804F1746C08CA18217C32905E462E36CE3B",
+            "E39E772C180E86039B2783A2EC07A28FB5C55DF06F4C52C9",
+            "DE2BCBF6955817183995497CEA956AE515D2261898FA0510",
+            "15728E5A8AACAA68FFFFFFFFFFFFFFFF").parse().unwrap();
+        let one = Residue::from_unsigned_integer(BigUint::from(1u64), p.clone());
+        let x = Residue::from_unsigned_integer(BigUint::from(3u64), p.clone());
+
+        assert_eq!(one, x.pow(BigInt::from(p.clone() - BigUint::from(1u64))));
+        assert_eq!(one, x.times(&x.inv()));
+        assert_eq!(x.inv(), x.pow(BigInt::from(-1i64)));
+    }
+
+    #[test]
+    fn test_pow_unsigned() {
+        let x = Residue::from_unsigned_integer(5u128, 7);
+        for e in 0..20u128 {
+            assert_eq!(x.pow(e as i128), x.pow_unsigned(&e));
+        }
+
+        // exponents above i128::MAX, which `pow` can't take for u128
+        let m = u128::MAX - 158;
+        let x = Residue::from_unsigned_integer(3u128, m);
+        assert_eq!(1, x.pow_unsigned(&(m - 1)).value);
+        assert_eq!(x, x.pow_unsigned(&m));
+    }
+
+    #[test]
+    fn test_to_bytes() {
+        assert_eq!(vec![0x00, 0x2a], Residue::from_unsigned_integer(42u128, 65521).to_bytes());
+        assert_eq!(vec![0x01], Residue::from_unsigned_integer(1u128, 256).to_bytes());
+        let mut expected = vec![0xff; 15];
+        expected.push(0xfe);
+        assert_eq!(expected, Residue::from_unsigned_integer(u128::MAX - 1, u128::MAX).to_bytes());
+
+        let big = Residue::from_unsigned_integer(BigUint::from(5u64), BigUint::from(1u64) << 64);
+        assert_eq!(vec![0, 0, 0, 0, 0, 0, 0, 5], big.to_bytes());
+    }
+
+    #[test]
+    fn test_order() {
+        // 2 is a primitive root mod 1019 = 2 * 509 + 1
+        let order = |value, modulus| Residue::from_unsigned_integer(value, modulus).order();
+        assert_eq!(Some(1018), order(2u128, 1019));
+        assert_eq!(Some(509), order(4u128, 1019));
+        assert_eq!(Some(2), order(1018u128, 1019));
+        assert_eq!(Some(1), order(1u128, 1019));
+
+        // mod 15 the unit group is Z/2 x Z/4, so no unit has order 8
+        assert_eq!(Some(4), order(2u128, 15));
+        assert_eq!(Some(2), order(4u128, 15));
+        assert_eq!(None, order(3u128, 15));
+        assert_eq!(None, order(0u128, 15));
+        for a in 1..15u128 {
+            if let Some(k) = order(a, 15) {
+                assert_eq!(1, Residue::from_unsigned_integer(a, 15).pow(k as i128).value);
+                assert!(4u128.is_multiple_of(k));
+            }
+        }
+
+        // mod 2^k, 5 has the largest possible order 2^{k-2}
+        assert_eq!(Some(1 << 125), order(5u128, 1 << 127));
+        assert_eq!(Some(1), order(0u128, 1));
+
+        let p = (BigUint::from(1u64) << 89) - BigUint::from(1u64);
+        let minus_one = Residue::from_unsigned_integer(p.clone() - BigUint::from(1u64), p);
+        assert_eq!(Some(BigUint::from(2u64)), minus_one.order());
+    }
+
+    #[test]
+    fn test_order_dividing() {
+        let x = Residue::from_unsigned_integer(4u128, 1019);
+        assert_eq!(Some(509), x.order_dividing(&1018));
+        assert_eq!(Some(509), x.order_dividing(&509));
+        assert_eq!(None, x.order_dividing(&2));
+
+        // the order of 4 in the 2048-bit RFC 3526 group 14, where p - 1 is
+        // far too big to factor but (p - 1) / 2 is prime
+        let p: BigUint = concat!(
+            "0xFFFFFFFFFFFFFFFFC90FDAA22168C234C4C6628B80DC1CD1",
+            "29024E088A67CC74020BBEA63B139B22514A08798E3404DD",
+            "EF9519B3CD3A431B302B0A6DF25F14374FE1356D6D51C245",
+            "E485B576625E7EC6F44C42E9A637ED6B0BFF5CB6F406B7ED",
+            "EE386BFB5A899FA5AE9F24117C4B1FE649286651ECE45B3D",
+            "C2007CB8A163BF0598DA48361C55D39A69163FA8FD24CF5F",
+            "83655D23DCA3AD961C62F356208552BB9ED529077096966D",
+            "670C354E4ABC9804F1746C08CA18217C32905E462E36CE3B",
+            "E39E772C180E86039B2783A2EC07A28FB5C55DF06F4C52C9",
+            "DE2BCBF6955817183995497CEA956AE515D2261898FA0510",
+            "15728E5A8AACAA68FFFFFFFFFFFFFFFF").parse().unwrap();
+        let q = (p.clone() - BigUint::from(1u64)) >> 1;
+        let x = Residue::from_unsigned_integer(BigUint::from(4u64), p);
+        assert_eq!(Some(q.clone()), x.order_dividing(&q));
+    }
+
+    #[test]
+    fn test_is_generator_of_subgroup() {
+        // 4 generates the squares mod the safe prime 1019 = 2 * 509 + 1
+        let residue = |value| Residue::from_unsigned_integer(value, 1019u128);
+        assert!(residue(4).is_generator_of_subgroup(&509));
+        assert!(!residue(2).is_generator_of_subgroup(&509));
+        assert!(residue(2).is_generator_of_subgroup(&1018));
+        assert!(residue(1018).is_generator_of_subgroup(&2));
+
+        // 1 and -1 are the small subgroup elements to reject
+        assert!(!residue(1).is_generator_of_subgroup(&509));
+        assert!(!residue(1018).is_generator_of_subgroup(&509));
+        assert!(!residue(0).is_generator_of_subgroup(&509));
+        assert!(!residue(4).is_generator_of_subgroup(&0));
+
+        // composite q: 2^2 has order 509, not 2 * 509
+        assert!(!residue(4).is_generator_of_subgroup(&1018));
+        for a in 1..1019u128 {
+            let order = residue(a).order().unwrap();
+            assert!(residue(a).is_generator_of_subgroup(&order));
+        }
+    }
+
     #[test]
     fn test_primitive_root() {
-        for &n in [2, 3, 5, 97].iter() {
+        for &n in [1u128, 2, 3, 4, 5, 9, 10, 18, 25, 50, 97, 243, 486, 2 * 97 * 97].iter() {
             let one = Residue::from_unsigned_integer(1, n);
-            let root = Residue::primitive_root(n);
-
-            // root is primitive if and only if none of
-            //     root, root^2, ..., root^{n-2}
-            // equals 1.
-            //
-            // TODO: This test doesn't quite work if the modulus is not prime.
-            // Once Residue::primitive_root() handles non-prime moduli, this
-            // test should be re-written to check the following three properties
-            // hold of {1, root, ..., root^{phi(modulus)-1}}:
-            //     1. all are distinct
-            //     2. all are units mod modulus;
-            // and that root^{phi(modulus)} = 1 mod modulus.
+            let root = Residue::primitive_root(n).unwrap();
+            let phi = euler_totient(n);
+
+            // root is primitive if and only if its powers
+            //     1, root, ..., root^{phi(n)-1}
+            // are all distinct units, and root^{phi(n)} = 1, so that they
+            // make up the whole unit group.
+            let mut powers = std::collections::HashSet::new();
             let mut root_power = one.clone();
-            for e in 1..(n-1) {
+            for _ in 0..phi {
+                assert_eq!(1, gcd(root_power.value, n));
+                assert!(powers.insert(root_power.value));
                 root_power = root_power.times(&root);
-                assert_ne!(root_power, one);
             }
+            assert_eq!(one, root_power);
         }
     }
 
     #[test]
-    #[should_panic]
-    fn test_primitive_root_panics_non_prime_modulus () {
-        Residue::primitive_root(10);
+    fn test_primitive_root_non_cyclic_moduli() {
+        for n in [0u128, 8, 12, 15, 16, 20, 21, 24, 36, 100, 1 << 100] {
+            assert!(!Residue::has_primitive_root(&n));
+            assert_eq!(None, Residue::primitive_root(n));
+            assert!(Residue::primitive_roots(n).is_none());
+        }
+
+        // cyclic exactly when the largest order among the units is phi(n)
+        for n in 1..300u128 {
+            let largest_order = (0..n)
+                .filter_map(|a| Residue::from_unsigned_integer(a, n).order())
+                .max()
+                .unwrap();
+            assert_eq!(largest_order == euler_totient(n), Residue::has_primitive_root(&n), "{}", n);
+        }
+    }
+
+    #[test]
+    fn test_primitive_roots() {
+        let values = |n: u128| {
+            let mut values: Vec<u128> = Residue::primitive_roots(n).unwrap().map(|r| r.value).collect();
+            values.sort();
+            values
+        };
+        assert_eq!(vec![0], values(1));
+        assert_eq!(vec![1], values(2));
+        assert_eq!(vec![3], values(4));
+        assert_eq!(vec![2, 6, 7, 8], values(11));
+        assert_eq!(vec![2, 5, 11, 14, 20, 23], values(27));
+        assert_eq!(vec![3, 5], values(14));
+
+        // there are phi(phi(n)) of them, each with order phi(n)
+        for n in [97u128, 2 * 3u128.pow(5), 5u128.pow(4)] {
+            let phi = euler_totient(n);
+            let roots: Vec<Residue> = Residue::primitive_roots(n).unwrap().collect();
+            assert_eq!(euler_totient(phi) as usize, roots.len());
+            assert!(roots.iter().all(|root| root.order() == Some(phi)));
+        }
+
+        let p = BigUint::from(1019u64);
+        assert_eq!(
+            euler_totient(BigUint::from(1018u64)),
+            BigUint::from(Residue::primitive_roots(p).unwrap().count() as u64));
+    }
+
+    #[test]
+    fn test_primitive_root_large_prime() {
+        // 2^89 - 1 is prime, and p - 1 factors quickly with Pollard rho
+        let p = (1u128 << 89) - 1;
+        let root = Residue::primitive_root(p).unwrap();
+        let one = Residue::from_unsigned_integer(1, p);
+
+        assert_eq!(one, root.pow((p - 1) as i128));
+        for (q, _) in prime_factorize(p - 1) {
+            assert_ne!(one, root.pow(((p - 1) / q) as i128));
+        }
+
+        // and for the square of the prime 2^61 - 1, whose unit group is
+        // still cyclic
+        let p = (1u128 << 61) - 1;
+        let root = Residue::primitive_root(p * p).unwrap();
+        assert_eq!(Some(p * (p - 1)), root.order());
+    }
+
+    #[test]
+    fn test_primitive_root_seeded() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let p = 952252135981u128;
+        let root = Residue::primitive_root_with_rng(&mut StdRng::seed_from_u64(7), p);
+        assert_eq!(root, Residue::primitive_root_with_rng(&mut StdRng::seed_from_u64(7), p));
     }
 }
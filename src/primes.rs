@@ -0,0 +1,291 @@
+use rand::{CryptoRng, Rng, RngCore};
+
+use super::integer::{is_prime, primes_up_to, UnsignedInteger};
+
+/// Small primes are sieved out of candidates before any primality test.
+const SIEVE_BOUND: u64 = 2000;
+
+/// How many candidates to walk through from one random starting point before
+/// picking a new one.
+const WINDOW: u64 = 1 << 16;
+
+/// Walks down the arithmetic progression start, start - step, ... (staying
+/// at or above low, which must be at least step), keeping the residues of
+/// the current candidate modulo small primes up to date so that most
+/// composites are rejected with a few additions.
+///
+/// Walking down rather than up means no candidate can overflow T.
+///
+/// `sieve` gets a residue r and a small prime p, and should return false if
+/// the candidate with that residue is certainly not wanted. `test` is then
+/// run on the survivors.
+fn search<T, S, P>(start: T, step: T, low: &T, sieve: S, test: P) -> Option<T>
+where
+    T: UnsignedInteger,
+    S: Fn(u64, u64) -> bool,
+    P: Fn(&T) -> bool,
+{
+    // only primes below every candidate, so that a zero residue really
+    // means a proper factor
+    let primes: Vec<u64> = primes_up_to(SIEVE_BOUND).into_iter()
+        .skip(1)
+        .filter(|&p| T::from(p) < *low)
+        .collect();
+    let residue = |x: &T, p: u64| (x.clone() % T::from(p)).to_u128().unwrap() as u64;
+    let mut residues: Vec<u64> = primes.iter().map(|&p| residue(&start, p)).collect();
+    let steps: Vec<u64> = primes.iter().map(|&p| residue(&step, p)).collect();
+
+    let mut candidate = start;
+    for _ in 0..WINDOW {
+        if candidate < *low {
+            return None;
+        }
+        if residues.iter().zip(primes.iter()).all(|(&r, &p)| sieve(r, p)) && test(&candidate) {
+            return Some(candidate);
+        }
+
+        candidate = candidate - step.clone();
+        for ((r, &s), &p) in residues.iter_mut().zip(steps.iter()).zip(primes.iter()) {
+            *r = (*r + p - s) % p;
+        }
+    }
+
+    None
+}
+
+/// Returns a random odd integer with exactly the given number of bits.
+fn random_odd<T: UnsignedInteger, R: RngCore + ?Sized>(rng: &mut R, bits: u32) -> T {
+    let low = T::one() << (bits - 1);
+    let x = T::random_below(rng, &low) + low;
+    if x.is_even() { x + T::one() } else { x }
+}
+
+/// Returns a random prime with exactly the given number of bits (so the top
+/// bit is set), for bits >= 2.
+///
+/// Searches downwards from a random odd starting point, so primes preceded
+/// by long gaps are slightly more likely than others; this is the usual
+/// trade-off for being able to sieve.
+pub fn random_prime<T: UnsignedInteger>(bits: u32) -> T {
+    random_prime_with_rng(&mut rand::thread_rng(), bits)
+}
+
+/// `random_prime` drawing its randomness from rng.
+pub fn random_prime_with_rng<T, R>(rng: &mut R, bits: u32) -> T
+where
+    T: UnsignedInteger,
+    R: RngCore + CryptoRng + ?Sized,
+{
+    assert!(bits >= 2, "There are no 1-bit primes.");
+    if bits == 2 {
+        return T::from(2 + rng.gen::<bool>() as u64);
+    }
+
+    let low = T::one() << (bits - 1);
+    loop {
+        let start = random_odd(rng, bits);
+        if let Some(p) = search(start, T::from(2), &low, |r, _| r != 0, |p| is_prime(p.clone())) {
+            return p;
+        }
+    }
+}
+
+/// Returns a random Sophie Germain prime q with the given number of bits:
+/// q and 2q + 1 are both prime. Requires bits >= 2, and 2q + 1 to fit in T.
+pub fn random_sophie_germain_prime<T: UnsignedInteger>(bits: u32) -> T {
+    random_sophie_germain_prime_with_rng(&mut rand::thread_rng(), bits)
+}
+
+/// `random_sophie_germain_prime` drawing its randomness from rng.
+pub fn random_sophie_germain_prime_with_rng<T, R>(rng: &mut R, bits: u32) -> T
+where
+    T: UnsignedInteger,
+    R: RngCore + CryptoRng + ?Sized,
+{
+    assert!(bits >= 2, "There are no 1-bit primes.");
+    if bits == 2 {
+        return T::from(3);
+    }
+
+    let low = T::one() << (bits - 1);
+    let sieve = |r: u64, p: u64| r != 0 && !(2 * r + 1).is_multiple_of(p);
+    let test = |q: &T| is_prime(q.clone()) && is_prime((q.clone() << 1) + T::one());
+    loop {
+        if let Some(q) = search(random_odd(rng, bits), T::from(2), &low, sieve, test) {
+            return q;
+        }
+    }
+}
+
+/// Returns a random safe prime p = 2q + 1 with q prime, with the given
+/// number of bits, for bits >= 3.
+///
+/// The multiplicative group mod a safe prime has order 2q, so every element
+/// other than +-1 has order q or 2q, which is what Diffie-Hellman and
+/// ElGamal want.
+pub fn random_safe_prime<T: UnsignedInteger>(bits: u32) -> T {
+    random_safe_prime_with_rng(&mut rand::thread_rng(), bits)
+}
+
+/// `random_safe_prime` drawing its randomness from rng.
+pub fn random_safe_prime_with_rng<T, R>(rng: &mut R, bits: u32) -> T
+where
+    T: UnsignedInteger,
+    R: RngCore + CryptoRng + ?Sized,
+{
+    assert!(bits >= 3, "There are no safe primes below 5.");
+    (random_sophie_germain_prime_with_rng::<T, R>(rng, bits - 1) << 1) + T::one()
+}
+
+/// Returns a random Blum prime, a prime p = 3 (mod 4), with the given
+/// number of bits, for bits >= 2.
+///
+/// -1 is not a square mod such a p, so each quadratic residue has exactly
+/// one square root that is itself a residue, namely a^{(p+1)/4}.
+pub fn random_blum_prime<T: UnsignedInteger>(bits: u32) -> T {
+    random_blum_prime_with_rng(&mut rand::thread_rng(), bits)
+}
+
+/// `random_blum_prime` drawing its randomness from rng.
+pub fn random_blum_prime_with_rng<T, R>(rng: &mut R, bits: u32) -> T
+where
+    T: UnsignedInteger,
+    R: RngCore + CryptoRng + ?Sized,
+{
+    assert!(bits >= 2, "There are no 1-bit primes.");
+    if bits <= 3 {
+        return T::from(if bits == 2 { 3 } else { 7 });
+    }
+
+    let low = T::one() << (bits - 1);
+    loop {
+        let mut start: T = random_odd(rng, bits);
+        if start.clone() % T::from(4) == T::one() {
+            start = start + T::from(2);
+        }
+        if let Some(p) = search(start, T::from(4), &low, |r, _| r != 0, |p| is_prime(p.clone())) {
+            return p;
+        }
+    }
+}
+
+/// Returns (p, q) with p a random prime of `bits` bits, and q a random prime
+/// of `factor_bits` bits dividing p - 1.
+///
+/// Requires factor_bits + 2 <= bits. Useful for working in a subgroup of
+/// prime order q, and as a p whose p - 1 is not smooth.
+pub fn random_prime_with_large_factor<T: UnsignedInteger>(bits: u32, factor_bits: u32) -> (T, T) {
+    random_prime_with_large_factor_with_rng(&mut rand::thread_rng(), bits, factor_bits)
+}
+
+/// `random_prime_with_large_factor` drawing its randomness from rng.
+pub fn random_prime_with_large_factor_with_rng<T, R>(rng: &mut R, bits: u32, factor_bits: u32) -> (T, T)
+where
+    T: UnsignedInteger,
+    R: RngCore + CryptoRng + ?Sized,
+{
+    assert!(factor_bits >= 2 && factor_bits + 2 <= bits, "Factor too large for the prime.");
+
+    let low = T::one() << (bits - 1);
+    let high = low.clone() - T::one() + low.clone();
+    loop {
+        // p = 2kq + 1 with k chosen so that low <= p <= high
+        let q: T = random_prime_with_rng(rng, factor_bits);
+        let step = q.clone() << 1;
+        let k_low = (low.clone() - T::from(2)) / step.clone() + T::one();
+        let k_high = (high.clone() - T::one()) / step.clone();
+        let k = T::random_below(rng, &(k_high - k_low.clone() + T::one()))
+            + k_low;
+        let start = k * step.clone() + T::one();
+
+        if let Some(p) = search(start, step, &low, |r, _| r != 0, |p| is_prime(p.clone())) {
+            return (p, q);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bigint::BigUint;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_random_prime() {
+        for bits in [2, 3, 4, 8, 17, 64, 100, 128] {
+            let p: u128 = random_prime(bits);
+            assert_eq!(bits, p.bits());
+            assert!(is_prime(p), "{} is not prime", p);
+        }
+    }
+
+    #[test]
+    fn test_random_prime_big_integers() {
+        let p: BigUint = random_prime(256);
+        assert_eq!(256, p.bits());
+        assert!(is_prime(p));
+    }
+
+    #[test]
+    fn test_random_safe_prime() {
+        for bits in [3, 4, 10, 32, 64, 96] {
+            let p: u128 = random_safe_prime(bits);
+            assert_eq!(bits, p.bits());
+            assert!(is_prime(p) && is_prime(p / 2), "{} is not a safe prime", p);
+        }
+    }
+
+    #[test]
+    fn test_random_sophie_germain_prime() {
+        for bits in [2, 5, 20, 63] {
+            let q: u128 = random_sophie_germain_prime(bits);
+            assert_eq!(bits, q.bits());
+            assert!(is_prime(q) && is_prime(2 * q + 1), "{} is not a Sophie Germain prime", q);
+        }
+    }
+
+    #[test]
+    fn test_random_blum_prime() {
+        for bits in [2, 3, 4, 5, 16, 64, 128] {
+            let p: u128 = random_blum_prime(bits);
+            assert_eq!(bits, p.bits());
+            assert!(is_prime(p), "{} is not prime", p);
+            assert_eq!(3, p % 4);
+        }
+    }
+
+    #[test]
+    fn test_random_prime_with_large_factor() {
+        for (bits, factor_bits) in [(10, 8), (64, 40), (127, 100), (128, 2)] {
+            let (p, q): (u128, u128) = random_prime_with_large_factor(bits, factor_bits);
+            assert_eq!(bits, p.bits());
+            assert_eq!(factor_bits, q.bits());
+            assert!(is_prime(p) && is_prime(q));
+            assert_eq!(0, (p - 1) % q);
+        }
+
+        let (p, q): (BigUint, BigUint) = random_prime_with_large_factor(512, 160);
+        assert_eq!(512, p.bits());
+        assert!(is_prime(p.clone()) && is_prime(q.clone()));
+        assert!(((p - BigUint::from(1u64)) % q).is_zero());
+    }
+
+    #[test]
+    fn test_seeded_generation_is_reproducible() {
+        let p: BigUint = random_prime_with_rng(&mut StdRng::seed_from_u64(1), 256);
+        let q: BigUint = random_prime_with_rng(&mut StdRng::seed_from_u64(1), 256);
+        assert_eq!(p, q);
+        let r: BigUint = random_prime_with_rng(&mut StdRng::seed_from_u64(2), 256);
+        assert_ne!(p, r);
+
+        let mut rng = StdRng::seed_from_u64(3);
+        let first: (u128, u128) = (random_safe_prime_with_rng(&mut rng, 64), random_prime_with_rng(&mut rng, 64));
+        let mut rng = StdRng::seed_from_u64(3);
+        let second: (u128, u128) = (random_safe_prime_with_rng(&mut rng, 64), random_prime_with_rng(&mut rng, 64));
+        assert_eq!(first, second);
+
+        let (p, q): (u128, u128) = random_prime_with_large_factor_with_rng(&mut StdRng::seed_from_u64(4), 100, 60);
+        assert_eq!((p, q), random_prime_with_large_factor_with_rng(&mut StdRng::seed_from_u64(4), 100, 60));
+    }
+}
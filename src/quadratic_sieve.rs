@@ -0,0 +1,1320 @@
+use std::collections::{HashMap, HashSet};
+
+use rand::Rng;
+
+use super::bigint::{BigInt, BigUint};
+use super::integer::{gcd, is_prime, isqrt, mod_inverse, pollard_brent, primes_up_to, sqrt_mod_prime, UnsignedInteger};
+
+/// A prime p in the factor base, with a square root of n mod p.
+struct FactorBasePrime {
+    p: u64,
+    sqrt_n: u64,
+    log_p: u8,
+    /// 2^64 / p rounded up, for reducing sieve indices mod p without a
+    /// division.
+    reciprocal: u64,
+}
+
+impl FactorBasePrime {
+    fn new(p: u64, sqrt_n: u64) -> FactorBasePrime {
+        let log_p = (p as f64).log2().round() as u8;
+        FactorBasePrime { p, sqrt_n, log_p, reciprocal: u64::MAX / p + 1 }
+    }
+
+    /// Returns j mod p for j < 2^32: the low 64 bits of j 2^64 / p are the
+    /// fractional part of j / p, which times p is the remainder (Lemire,
+    /// Kaser and Kurz, "Faster remainder by direct computation").
+    fn reduce(&self, j: u32) -> u64 {
+        let fraction = self.reciprocal.wrapping_mul(j as u64);
+        ((fraction as u128 * self.p as u128) >> 64) as u64
+    }
+}
+
+/// A congruence y^2 = Q (mod n) with Q factored over the factor base.
+struct Relation {
+    /// Ax + B, reduced mod n.
+    y: BigUint,
+    /// Matrix columns of the prime factors of Q, with multiplicity. Column
+    /// 0 stands for -1 and column i + 1 for the i-th factor base prime.
+    columns: Vec<usize>,
+    /// For a relation combined from partial relations, the large primes
+    /// they had left over, each of which divides Q an even number of times;
+    /// their squares are not in the columns.
+    large_primes: Vec<u64>,
+}
+
+/// The partial relations, as a graph on 1 and the large primes: a relation
+/// with one large prime p left over is an edge from 1 to p, and one with two,
+/// p and q, is an edge from p to q.
+///
+/// The product of the relations around a cycle has every large prime to an
+/// even power, so it is a full relation. Only a spanning forest is kept: an
+/// edge between two vertices of the same tree closes a cycle with the path
+/// between them in the tree, and is combined with it straight away. An edge
+/// between two trees joins them, by re-rooting the smaller one at its end
+/// of the edge and hanging it below the other end.
+#[derive(Default)]
+struct LargePrimeGraph {
+    /// The parent of each vertex other than a root, and the index of the
+    /// relation for the edge between them.
+    parents: HashMap<u64, (u64, usize)>,
+    /// The number of vertices in the tree below each root, unless it is 1.
+    sizes: HashMap<u64, usize>,
+    relations: Vec<Relation>,
+}
+
+impl LargePrimeGraph {
+    /// Returns the vertices on the path from vertex to its root, with the
+    /// relation for the edge from each to the next.
+    fn path_to_root(&self, vertex: u64) -> Vec<(u64, Option<usize>)> {
+        let mut path = vec![];
+        let mut v = vertex;
+        while let Some(&(parent, r)) = self.parents.get(&v) {
+            path.push((v, Some(r)));
+            v = parent;
+        }
+        path.push((v, None));
+        path
+    }
+
+    /// Makes vertex the root of its tree, by reversing the path above it.
+    fn reroot(&mut self, vertex: u64) {
+        let mut v = vertex;
+        let mut below = None;
+        loop {
+            let above = self.parents.remove(&v);
+            if let Some(edge) = below {
+                self.parents.insert(v, edge);
+            }
+            match above {
+                Some((parent, r)) => {
+                    below = Some((v, r));
+                    v = parent;
+                }
+                None => break,
+            }
+        }
+        if let Some(size) = self.sizes.remove(&v) {
+            self.sizes.insert(vertex, size);
+        }
+    }
+
+    /// Adds a partial relation with large primes p and q left over, and
+    /// returns the full relation from the cycle it closes, if any.
+    fn add(&mut self, n: &BigUint, relation: Relation, p: u64, q: u64) -> Option<Relation> {
+        let path_p = self.path_to_root(p);
+        let path_q = self.path_to_root(q);
+        let (root_p, root_q) = (path_p.last().unwrap().0, path_q.last().unwrap().0);
+
+        if root_p != root_q {
+            let size = |root| *self.sizes.get(&root).unwrap_or(&1);
+            let (size_p, size_q) = (size(root_p), size(root_q));
+            let (p, q, root_q) = if size_p <= size_q { (p, q, root_q) } else { (q, p, root_p) };
+            self.reroot(p);
+            self.sizes.remove(&p);
+            self.sizes.insert(root_q, size_p + size_q);
+            self.parents.insert(p, (q, self.relations.len()));
+            self.relations.push(relation);
+            return None;
+        }
+
+        // the cycle runs from p up to the lowest common ancestor, down to q
+        // and back to p along the new edge
+        let ancestors: HashMap<u64, usize> = path_p.iter().enumerate().map(|(i, &(v, _))| (v, i)).collect();
+        let meet_q = path_q.iter().position(|(v, _)| ancestors.contains_key(v)).unwrap();
+        let meet_p = ancestors[&path_q[meet_q].0];
+
+        let mut combined = relation;
+        for &(v, _) in path_p[..=meet_p].iter().chain(&path_q[..meet_q]) {
+            if v != 1 {
+                combined.large_primes.push(v);
+            }
+        }
+        for &(_, r) in path_p[..meet_p].iter().chain(&path_q[..meet_q]) {
+            let r = &self.relations[r.unwrap()];
+            combined.y = combined.y * &r.y % n;
+            combined.columns.extend_from_slice(&r.columns);
+        }
+        Some(combined)
+    }
+}
+
+/// A polynomial g(x) = A x^2 + 2 B x + C with B^2 - n = A C, so that
+/// (Ax + B)^2 - n = A g(x).
+struct Polynomial {
+    a: BigInt,
+    b: BigInt,
+    c: BigInt,
+}
+
+/// The self-initialising quadratic sieve (SIQS).
+///
+/// Collects many x with (Ax + B)^2 - n smooth over a factor base of small
+/// primes for which n is a square, then finds a subset whose product is a
+/// square Y^2 using linear algebra over GF(2). With X the product of the
+/// Ax + B, X^2 = Y^2 (mod n), so gcd(X - Y, n) is a factor of n at least
+/// half the time.
+///
+/// Each A is a product of s factor base primes, and there are 2^{s-1}
+/// choices of B per A. Switching between them in Gray code order only needs
+/// one addition per factor base prime to update the sieve roots, which is
+/// what makes the method self-initialising.
+///
+/// Cofactors left after the factor base that are one or two large primes are
+/// kept as partial relations, and combined into full relations when they
+/// form cycles. The linear algebra removes singletons and merges pairs of
+/// rows to shrink the sparse matrix, then uses block Lanczos. In release
+/// mode 60 digits take around 15 seconds, 70 digits a minute and 80 digits
+/// 20 minutes, so 100 digits are out of reach without the number field
+/// sieve or a lot of patience.
+#[derive(Clone, Debug)]
+pub struct QuadraticSieve {
+    /// Number of primes in the factor base.
+    pub factor_base_size: usize,
+    /// Each polynomial is sieved for x in [-radius, radius).
+    pub sieve_radius: usize,
+    /// Cofactors below this multiple of the largest factor base prime are
+    /// kept as partial relations, and paired up when they repeat.
+    pub large_prime_multiplier: u64,
+    /// Whether to also keep cofactors that are the product of two large
+    /// primes, and find the cycles they form with the other partials.
+    pub double_large_primes: bool,
+}
+
+impl QuadraticSieve {
+    /// Relations to collect beyond the number of matrix columns, each of
+    /// which gives another chance of a dependency that splits n.
+    const EXTRA_RELATIONS: usize = 20;
+
+    /// Primes below this are not sieved, as they cost the most time and
+    /// contribute the least; the threshold allows for them.
+    const SMALL_PRIME_LIMIT: u64 = 30;
+
+    /// Rounds of fresh relations to try when every dependency is trivial,
+    /// before concluding that n is a prime power.
+    const MAX_TRIVIAL_ROUNDS: usize = 5;
+
+    /// Numbers of at least this many bits use the double large prime
+    /// variation, below which the partials it adds cost more to find than
+    /// the sieving they save.
+    const DOUBLE_LARGE_PRIME_BITS: u32 = 180;
+
+    /// With two large primes, the sieve threshold allows for cofactors up to
+    /// this power of the large prime bound rather than all the way to its
+    /// square, as the biggest are rarely useful and slow to split.
+    const DOUBLE_LARGE_PRIME_SLACK: f64 = 1.6;
+
+    /// Returns parameters suited to n with the given number of bits,
+    /// interpolating the factor base size in msieve's table.
+    pub fn for_bits(bits: u32) -> QuadraticSieve {
+        const TABLE: [(u32, usize, usize); 11] = [
+            (40, 40, 2048),
+            (64, 100, 32768),
+            (128, 450, 32768),
+            (183, 2000, 32768),
+            (200, 3000, 32768),
+            (212, 5400, 98304),
+            (233, 10000, 98304),
+            (249, 27000, 98304),
+            (266, 50000, 98304),
+            (298, 60000, 294912),
+            (332, 100000, 294912),
+        ];
+
+        let i = TABLE.iter().rposition(|&(b, _, _)| b <= bits).unwrap_or(0);
+        let (low_bits, low_size, sieve_radius) = TABLE[i];
+        let factor_base_size = match TABLE.get(i + 1) {
+            Some(&(high_bits, high_size, _)) if bits > low_bits => {
+                let t = (bits - low_bits) as usize;
+                low_size + (high_size - low_size) * t / (high_bits - low_bits) as usize
+            }
+            _ => low_size,
+        };
+
+        QuadraticSieve {
+            factor_base_size,
+            sieve_radius,
+            large_prime_multiplier: 50,
+            double_large_primes: bits >= QuadraticSieve::DOUBLE_LARGE_PRIME_BITS,
+        }
+    }
+
+    /// Returns a non-trivial factor of the odd composite n, or None if the
+    /// sieve runs out of polynomials first.
+    ///
+    /// For a perfect power other than a square every dependency is trivial,
+    /// so this gives up and returns None after `MAX_TRIVIAL_ROUNDS` rounds.
+    pub fn factor<T: UnsignedInteger>(&self, n: &T) -> Option<T> {
+        self.factor_biguint(&n.to_biguint()).map(|d| T::from_biguint(&d))
+    }
+
+    fn factor_biguint(&self, n: &BigUint) -> Option<BigUint> {
+        let root = isqrt(n.clone());
+        if &root * &root == *n {
+            return Some(root);
+        }
+
+        let factor_base = match self.factor_base(n) {
+            Ok(factor_base) => factor_base,
+            Err(p) => return Some(BigUint::from(p)),
+        };
+
+        let p_max = factor_base.last().unwrap().p;
+        let large_prime_bound = p_max * self.large_prime_multiplier.min(p_max);
+        let columns = factor_base.len() + 1;
+
+        let mut rng = rand::thread_rng();
+        let mut used_a = HashSet::new();
+        let mut relations = vec![];
+        let mut partials = LargePrimeGraph::default();
+        let mut wanted = columns + QuadraticSieve::EXTRA_RELATIONS;
+        let mut trivial_rounds = 0;
+
+        loop {
+            while relations.len() < wanted {
+                let (a, a_indices) = self.choose_a(n, &factor_base, &mut used_a, &mut rng)?;
+                self.sieve_a(
+                    n, &factor_base, &a, &a_indices, large_prime_bound,
+                    &mut relations, &mut partials, wanted);
+            }
+
+            for dependency in find_dependencies(&relations, columns, &mut rng) {
+                if let Some(d) = square_root(n, &factor_base, &relations, &dependency) {
+                    return Some(d);
+                }
+            }
+
+            // every dependency was trivial; extremely unlikely for n with
+            // two distinct prime factors, but more relations will give new
+            // ones. For a prime power they are always trivial.
+            trivial_rounds += 1;
+            if trivial_rounds == QuadraticSieve::MAX_TRIVIAL_ROUNDS {
+                return None;
+            }
+            wanted += QuadraticSieve::EXTRA_RELATIONS;
+        }
+    }
+
+    /// Returns the factor base: 2 and the smallest odd primes p with n a
+    /// non-zero square mod p, or Err(p) if some small prime p divides n.
+    fn factor_base(&self, n: &BigUint) -> Result<Vec<FactorBasePrime>, u64> {
+        let mut factor_base = vec![FactorBasePrime::new(2, 1)];
+        let mut searched = 2;
+        let mut bound = 1000;
+
+        loop {
+            for p in primes_up_to(bound).into_iter().skip_while(|&p| p <= searched) {
+                if factor_base.len() >= self.factor_base_size {
+                    return Ok(factor_base);
+                }
+
+                let n_mod_p = n.rem_u64(p);
+                if n_mod_p == 0 {
+                    return Err(p);
+                }
+                if let Some(sqrt_n) = sqrt_mod_prime(&(n_mod_p as u128), &(p as u128)) {
+                    factor_base.push(FactorBasePrime::new(p, sqrt_n as u64));
+                }
+            }
+            searched = bound;
+            bound *= 2;
+        }
+    }
+
+    /// Picks a new A = q_1 ... q_s close to sqrt(2n) / radius, which keeps
+    /// g(x) as small as possible over the sieve interval.
+    ///
+    /// The q_j are taken at random from the upper part of the factor base,
+    /// with the last one chosen to bring A closest to the target.
+    fn choose_a<R: Rng>(
+            &self, n: &BigUint, factor_base: &[FactorBasePrime],
+            used_a: &mut HashSet<BigUint>, rng: &mut R) -> Option<(BigUint, Vec<usize>)> {
+        let target = isqrt(n.clone() << 1) / BigUint::from(self.sieve_radius as u64);
+        let pool: Vec<usize> = (factor_base.len() / 3..factor_base.len())
+            .filter(|&i| factor_base[i].p > QuadraticSieve::SMALL_PRIME_LIMIT)
+            .collect();
+        if pool.is_empty() {
+            return None;
+        }
+        let largest = BigUint::from(factor_base[*pool.last().unwrap()].p);
+
+        for _ in 0..100 {
+            let mut a = BigUint::one();
+            let mut indices = vec![];
+            while indices.len() + 1 < pool.len() && &target / &a > largest {
+                let i = pool[rng.gen_range(0..pool.len())];
+                if !indices.contains(&i) {
+                    indices.push(i);
+                    a *= BigUint::from(factor_base[i].p);
+                }
+            }
+
+            let remaining = (&target / &a).to_u128().unwrap_or(u128::MAX);
+            let mut last: Vec<usize> = pool.iter()
+                .copied()
+                .filter(|i| !indices.contains(i))
+                .collect();
+            last.sort_by_key(|&i| (factor_base[i].p as u128).abs_diff(remaining));
+
+            for i in last {
+                let candidate = &a * &BigUint::from(factor_base[i].p);
+                if used_a.insert(candidate.clone()) {
+                    indices.push(i);
+                    return Some((candidate, indices));
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Sieves every polynomial with leading coefficient A, adding what it
+    /// finds to relations and partials until there are `wanted` relations.
+    #[allow(clippy::too_many_arguments)]
+    fn sieve_a(
+            &self, n: &BigUint, factor_base: &[FactorBasePrime], a: &BigUint,
+            a_indices: &[usize], large_prime_bound: u64, relations: &mut Vec<Relation>,
+            partials: &mut LargePrimeGraph, wanted: usize) {
+        // B_l = (A / q_l) * gamma_l with gamma_l = t_l (A / q_l)^{-1} mod q_l,
+        // so that B_l^2 = n (mod q_l) and B_l = 0 (mod q_k) for k != l; the
+        // sums +-B_1 +- ... +- B_s are then square roots of n mod A.
+        let b_terms: Vec<BigUint> = a_indices.iter()
+            .map(|&i| {
+                let q = factor_base[i].p;
+                let (a_over_q, _) = a.div_rem_u64(q);
+                let inverse = mod_inverse(a_over_q.rem_u64(q) as u128, q as u128).unwrap() as u64;
+                let mut gamma = factor_base[i].sqrt_n * inverse % q;
+                if gamma > q / 2 {
+                    gamma = q - gamma;
+                }
+                a_over_q * BigUint::from(gamma)
+            })
+            .collect();
+
+        // for p not dividing A, the roots of g mod p are A^{-1} (+-t - B);
+        // switching B by +-2 B_l moves them by -+2 B_l A^{-1}
+        let radius = self.sieve_radius as u64;
+        let mut roots = vec![None; factor_base.len()];
+        let mut steps = vec![vec![0; factor_base.len()]; b_terms.len()];
+        for (i, fb) in factor_base.iter().enumerate().skip(1) {
+            let p = fb.p;
+            let a_inverse = match mod_inverse(a.rem_u64(p) as u128, p as u128) {
+                Some(a_inverse) => a_inverse as u64,
+                None => continue,
+            };
+            let b_mod_p = b_terms.iter().map(|b| b.rem_u64(p)).sum::<u64>() % p;
+            for (l, b) in b_terms.iter().enumerate() {
+                steps[l][i] = 2 * b.rem_u64(p) % p * a_inverse % p;
+            }
+            // store roots shifted by the radius, as sieve array indices
+            let root = |t: u64| (a_inverse * ((t + 2 * p - b_mod_p) % p) + radius) % p;
+            roots[i] = Some((root(fb.sqrt_n), root(p - fb.sqrt_n)));
+        }
+
+        let a_signed = BigInt::from(a.clone());
+        let mut b = BigInt::from(b_terms.iter().fold(BigUint::zero(), |sum, b| sum + b));
+        let gray_codes = 1usize << (b_terms.len() - 1);
+
+        for k in 0..gray_codes {
+            if k > 0 {
+                // move to the next Gray code, which flips sign bit v of B_{v+1}
+                let v = k.trailing_zeros() as usize;
+                let negate = (k ^ (k >> 1)) >> v & 1 == 1;
+                let twice_b_l = BigInt::from(&b_terms[v + 1] << 1);
+                for (i, root) in roots.iter_mut().enumerate() {
+                    if let Some((r1, r2)) = root {
+                        let p = factor_base[i].p;
+                        let step = if negate { steps[v + 1][i] } else { p - steps[v + 1][i] };
+                        // step <= p, so one subtraction reduces mod p
+                        for r in [r1, r2] {
+                            *r += step;
+                            if *r >= p {
+                                *r -= p;
+                            }
+                        }
+                    }
+                }
+                b = if negate { b - twice_b_l } else { b + twice_b_l };
+            }
+
+            let c = (&b * &b - BigInt::from(n.clone())) / a_signed.clone();
+            let polynomial = Polynomial { a: a_signed.clone(), b: b.clone(), c };
+
+            for x in self.sieve(factor_base, &roots, n.bits()) {
+                if let Some((relation, (p, q))) = self.check_candidate(
+                        n, factor_base, &polynomial, a_indices, &roots, x, large_prime_bound) {
+                    if q == 1 {
+                        relations.push(relation);
+                    } else if let Some(relation) = partials.add(n, relation, p, q) {
+                        relations.push(relation);
+                    }
+                }
+            }
+
+            if relations.len() >= wanted {
+                return;
+            }
+        }
+    }
+
+    /// Adds log p at every index where p divides g(x), and returns the
+    /// indices whose total is large enough that g(x) is probably smooth
+    /// apart from the large primes allowed.
+    fn sieve(
+            &self, factor_base: &[FactorBasePrime], roots: &[Option<(u64, u64)>],
+            n_bits: u32) -> Vec<usize> {
+        let length = 2 * self.sieve_radius;
+        let mut sieve = vec![0u8; length];
+
+        for (fb, root) in factor_base.iter().zip(roots.iter()) {
+            if fb.p < QuadraticSieve::SMALL_PRIME_LIMIT {
+                continue;
+            }
+            if let Some((r1, r2)) = *root {
+                let p = fb.p as usize;
+                for start in [r1 as usize, r2 as usize] {
+                    for j in (start..length).step_by(p) {
+                        sieve[j] = sieve[j].saturating_add(fb.log_p);
+                    }
+                }
+            }
+        }
+
+        // |g(x)| is at most about radius * sqrt(n / 2); allow for a cofactor
+        // of one or two large primes and for the unsieved small primes
+        let p_max = factor_base.last().unwrap().p as f64;
+        let log_g = (self.sieve_radius as f64).log2() + (n_bits as f64 - 1.0) / 2.0;
+        let lp = (p_max * self.large_prime_multiplier.min(p_max as u64) as f64).log2();
+        let cofactor = if self.double_large_primes { QuadraticSieve::DOUBLE_LARGE_PRIME_SLACK * lp } else { lp };
+        let slack = cofactor + 6.0;
+        let threshold = (log_g - slack).max(0.0) as u8;
+
+        // find the rare candidates a chunk at a time, as max vectorises
+        let mut r = vec![];
+        for (c, chunk) in sieve.chunks(64).enumerate() {
+            if chunk.iter().fold(0, |m, &s| m.max(s)) >= threshold {
+                r.extend((0..chunk.len()).filter(|&j| chunk[j] >= threshold).map(|j| 64 * c + j));
+            }
+        }
+        r
+    }
+
+    /// Trial divides g(x) for the candidate at sieve index j, using the
+    /// sieve roots to skip primes that cannot divide it. Returns the
+    /// relation and the large primes (p, q) left over, with 1 for each one
+    /// missing.
+    #[allow(clippy::too_many_arguments)]
+    fn check_candidate(
+            &self, n: &BigUint, factor_base: &[FactorBasePrime], polynomial: &Polynomial,
+            a_indices: &[usize], roots: &[Option<(u64, u64)>], j: usize,
+            large_prime_bound: u64) -> Option<(Relation, (u64, u64))> {
+        let x = BigInt::from(j as i64 - self.sieve_radius as i64);
+        let two_b = &polynomial.b + &polynomial.b;
+        let g = (&polynomial.a * &x + two_b) * &x + &polynomial.c;
+        if *g.magnitude() == BigUint::zero() {
+            return None;
+        }
+
+        let mut columns = vec![];
+        if g.is_negative() {
+            columns.push(0);
+        }
+        let mut m = g.magnitude().clone();
+        while m.is_even() {
+            m >>= 1;
+            columns.push(1);
+        }
+
+        for (i, fb) in factor_base.iter().enumerate().skip(1) {
+            let p = fb.p;
+            let divides = match roots[i] {
+                Some((r1, r2)) => {
+                    let r = fb.reduce(j as u32);
+                    r == r1 || r == r2
+                }
+                None => m.rem_u64(p) == 0,
+            };
+            if !divides {
+                continue;
+            }
+            loop {
+                let (q, r) = m.div_rem_u64(p);
+                if r != 0 {
+                    break;
+                }
+                m = q;
+                columns.push(i + 1);
+            }
+        }
+
+        let large_primes = match m.to_u128() {
+            Some(1) => (1, 1),
+            Some(m) if m < large_prime_bound as u128 => (1, m as u64),
+            Some(m) if self.double_large_primes && m < (large_prime_bound as u128).pow(2) && !is_prime(m) => {
+                // with no factor in the factor base, m is almost always the
+                // product of two primes, but nothing below relies on it
+                let p = pollard_brent(&m);
+                let q = m / p;
+                if p.max(q) >= large_prime_bound as u128 {
+                    return None;
+                }
+                (p.min(q) as u64, p.max(q) as u64)
+            }
+            _ => return None,
+        };
+
+        // (Ax + B)^2 - n = A g(x)
+        columns.extend(a_indices.iter().map(|&i| i + 1));
+        let y = (&polynomial.a * &x + &polynomial.b).rem_euclid(n);
+
+        Some((Relation { y, columns, large_primes: vec![] }, large_primes))
+    }
+}
+
+/// Returns subsets of relations whose exponent vectors sum to zero mod 2.
+///
+/// Structured elimination shrinks the matrix first, then block Lanczos finds
+/// dependencies among the rows that are left, or dense Gauss-Jordan
+/// elimination does when there are few enough columns.
+fn find_dependencies<R: Rng>(relations: &[Relation], columns: usize, rng: &mut R) -> Vec<Vec<usize>> {
+    let rows = relations.iter()
+        .enumerate()
+        .map(|(r, relation)| {
+            let mut columns = relation.columns.clone();
+            columns.sort_unstable();
+            MatrixRow { columns: sum_mod_2(&columns, &[]), relations: vec![r] }
+        })
+        .collect();
+    let (rows, columns) = structured_elimination(rows, columns);
+
+    let matrix: Vec<Vec<usize>> = rows.iter().map(|row| row.columns.clone()).collect();
+    let dependencies = if columns <= DENSE_COLUMNS {
+        dense_dependencies(&matrix, columns)
+    } else {
+        (0..LANCZOS_ATTEMPTS)
+            .find_map(|_| block_lanczos(&matrix, columns, rng))
+            .unwrap_or_default()
+    };
+
+    dependencies.into_iter()
+        .map(|dependency| {
+            let mut relations: Vec<usize> = dependency.iter()
+                .flat_map(|&i| rows[i].relations.iter().copied())
+                .collect();
+            relations.sort_unstable();
+            relations
+        })
+        .collect()
+}
+
+/// Matrices with at most this many columns left after structured
+/// elimination are solved densely.
+const DENSE_COLUMNS: usize = 256;
+
+/// Block Lanczos fails now and then, and is restarted with a different
+/// random start when it does.
+const LANCZOS_ATTEMPTS: usize = 4;
+
+/// A row of the matrix: its columns with an odd exponent, in increasing
+/// order, and the relations it is the sum of.
+struct MatrixRow {
+    columns: Vec<usize>,
+    relations: Vec<usize>,
+}
+
+/// Adds two rows given as sorted lists of columns: the columns in exactly
+/// one of them. Repeats within a list also cancel in pairs.
+fn sum_mod_2(a: &[usize], b: &[usize]) -> Vec<usize> {
+    let mut sum: Vec<usize> = Vec::with_capacity(a.len() + b.len());
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        let c = if j == b.len() || (i < a.len() && a[i] <= b[j]) {
+            i += 1;
+            a[i - 1]
+        } else {
+            j += 1;
+            b[j - 1]
+        };
+        if sum.last() == Some(&c) {
+            sum.pop();
+        } else {
+            sum.push(c);
+        }
+    }
+    sum
+}
+
+/// The cheap part of structured Gaussian elimination, which shrinks the
+/// matrix before the expensive part sees it.
+///
+/// A column that only one row has can't cancel, so that row is in no
+/// dependency and is deleted. A column that exactly two rows have cancels
+/// only if both are used, so they are merged into their sum, removing a
+/// row and a column at once. Repeating both until neither applies removes
+/// most of the large primes of the factor base, which are the majority of
+/// columns but rarely occur. Empty columns are then dropped and the rest
+/// renumbered, and the number of columns left is returned with the rows.
+fn structured_elimination(mut rows: Vec<MatrixRow>, columns: usize) -> (Vec<MatrixRow>, usize) {
+    loop {
+        // the number of rows with each column, and the first two of them
+        let mut weights = vec![0usize; columns];
+        let mut owners = vec![[0usize; 2]; columns];
+        for (r, row) in rows.iter().enumerate() {
+            for &c in row.columns.iter() {
+                if weights[c] < 2 {
+                    owners[c][weights[c]] = r;
+                }
+                weights[c] += 1;
+            }
+        }
+
+        let before = rows.len();
+        rows.retain(|row| row.columns.iter().all(|&c| weights[c] != 1));
+        if rows.len() < before {
+            continue;
+        }
+
+        // merge each pair of rows at most once per pass, as the weights go
+        // stale once they change
+        let mut merged = vec![false; rows.len()];
+        let mut deleted = vec![false; rows.len()];
+        for c in 0..columns {
+            let [r, s] = owners[c];
+            if weights[c] != 2 || merged[r] || merged[s] {
+                continue;
+            }
+            let sum = MatrixRow {
+                columns: sum_mod_2(&rows[r].columns, &rows[s].columns),
+                relations: [rows[r].relations.clone(), rows[s].relations.clone()].concat(),
+            };
+            rows[r] = sum;
+            merged[r] = true;
+            merged[s] = true;
+            deleted[s] = true;
+        }
+        if !deleted.contains(&true) {
+            break;
+        }
+        let mut deleted = deleted.into_iter();
+        rows.retain(|_| !deleted.next().unwrap());
+    }
+
+    let mut renumbered = vec![None; columns];
+    let mut used = 0;
+    for row in rows.iter_mut() {
+        for c in row.columns.iter_mut() {
+            *c = *renumbered[*c].get_or_insert_with(|| {
+                used += 1;
+                used - 1
+            });
+        }
+        row.columns.sort_unstable();
+    }
+    (rows, used)
+}
+
+/// Returns subsets of the rows whose sum is zero.
+///
+/// Gauss-Jordan elimination on the rows, each augmented with an identity
+/// row recording which rows it is a sum of; rows that end up zero give the
+/// dependencies.
+fn dense_dependencies(rows: &[Vec<usize>], columns: usize) -> Vec<Vec<usize>> {
+    let matrix_words = columns.div_ceil(64);
+    let history_words = rows.len().div_ceil(64);
+    let mut dense: Vec<Vec<u64>> = rows.iter()
+        .enumerate()
+        .map(|(r, row)| {
+            let mut dense_row = vec![0; matrix_words + history_words];
+            for &c in row.iter() {
+                dense_row[c / 64] ^= 1 << (c % 64);
+            }
+            dense_row[matrix_words + r / 64] |= 1 << (r % 64);
+            dense_row
+        })
+        .collect();
+
+    let mut is_pivot = vec![false; dense.len()];
+    for c in 0..columns {
+        let (word, bit) = (c / 64, 1 << (c % 64));
+        let pivot = match (0..dense.len()).find(|&r| !is_pivot[r] && dense[r][word] & bit != 0) {
+            Some(pivot) => pivot,
+            None => continue,
+        };
+        is_pivot[pivot] = true;
+
+        let pivot_row = dense[pivot].clone();
+        for (r, row) in dense.iter_mut().enumerate() {
+            if r != pivot && row[word] & bit != 0 {
+                for (x, y) in row.iter_mut().zip(pivot_row.iter()) {
+                    *x ^= y;
+                }
+            }
+        }
+    }
+
+    dense.iter()
+        .zip(is_pivot.iter())
+        .filter(|&(_, &is_pivot)| !is_pivot)
+        .map(|(row, _)| {
+            (0..rows.len())
+                .filter(|&r| row[matrix_words + r / 64] >> (r % 64) & 1 == 1)
+                .collect()
+        })
+        .collect()
+}
+
+/// A 64 x 64 matrix over GF(2), by rows: bit j of entry i is in row i and
+/// column j.
+type Matrix64 = [u64; 64];
+
+/// Returns subsets of the rows whose sum is zero, found 64 at a time by
+/// Montgomery's block Lanczos algorithm, or None if it breaks down.
+///
+/// Take B to be the matrix with the rows as its columns, so that the
+/// dependencies are the null space of B, and A = B^T B, which is symmetric.
+/// Lanczos iterates on blocks V_i of 64 vectors, each A-orthogonal to all the
+/// earlier ones, so A is only ever applied to vectors, costing twice the
+/// number of non-zero entries each time, and it stops after about rows / 63
+/// iterations with a solution X of A X = A Y for a random Y. Then X - Y and
+/// the last block V_m are nearly in the null space of A, and the
+/// combinations of their 128 columns that B maps to zero are dependencies.
+/// O(rows * weight * rows / 64) time and O(rows) space, against O(rows^3 /
+/// 64) time and O(rows^2) space for Gaussian elimination.
+///
+/// Blocks are stored with one 64-bit word per row of the matrix, bit j of
+/// word i being entry i of vector j.
+fn block_lanczos<R: Rng>(rows: &[Vec<usize>], columns: usize, rng: &mut R) -> Option<Vec<Vec<usize>>> {
+    let n = rows.len();
+    let multiply_a = |v: &[u64]| multiply_transpose(rows, &multiply(rows, columns, v));
+
+    let y: Vec<u64> = (0..n).map(|_| rng.gen()).collect();
+    let v0 = multiply_a(&y);
+    let mut x = vec![0u64; n];
+
+    // index 0 is iteration i, 1 is i - 1, 2 is i - 2; before the start the
+    // earlier blocks are zero and every column counts as chosen
+    let mut v = [v0.clone(), vec![0; n], vec![0; n]];
+    let mut w_inverse = [[0u64; 64]; 3];
+    let mut vt_a_v = [[0u64; 64]; 2];
+    let mut vt_a2_v = [[0u64; 64]; 2];
+    let mut chosen = [u64::MAX; 2];
+    let mut previous: Vec<usize> = (0..64).collect();
+
+    // each iteration uses up about 63 dimensions
+    for _ in 0..n / 60 + 10 {
+        let av = multiply_a(&v[0]);
+        vt_a_v[0] = inner_product(&v[0], &av);
+        if vt_a_v[0].iter().all(|&word| word == 0) {
+            return Some(combine_null_vectors(rows, columns, &x, &y, &v[0]));
+        }
+        vt_a2_v[0] = inner_product(&av, &av);
+
+        // W_i is the largest set of columns of V_i with V_i^T A V_i
+        // invertible on it, preferring those left out of W_{i-1}
+        let (inverse, columns_chosen) = invert_submatrix(&vt_a_v[0], &previous)?;
+        w_inverse[0] = inverse;
+        chosen[0] = columns_chosen.iter().fold(0, |mask, &j| mask | 1 << j);
+        previous = columns_chosen;
+        if chosen[0] | chosen[1] != u64::MAX {
+            return None;
+        }
+
+        // V_{i+1} = A V_i S_i S_i^T + V_i D + V_{i-1} E + V_{i-2} F
+        let mut d = [0u64; 64];
+        let mut e = [0u64; 64];
+        let mut f = [0u64; 64];
+        let mut f2 = [0u64; 64];
+        for i in 0..64 {
+            d[i] = (vt_a2_v[0][i] & chosen[0]) ^ vt_a_v[0][i];
+            f2[i] = ((vt_a2_v[1][i] & chosen[1]) ^ vt_a_v[1][i]) & chosen[0];
+        }
+        d = multiply_64(&w_inverse[0], &d);
+        let c = multiply_64(&vt_a_v[1], &w_inverse[1]);
+        for i in 0..64 {
+            d[i] ^= 1 << i;
+            f[i] = c[i] ^ 1 << i;
+        }
+        f = multiply_64(&multiply_64(&w_inverse[2], &f), &f2);
+        for (i, row) in multiply_64(&w_inverse[1], &vt_a_v[0]).iter().enumerate() {
+            e[i] = row & chosen[0];
+        }
+
+        let vd = multiply_block(&v[0], &d);
+        let ve = multiply_block(&v[1], &e);
+        let vf = multiply_block(&v[2], &f);
+        let next: Vec<u64> = (0..n).map(|k| (av[k] & chosen[0]) ^ vd[k] ^ ve[k] ^ vf[k]).collect();
+
+        // X += V_i W_i^{-1} V_i^T V_0
+        let step = multiply_block(&v[0], &multiply_64(&w_inverse[0], &inner_product(&v[0], &v0)));
+        for (xk, sk) in x.iter_mut().zip(step) {
+            *xk ^= sk;
+        }
+
+        v.rotate_right(1);
+        v[0] = next;
+        w_inverse.rotate_right(1);
+        vt_a_v[1] = vt_a_v[0];
+        vt_a2_v[1] = vt_a2_v[0];
+        chosen[1] = chosen[0];
+    }
+
+    None
+}
+
+/// Returns the combinations of the columns of X - Y and V_m that B maps to
+/// zero, as subsets of the rows.
+fn combine_null_vectors(
+        rows: &[Vec<usize>], columns: usize, x: &[u64], y: &[u64], v: &[u64]) -> Vec<Vec<usize>> {
+    let candidates: Vec<u128> = x.iter()
+        .zip(y.iter())
+        .zip(v.iter())
+        .map(|((&x, &y), &v)| (x ^ y) as u128 | (v as u128) << 64)
+        .collect();
+    let low = multiply(rows, columns, &candidates.iter().map(|&c| c as u64).collect::<Vec<_>>());
+    let high = multiply(rows, columns, &candidates.iter().map(|&c| (c >> 64) as u64).collect::<Vec<_>>());
+
+    // reduce the images to reduced echelon form, one equation per column of
+    // B, so that each equation has its pivot bit and otherwise only free bits
+    let mut pivots: Vec<(u32, u128)> = vec![];
+    for (&l, &h) in low.iter().zip(high.iter()) {
+        let mut equation = l as u128 | (h as u128) << 64;
+        for &(bit, pivot) in pivots.iter() {
+            if equation >> bit & 1 == 1 {
+                equation ^= pivot;
+            }
+        }
+        if equation != 0 {
+            let bit = equation.trailing_zeros();
+            for (_, pivot) in pivots.iter_mut() {
+                if *pivot >> bit & 1 == 1 {
+                    *pivot ^= equation;
+                }
+            }
+            pivots.push((bit, equation));
+        }
+    }
+
+    // each free bit f gives the solution e_f + the pivot bits of the
+    // equations with f
+    let pivot_bits: u128 = pivots.iter().fold(0, |mask, &(bit, _)| mask | 1 << bit);
+    (0..128)
+        .filter(|&f| pivot_bits >> f & 1 == 0)
+        .map(|f| {
+            let combination = pivots.iter()
+                .filter(|&&(_, p)| p >> f & 1 == 1)
+                .fold(1u128 << f, |c, &(bit, _)| c | 1 << bit);
+            (0..rows.len()).filter(|&k| (candidates[k] & combination).count_ones() % 2 == 1).collect()
+        })
+        .filter(|dependency: &Vec<usize>| !dependency.is_empty())
+        .collect()
+}
+
+/// Returns B v for a block v, where B has the rows as its columns.
+fn multiply(rows: &[Vec<usize>], columns: usize, v: &[u64]) -> Vec<u64> {
+    let mut product = vec![0; columns];
+    for (row, &word) in rows.iter().zip(v.iter()) {
+        for &c in row.iter() {
+            product[c] ^= word;
+        }
+    }
+    product
+}
+
+/// Returns B^T w for a block w.
+fn multiply_transpose(rows: &[Vec<usize>], w: &[u64]) -> Vec<u64> {
+    rows.iter().map(|row| row.iter().fold(0, |sum, &c| sum ^ w[c])).collect()
+}
+
+/// Returns x^T y, for blocks x and y.
+fn inner_product(x: &[u64], y: &[u64]) -> Matrix64 {
+    // sum y[k] into one table entry per byte of x[k], then expand
+    let mut tables = [[0u64; 256]; 8];
+    for (&a, &b) in x.iter().zip(y.iter()) {
+        for (table, byte) in tables.iter_mut().zip(a.to_le_bytes()) {
+            table[byte as usize] ^= b;
+        }
+    }
+
+    let mut product = [0u64; 64];
+    for (t, table) in tables.iter().enumerate() {
+        for (byte, &sum) in table.iter().enumerate() {
+            for (bit, row) in product[8 * t..8 * t + 8].iter_mut().enumerate() {
+                if byte >> bit & 1 == 1 {
+                    *row ^= sum;
+                }
+            }
+        }
+    }
+    product
+}
+
+/// Returns v m, for a block v.
+fn multiply_block(v: &[u64], m: &Matrix64) -> Vec<u64> {
+    // the sums of the rows of m selected by each value of each byte
+    let mut tables = [[0u64; 256]; 8];
+    for (t, table) in tables.iter_mut().enumerate() {
+        for byte in 1..256 {
+            let low = byte & (byte - 1);
+            table[byte] = table[low] ^ m[8 * t + (byte ^ low).trailing_zeros() as usize];
+        }
+    }
+
+    v.iter()
+        .map(|&word| {
+            tables.iter()
+                .zip(word.to_le_bytes())
+                .fold(0, |sum, (table, byte)| sum ^ table[byte as usize])
+        })
+        .collect()
+}
+
+/// Returns a b, for 64 x 64 matrices.
+fn multiply_64(a: &Matrix64, b: &Matrix64) -> Matrix64 {
+    let mut product = [0u64; 64];
+    for (row, &a_row) in product.iter_mut().zip(a.iter()) {
+        let mut bits = a_row;
+        while bits != 0 {
+            *row ^= b[bits.trailing_zeros() as usize];
+            bits &= bits - 1;
+        }
+    }
+    product
+}
+
+/// Inverts a largest invertible submatrix of a symmetric t, built from its
+/// columns one at a time, trying those not previously chosen first.
+///
+/// Returns the inverse, padded with zero rows and columns to 64 x 64, and
+/// the chosen columns, or None if some column has neither a pivot nor a substitute, which only
+/// happens when the iteration has gone wrong. Gauss-Jordan on [t | I], as
+/// in Montgomery's paper.
+fn invert_submatrix(t: &Matrix64, previous: &[usize]) -> Option<(Matrix64, Vec<usize>)> {
+    let mut m: Vec<[u64; 2]> = (0..64).map(|i| [t[i], 1 << i]).collect();
+
+    // columns chosen last time go last
+    let last: u64 = previous.iter().fold(0, |mask, &j| mask | 1 << j);
+    let mut order: Vec<usize> = (0..64).filter(|&j| last >> j & 1 == 0).collect();
+    order.extend(previous.iter().copied());
+
+    let mut chosen = vec![];
+    for i in 0..64 {
+        let column = order[i];
+        let mask = 1u64 << column;
+        for half in 0..2 {
+            let pivot = match (i..64).find(|&k| m[order[k]][half] & mask != 0) {
+                Some(pivot) => pivot,
+                None if half == 0 => continue,
+                None => return None,
+            };
+            m.swap(order[i], order[pivot]);
+            let pivot_row = m[order[i]];
+            for (r, row) in m.iter_mut().enumerate() {
+                if r != column && row[half] & mask != 0 {
+                    row[0] ^= pivot_row[0];
+                    row[1] ^= pivot_row[1];
+                }
+            }
+            if half == 0 {
+                chosen.push(column);
+            } else {
+                m[column] = [0, 0];
+            }
+            break;
+        }
+    }
+
+    let mut inverse = [0u64; 64];
+    for (row, m_row) in inverse.iter_mut().zip(m.iter()) {
+        *row = m_row[1];
+    }
+    Some((inverse, chosen))
+}
+
+/// Multiplies out a dependency to get X^2 = Y^2 (mod n), and returns
+/// gcd(X - Y, n) if it is a non-trivial factor.
+fn square_root(
+        n: &BigUint, factor_base: &[FactorBasePrime], relations: &[Relation],
+        dependency: &[usize]) -> Option<BigUint> {
+    let mut x = BigUint::one();
+    let mut y = BigUint::one();
+    let mut exponents = vec![0u32; factor_base.len() + 1];
+
+    for &r in dependency {
+        let relation = &relations[r];
+        x = x * &relation.y % n;
+        for &p in relation.large_primes.iter() {
+            y = y * BigUint::from(p) % n;
+        }
+        for &c in relation.columns.iter() {
+            exponents[c] += 1;
+        }
+    }
+
+    // every exponent is even, and the sign (column 0) cancels out
+    for (fb, &e) in factor_base.iter().zip(exponents.iter().skip(1)) {
+        for _ in 0..e / 2 {
+            y = y * BigUint::from(fb.p) % n;
+        }
+    }
+
+    let d = gcd((x + n - y) % n, n.clone());
+    if d != BigUint::one() && d != *n { Some(d) } else { None }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Asserts that the sieve with parameters for n splits it.
+    fn assert_splits(n: u128) {
+        let d = QuadraticSieve::for_bits(u128::BITS - n.leading_zeros())
+            .factor(&n)
+            .unwrap_or_else(|| panic!("quadratic sieve did not split {}", n));
+        assert!(d > 1 && d < n && n.is_multiple_of(d), "{} is not a proper factor of {}", d, n);
+    }
+
+    #[test]
+    fn test_parameters() {
+        assert_eq!(100, QuadraticSieve::for_bits(64).factor_base_size);
+        assert_eq!(275, QuadraticSieve::for_bits(96).factor_base_size);
+        assert_eq!(450, QuadraticSieve::for_bits(128).factor_base_size);
+        assert_eq!(100000, QuadraticSieve::for_bits(400).factor_base_size);
+        assert_eq!(40, QuadraticSieve::for_bits(20).factor_base_size);
+
+        assert!(!QuadraticSieve::for_bits(128).double_large_primes);
+        assert!(QuadraticSieve::for_bits(200).double_large_primes);
+    }
+
+    #[test]
+    fn test_factor_base() {
+        let n = BigUint::from(1000003u128 * 1000033);
+        let factor_base = QuadraticSieve::for_bits(40).factor_base(&n).unwrap();
+        assert_eq!(40, factor_base.len());
+        for fb in factor_base.iter() {
+            assert_eq!(n.rem_u64(fb.p), fb.sqrt_n * fb.sqrt_n % fb.p);
+            for j in [0, 1, fb.p as u32 - 1, fb.p as u32, 12345, 1 << 20, u32::MAX] {
+                assert_eq!(j as u64 % fb.p, fb.reduce(j));
+            }
+        }
+
+        let n = BigUint::from(211u128 * 1000033);
+        assert_eq!(Some(211), QuadraticSieve::for_bits(40).factor_base(&n).err());
+    }
+
+    #[test]
+    fn test_relations_are_congruences() {
+        let n = BigUint::from(4611686018427388039u128 * 1000000000000000003);
+        let qs = QuadraticSieve::for_bits(n.bits());
+        let factor_base = qs.factor_base(&n).unwrap();
+        let mut used_a = HashSet::new();
+        let (a, a_indices) = qs.choose_a(&n, &factor_base, &mut used_a, &mut rand::thread_rng())
+            .unwrap();
+        let mut relations = vec![];
+        qs.sieve_a(&n, &factor_base, &a, &a_indices, u64::MAX, &mut relations,
+            &mut LargePrimeGraph::default(), usize::MAX);
+        assert!(!relations.is_empty());
+
+        for relation in relations.iter() {
+            let mut q = relation.large_primes.iter().fold(BigUint::one(), |q, &p| q * BigUint::from(p).pow(2));
+            let mut negative = false;
+            for &c in relation.columns.iter() {
+                match c {
+                    0 => negative = !negative,
+                    c => q *= BigUint::from(factor_base[c - 1].p),
+                }
+            }
+            let q = if negative { (n.clone() - q % &n) % &n } else { q % &n };
+            assert_eq!(q, &relation.y * &relation.y % &n);
+        }
+    }
+
+    #[test]
+    fn test_find_dependencies() {
+        // columns: 0 = -1, 1 = 2, 2 = 3, 3 = 5
+        let relation = |columns: Vec<usize>| Relation { y: BigUint::one(), columns, large_primes: vec![] };
+        let relations = vec![
+            relation(vec![1, 2]),
+            relation(vec![2, 3]),
+            relation(vec![1, 1, 3]),
+            relation(vec![0, 3]),
+            relation(vec![0, 1, 2]),
+        ];
+
+        let dependencies = find_dependencies(&relations, 4, &mut rand::thread_rng());
+        assert!(!dependencies.is_empty());
+        for dependency in dependencies {
+            let mut parity = [0; 4];
+            for r in dependency {
+                for &c in relations[r].columns.iter() {
+                    parity[c] ^= 1;
+                }
+            }
+            assert_eq!([0; 4], parity);
+        }
+    }
+
+    /// Asserts that each dependency is a non-empty set of rows summing to zero.
+    fn assert_dependencies(rows: &[Vec<usize>], columns: usize, dependencies: &[Vec<usize>]) {
+        for dependency in dependencies {
+            assert!(!dependency.is_empty());
+            let mut parity = vec![0; columns];
+            for &r in dependency {
+                for &c in rows[r].iter() {
+                    parity[c] ^= 1;
+                }
+            }
+            assert_eq!(vec![0; columns], parity);
+        }
+    }
+
+    /// A random sparse matrix shaped like a sieve's: about 20 entries per
+    /// row, most of them in the first few columns.
+    fn random_rows(rows: usize, columns: usize) -> Vec<Vec<usize>> {
+        let mut rng = rand::thread_rng();
+        (0..rows)
+            .map(|_| {
+                let mut row: Vec<usize> = (0..20)
+                    .map(|_| (columns as f64).powf(rng.gen::<f64>()) as usize)
+                    .collect();
+                row.sort_unstable();
+                sum_mod_2(&row, &[])
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_block_lanczos() {
+        let (rows, columns) = (1000, 950);
+        let matrix = random_rows(rows, columns);
+        let dependencies = (0..LANCZOS_ATTEMPTS)
+            .find_map(|_| block_lanczos(&matrix, columns, &mut rand::thread_rng()))
+            .unwrap();
+        assert!(dependencies.len() >= 32);
+        assert_dependencies(&matrix, columns, &dependencies);
+    }
+
+    #[test]
+    fn test_structured_elimination() {
+        // column 3 is a singleton, so row 2 goes; merging pairs then leaves
+        // rows 0, 3 and 4, which sum to zero
+        let rows = [vec![0, 1], vec![1, 2], vec![0, 2, 3], vec![0, 4], vec![1, 4], vec![0, 1, 2]];
+        let rows = rows.iter()
+            .enumerate()
+            .map(|(r, columns)| MatrixRow { columns: columns.clone(), relations: vec![r] })
+            .collect();
+        let (rows, columns) = structured_elimination(rows, 5);
+        assert_eq!(0, columns);
+        assert_eq!(1, rows.len());
+        let mut relations = rows[0].relations.clone();
+        relations.sort_unstable();
+        assert_eq!(vec![0, 3, 4], relations);
+
+        // on a sieve-like matrix, every dependency of what is left is one
+        // of the original
+        let (count, columns) = (600, 500);
+        let matrix = random_rows(count, columns);
+        let rows = matrix.iter()
+            .enumerate()
+            .map(|(r, columns)| MatrixRow { columns: columns.clone(), relations: vec![r] })
+            .collect();
+        let (rows, reduced_columns) = structured_elimination(rows, columns);
+        assert!(rows.len() < count && reduced_columns < columns);
+        let reduced: Vec<Vec<usize>> = rows.iter().map(|row| row.columns.clone()).collect();
+        let dependencies: Vec<Vec<usize>> = dense_dependencies(&reduced, reduced_columns)
+            .iter()
+            .map(|dependency| dependency.iter().flat_map(|&i| rows[i].relations.clone()).collect())
+            .collect();
+        assert!(!dependencies.is_empty());
+        assert_dependencies(&matrix, columns, &dependencies);
+    }
+
+    #[test]
+    fn test_large_prime_graph() {
+        // each y is the product of the relation's large primes, so around a
+        // cycle it is the product of their squares
+        let n = BigUint::from(1000000007u64);
+        let mut graph = LargePrimeGraph::default();
+        let mut add = |column: usize, p: u64, q: u64| {
+            let relation = Relation { y: BigUint::from(p * q), columns: vec![column], large_primes: vec![] };
+            graph.add(&n, relation, p, q).map(|mut relation| {
+                let y = relation.large_primes.iter().fold(BigUint::one(), |y, &p| y * BigUint::from(p * p));
+                assert_eq!(y % &n, relation.y);
+                relation.large_primes.sort_unstable();
+                relation.columns.sort_unstable();
+                (relation.large_primes, relation.columns)
+            })
+        };
+
+        // two trees, then an edge joining them
+        assert_eq!(None, add(0, 1, 101));
+        assert_eq!(None, add(1, 101, 103));
+        assert_eq!(None, add(2, 107, 109));
+        assert_eq!(None, add(3, 109, 113));
+        assert_eq!(None, add(4, 113, 103));
+
+        // a cycle through 1
+        assert_eq!(Some((vec![101, 103, 107, 109, 113], vec![0, 1, 2, 3, 4, 5])), add(5, 107, 1));
+        // a cycle away from 1, the edges between 101 and 109 being 1, 4, 3
+        assert_eq!(Some((vec![101, 103, 109, 113], vec![1, 3, 4, 6])), add(6, 109, 101));
+        // a single large prime seen twice
+        assert_eq!(None, add(7, 1, 127));
+        assert_eq!(Some((vec![127], vec![7, 8])), add(8, 127, 1));
+        // a square cofactor
+        assert_eq!(Some((vec![131], vec![9])), add(9, 131, 131));
+    }
+
+    #[test]
+    fn test_quadratic_sieve() {
+        assert_splits(1000003 * 1000033);
+        assert_splits(4294967291 * 4294967311);
+        assert_splits(1000000000000000003 * 1000000000000000009);
+    }
+
+    #[test]
+    fn test_quadratic_sieve_gives_up_on_prime_powers() {
+        // every dependency is trivial, which used to loop forever
+        let n = 1000003u128.pow(3);
+        assert_eq!(None, QuadraticSieve::for_bits(u128::BITS - n.leading_zeros()).factor(&n));
+    }
+
+    #[test]
+    fn test_quadratic_sieve_full_u128() {
+        // two 64-bit primes
+        assert_splits(18446744073709551557 * 18446744073709551533);
+    }
+
+    #[test]
+    fn test_quadratic_sieve_big_integers() {
+        // 42 digits, beyond u128
+        let p: BigUint = "100000000000000000039".parse().unwrap();
+        let q: BigUint = "1000000000000000000117".parse().unwrap();
+        let n = &p * &q;
+
+        let d = QuadraticSieve::for_bits(n.bits()).factor(&n).expect("QS did not split n");
+        assert!(d == p || d == q);
+    }
+
+    #[test]
+    #[ignore]
+    fn test_quadratic_sieve_60_digits() {
+        // about 15 seconds in release mode
+        let p: BigUint = "377887688554896545928674084063".parse().unwrap();
+        let q: BigUint = "609725926018754320960493015363".parse().unwrap();
+        let n = &p * &q;
+
+        let d = QuadraticSieve::for_bits(n.bits()).factor(&n).expect("QS did not split n");
+        assert!(d == p || d == q);
+    }
+
+    #[test]
+    #[ignore]
+    fn test_quadratic_sieve_80_digits() {
+        // about 20 minutes in release mode
+        let p: BigUint = "6833887697840518712890652023605473820359".parse().unwrap();
+        let q: BigUint = "7322990884776430073869978039590206290757".parse().unwrap();
+        let n = &p * &q;
+
+        let d = QuadraticSieve::for_bits(n.bits()).factor(&n).expect("QS did not split n");
+        assert!(d == p || d == q);
+    }
+}
@@ -0,0 +1,9 @@
+/// Decodes a string of hex digit pairs, as test vectors are written.
+pub fn bytes(hex: &str) -> Vec<u8> {
+    (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap()).collect()
+}
+
+/// Encodes bytes as lowercase hex, for comparing against test vectors.
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -0,0 +1,323 @@
+use std::collections::HashSet;
+
+use super::integer::{chinese_remainder, gcd, prime_factorize, UnsignedInteger};
+use super::modular::Residue;
+
+/// The structure of the unit group (Z/nZ)^*, as a product of cyclic groups
+/// Z/d_1 x Z/d_2 x ... x Z/d_r with each d_i dividing the next.
+///
+/// By the Chinese remainder theorem (Z/nZ)^* is the product of the unit
+/// groups modulo the prime powers p^k dividing n. For odd p those are cyclic
+/// of order p^{k-1}(p - 1), generated by a primitive root mod p^k. Powers of
+/// two are the special case: mod 2 the group is trivial, mod 4 it is {1, 3},
+/// and for k >= 3 it is Z/2 x Z/2^{k-2}, generated by -1 and 5.
+///
+/// Splitting each of those cyclic factors into its prime-power parts and
+/// regrouping them, largest with largest, gives the invariant factors: d_r
+/// is the exponent of the group, Carmichael's lambda(n), and n has a
+/// primitive root exactly when there is at most one factor.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnitGroup<T = u128> {
+    pub modulus: T,
+    /// d_1 | d_2 | ... | d_r, all greater than 1; empty for n = 1, 2.
+    pub invariant_factors: Vec<T>,
+    /// g_i of order d_i, such that every unit is g_1^{e_1} ... g_r^{e_r}
+    /// for exactly one choice of 0 <= e_i < d_i.
+    pub generators: Vec<Residue<T>>,
+}
+
+impl<T: UnsignedInteger> UnitGroup<T> {
+    /// Returns the invariant-factor decomposition of (Z/nZ)^*, with
+    /// generators, for n >= 1.
+    pub fn new(modulus: T) -> UnitGroup<T> {
+        assert!(!modulus.is_zero(), "The modulus must be positive.");
+
+        // the cyclic factors for the prime powers, lifted to units mod n
+        let mut cyclic_factors = vec![];
+        for (p, k) in prime_factorize(modulus.clone()) {
+            let prime_power = (0..k).fold(T::one(), |power, _| power * p.clone());
+            let lift = |g: T| Residue::from_unsigned_integer(
+                chinese_remainder(&g, &prime_power, &T::one(), &(modulus.clone() / prime_power.clone())),
+                modulus.clone());
+
+            if p != T::from(2) {
+                let root = Residue::primitive_roots(prime_power.clone()).unwrap().next().unwrap();
+                let order = prime_power.clone() / p.clone() * (p.clone() - T::one());
+                cyclic_factors.push((lift(root.value), order));
+            } else if k >= 2 {
+                cyclic_factors.push((lift(prime_power.clone() - T::one()), T::from(2)));
+                if k >= 3 {
+                    cyclic_factors.push((lift(T::from(5)), prime_power.clone() >> 2));
+                }
+            }
+        }
+
+        // split each into cyclic groups of prime power order, by prime:
+        // g of order m = q^e s with q not dividing s gives g^s of order q^e
+        let mut components: Vec<(T, Vec<_>)> = vec![];
+        for (g, order) in cyclic_factors {
+            for (q, e) in prime_factorize(order.clone()) {
+                let q_power = (0..e).fold(T::one(), |power, _| power * q.clone());
+                let element = g.pow_unsigned(&(order.clone() / q_power.clone()));
+                match components.iter_mut().find(|(prime, _)| *prime == q) {
+                    Some((_, list)) => list.push((element, q_power)),
+                    None => components.push((q, vec![(element, q_power)])),
+                }
+            }
+        }
+
+        // the largest components of each prime combine into the largest
+        // invariant factor, the second largest into the next, and so on
+        for (_, list) in components.iter_mut() {
+            list.sort_by(|a, b| b.1.cmp(&a.1));
+        }
+        let rank = components.iter().map(|(_, list)| list.len()).max().unwrap_or(0);
+        let mut invariant_factors = vec![];
+        let mut generators = vec![];
+        for i in (0..rank).rev() {
+            let mut generator = Residue::from_unsigned_integer(T::one(), modulus.clone());
+            let mut factor = T::one();
+            for (_, list) in components.iter() {
+                if let Some((element, order)) = list.get(i) {
+                    generator = generator.times(element);
+                    factor = factor * order.clone();
+                }
+            }
+            invariant_factors.push(factor);
+            generators.push(generator);
+        }
+
+        UnitGroup { modulus, invariant_factors, generators }
+    }
+
+    /// Returns the order of the group, phi(n).
+    pub fn order(&self) -> T {
+        self.invariant_factors.iter().fold(T::one(), |order, d| order * d.clone())
+    }
+
+    /// Returns the exponent of the group, the largest order of any element,
+    /// which is Carmichael's lambda(n).
+    pub fn exponent(&self) -> T {
+        self.invariant_factors.last().cloned().unwrap_or_else(T::one)
+    }
+
+    /// Returns whether the group is cyclic, i.e. n has a primitive root.
+    pub fn is_cyclic(&self) -> bool {
+        self.invariant_factors.len() <= 1
+    }
+
+    /// Returns g_1^{e_1} ... g_r^{e_r} for the given exponents.
+    pub fn element(&self, exponents: &[T]) -> Residue<T> {
+        assert_eq!(self.generators.len(), exponents.len(), "Need one exponent per generator.");
+        self.generators.iter()
+            .zip(exponents.iter())
+            .fold(
+                Residue::from_unsigned_integer(T::one(), self.modulus.clone()),
+                |product, (g, e)| product.times(&g.pow_unsigned(e)))
+    }
+}
+
+/// Returns the units mod n in increasing order, for n >= 1.
+///
+/// Mod 1 the only residue is 0, which counts as a unit since 0 = 1 there.
+pub fn units<T: UnsignedInteger>(modulus: T) -> impl Iterator<Item = Residue<T>> {
+    let mut value = T::zero();
+    std::iter::from_fn(move || {
+        while value < modulus {
+            let candidate = value.clone();
+            value = value.clone() + T::one();
+            if gcd(candidate.clone(), modulus.clone()) == T::one() {
+                return Some(Residue::from_unsigned_integer(candidate, modulus.clone()));
+            }
+        }
+        None
+    })
+}
+
+/// A subgroup of (Z/nZ)^* within a `subgroup_lattice`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Subgroup<T = u128> {
+    /// The values of the elements, in increasing order.
+    pub elements: Vec<T>,
+    /// The positions in the lattice of the maximal proper subgroups of this
+    /// one, i.e. the edges below it in the Hasse diagram.
+    pub maximal_subgroups: Vec<usize>,
+}
+
+/// Returns every subgroup of (Z/nZ)^*, ordered by size and then by elements,
+/// with the containments between them.
+///
+/// Brute force: starts from the cyclic subgroups, one per unit, and adds
+/// the join HK of any two subgroups found so far (in an abelian group that
+/// product set is already a subgroup) until no more appear. This is only
+/// meant for n up to a few hundred.
+pub fn subgroup_lattice<T: UnsignedInteger>(modulus: T) -> Vec<Subgroup<T>> {
+    let mut subgroups: Vec<Vec<T>> = vec![];
+    let mut seen = HashSet::new();
+    let mut add = |subgroups: &mut Vec<Vec<T>>, mut elements: Vec<T>| {
+        elements.sort();
+        if seen.insert(elements.clone()) {
+            subgroups.push(elements);
+        }
+    };
+
+    let one = Residue::from_unsigned_integer(T::one(), modulus.clone());
+    for unit in units(modulus.clone()) {
+        let mut elements = vec![one.value.clone()];
+        let mut element = unit.clone();
+        while element != one {
+            elements.push(element.value.clone());
+            element = element.times(&unit);
+        }
+        add(&mut subgroups, elements);
+    }
+
+    // joins of pairs, until closed under them
+    let mut checked = 0;
+    while checked < subgroups.len() {
+        for i in 0..checked {
+            let mut product = HashSet::new();
+            for h in subgroups[i].iter() {
+                for k in subgroups[checked].iter() {
+                    product.insert(h.mul_mod(k, &modulus));
+                }
+            }
+            add(&mut subgroups, product.into_iter().collect());
+        }
+        checked += 1;
+    }
+
+    subgroups.sort_by(|a, b| a.len().cmp(&b.len()).then_with(|| a.cmp(b)));
+
+    // H < K is maximal when no L has H < L < K
+    let sets: Vec<HashSet<&T>> = subgroups.iter().map(|elements| elements.iter().collect()).collect();
+    let contains = |k: usize, h: usize| {
+        sets[k].len() > sets[h].len() && sets[k].len().is_multiple_of(sets[h].len()) && sets[h].is_subset(&sets[k])
+    };
+    (0..subgroups.len())
+        .map(|k| {
+            let below: Vec<usize> = (0..k).filter(|&h| contains(k, h)).collect();
+            let maximal_subgroups = below.iter()
+                .copied()
+                .filter(|&h| !below.iter().any(|&l| contains(l, h)))
+                .collect();
+            Subgroup { elements: subgroups[k].clone(), maximal_subgroups }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bigint::BigUint;
+    use crate::integer::{carmichael_lambda, euler_totient};
+
+    #[test]
+    fn test_invariant_factors() {
+        let factors = |n: u128| UnitGroup::new(n).invariant_factors;
+        assert_eq!(Vec::<u128>::new(), factors(1));
+        assert_eq!(Vec::<u128>::new(), factors(2));
+        assert_eq!(vec![2], factors(4));
+        assert_eq!(vec![2, 2], factors(8));
+        assert_eq!(vec![2, 4], factors(15));
+        assert_eq!(vec![2, 4], factors(16));
+        assert_eq!(vec![6], factors(9));
+        assert_eq!(vec![6], factors(18));
+        assert_eq!(vec![2, 2, 2], factors(24));
+        assert_eq!(vec![6, 6], factors(63));
+        // 2^5 * 3^2 * 5 * 7: Z/2 x Z/8, Z/6, Z/4 and Z/6 regroup as
+        // Z/2 x Z/2 x Z/2 x Z/12 x Z/24
+        assert_eq!(vec![2, 2, 2, 12, 24], factors(32 * 9 * 5 * 7));
+
+        for n in 1..500u128 {
+            let group = UnitGroup::new(n);
+            assert_eq!(euler_totient(n), group.order(), "{}", n);
+            assert_eq!(carmichael_lambda(n), group.exponent(), "{}", n);
+            assert_eq!(Residue::has_primitive_root(&n), group.is_cyclic(), "{}", n);
+            for pair in group.invariant_factors.windows(2) {
+                assert!(pair[1].is_multiple_of(pair[0]), "{}", n);
+            }
+            for (g, d) in group.generators.iter().zip(group.invariant_factors.iter()) {
+                assert_eq!(Some(*d), g.order(), "{}", n);
+            }
+        }
+    }
+
+    #[test]
+    fn test_generators_give_every_unit_once() {
+        for n in [15u128, 16, 63, 105, 720, 2 * 3 * 5 * 7 * 11] {
+            let group = UnitGroup::new(n);
+            let mut seen = HashSet::new();
+            let mut exponents = vec![0u128; group.generators.len()];
+            loop {
+                assert!(seen.insert(group.element(&exponents).value), "{}", n);
+
+                // count through all exponent vectors like an odometer
+                let mut i = 0;
+                while i < exponents.len() && exponents[i] + 1 == group.invariant_factors[i] {
+                    exponents[i] = 0;
+                    i += 1;
+                }
+                if i == exponents.len() {
+                    break;
+                }
+                exponents[i] += 1;
+            }
+
+            let units: HashSet<u128> = units(n).map(|unit| unit.value).collect();
+            assert_eq!(units, seen);
+        }
+    }
+
+    #[test]
+    fn test_unit_group_big_integers() {
+        // (2^89 - 1)^2 * 2^70
+        let p = (BigUint::from(1u64) << 89) - BigUint::from(1u64);
+        let two_power = BigUint::from(1u64) << 70;
+        let group = UnitGroup::new(p.clone() * p.clone() * two_power.clone());
+        assert_eq!(3, group.invariant_factors.len());
+        assert_eq!(BigUint::from(2u64), group.invariant_factors[0]);
+        assert_eq!(
+            euler_totient(p.clone() * p.clone()) * (two_power.clone() >> 1),
+            group.order());
+        assert_eq!(Some(group.exponent()), group.generators[2].order());
+    }
+
+    #[test]
+    fn test_units() {
+        let values = |n: u128| units(n).map(|unit| unit.value).collect::<Vec<u128>>();
+        assert_eq!(vec![0], values(1));
+        assert_eq!(vec![1], values(2));
+        assert_eq!(vec![1, 5, 7, 11], values(12));
+        assert_eq!((1..97).collect::<Vec<u128>>(), values(97));
+        assert_eq!(euler_totient(1000u128) as usize, units(1000u128).count());
+    }
+
+    #[test]
+    fn test_subgroup_lattice() {
+        // Z/6 has one subgroup per divisor of 6, forming a diamond
+        let lattice = subgroup_lattice(7u128);
+        let elements: Vec<Vec<u128>> = lattice.iter().map(|h| h.elements.clone()).collect();
+        assert_eq!(vec![vec![1], vec![1, 6], vec![1, 2, 4], vec![1, 2, 3, 4, 5, 6]], elements);
+        let maximal: Vec<Vec<usize>> = lattice.iter().map(|h| h.maximal_subgroups.clone()).collect();
+        assert_eq!(vec![vec![], vec![0], vec![0], vec![1, 2]], maximal);
+
+        // Z/2 x Z/2 has three subgroups of order 2
+        let lattice = subgroup_lattice(8u128);
+        assert_eq!(5, lattice.len());
+        assert_eq!(vec![0], lattice[2].maximal_subgroups);
+        assert_eq!(vec![1, 2, 3], lattice[4].maximal_subgroups);
+
+        // Z/2 x Z/4 has 8 subgroups, and Z/2 x Z/2 x Z/2 has 16
+        assert_eq!(8, subgroup_lattice(15u128).len());
+        assert_eq!(16, subgroup_lattice(24u128).len());
+        assert_eq!(1, subgroup_lattice(2u128).len());
+
+        // a cyclic group of order m has one subgroup for each divisor of m
+        let lattice = subgroup_lattice(2 * 3u128.pow(4));
+        assert_eq!(8, lattice.len());
+        for subgroup in lattice.iter() {
+            assert!(54usize.is_multiple_of(subgroup.elements.len()));
+        }
+    }
+}